@@ -4,8 +4,11 @@
 
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
-use galdi_core::ChecksumAlgorithm;
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Parser, Subcommand, ValueEnum};
+use galdi_core::{
+    ChecksumAlgorithm, Compression, DEFAULT_SAMPLE_LEN, DocumentKind, Encoding, parse_label,
+};
 
 /// Unified galdi tool for filesystem snapshots and diffs
 #[derive(Parser, Debug)]
@@ -26,13 +29,46 @@ pub enum Command {
     Snapshot(SnapshotArgs),
     /// Compare two snapshots or directories
     Diff(DiffArgs),
+    /// Check a live tree against a stored snapshot
+    Verify(VerifyArgs),
+    /// Write a stored snapshot in another tool's format
+    Export(ExportArgs),
+    /// Generate an Ed25519 key pair for signing
+    Keygen(KeygenArgs),
+    /// Sign a snapshot or diff report
+    Sign(SignArgs),
+    /// Check the signature of a snapshot or diff report
+    VerifySignature(VerifySignatureArgs),
+    /// Rewrite stored snapshots in the current format version
+    Migrate(MigrateArgs),
+    /// Print the JSON Schema of a kind of document
+    Schema(SchemaArgs),
+    /// Check a document against its JSON Schema
+    Validate(ValidateArgs),
+    /// Merge, subtract, intersect or filter stored snapshots
+    SnapshotOps(SnapshotOpsArgs),
+    /// Keep snapshots in a local store, with tags and retention
+    Store(StoreArgs),
+    /// Show when a path appeared, disappeared or changed across snapshots
+    History(HistoryArgs),
 }
 
 /// Arguments for the snapshot subcommand
 #[derive(Parser, Debug)]
 pub struct SnapshotArgs {
-    /// Directory to snapshot (required).
-    pub path: PathBuf,
+    /// Directory to snapshot, or a git object such as `git:HEAD` or `git:v1.2.0:subdir`
+    /// (required unless `--from-tar` is given).
+    #[arg(required_unless_present = "from_tar")]
+    pub path: Option<PathBuf>,
+
+    /// Build the snapshot from a tar stream instead of a directory (`-` for stdin).
+    /// Nothing is extracted to disk.
+    #[arg(long, value_name = "TAR", conflicts_with = "path")]
+    pub from_tar: Option<PathBuf>,
+
+    /// Repository that a `git:` path is read from; the current directory's if omitted.
+    #[arg(long, value_name = "DIR")]
+    pub repo: Option<PathBuf>,
 
     /// Write snapshot to this file; stdout if omitted.
     #[arg(short, long)]
@@ -43,6 +79,30 @@ pub struct SnapshotArgs {
     #[arg(long, default_value = "xxh3_64")]
     pub checksum: ChecksumAlgorithm,
 
+    /// Additional checksum algorithms computed in the same read, e.g.
+    /// `--extra-checksum sha256`. Stored per entry under `checksums`.
+    #[arg(long, value_name = "ALGORITHM", value_delimiter = ',')]
+    pub extra_checksum: Vec<ChecksumAlgorithm>,
+
+    /// Bytes hashed from each of the start, middle and end of a file by
+    /// sampled algorithms (`xxh3_64s`).
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_SAMPLE_LEN,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    pub sample_len: u64,
+
+    /// Read the key for keyed checksums (`blake3_keyed`, `hmac_sha256`) from this file.
+    #[arg(long, value_name = "PATH", conflicts_with = "key_env")]
+    pub key_file: Option<PathBuf>,
+
+    /// Read the key for keyed checksums from this environment variable.
+    #[arg(long, value_name = "VAR")]
+    pub key_env: Option<String>,
+
+    /// Reuse checksums cached in `user.galdi.<algorithm>` extended attributes
+    /// while size and mtime are unchanged, and cache fresh ones.
+    #[arg(long)]
+    pub xattr_cache: bool,
+
     /// Follow symbolic links when scanning.
     #[arg(long)]
     pub follow_symlinks: bool,
@@ -72,6 +132,21 @@ pub struct SnapshotArgs {
     #[arg(long)]
     pub jsonl: bool,
 
+    /// Encode the snapshot as `json`, `cbor` or `msgpack`. Readers recognize
+    /// the encoding by itself.
+    #[arg(long, default_value = "json", conflicts_with_all = ["jsonl", "human"])]
+    pub format: Encoding,
+
+    /// Compress the snapshot with `zstd` or `gzip`.
+    #[arg(long, default_value = "none", conflicts_with_all = ["jsonl", "human"])]
+    pub compress: Compression,
+
+    /// Run this jq expression over the output, in-process: over the whole
+    /// snapshot, or over each entry line with `--jsonl`. Error documents are
+    /// never filtered.
+    #[arg(long, value_name = "EXPR", conflicts_with_all = ["human", "format", "compress"])]
+    pub filter: Option<String>,
+
     /// Number of threads for parallel scanning (default: auto-detect).
     #[arg(long)]
     pub threads: Option<usize>,
@@ -80,6 +155,16 @@ pub struct SnapshotArgs {
     #[arg(long)]
     pub normalize_paths: bool,
 
+    /// Record a `key=value` label in `$plumbah.meta.scan_provenance`,
+    /// alongside the host, user, command line and scan options. May be repeated.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_label)]
+    pub label: Vec<(String, String)>,
+
+    /// Leave the hostname, username and command line out of the provenance,
+    /// for snapshots that are shared.
+    #[arg(long)]
+    pub redact: bool,
+
     /// Return only Plumbah metadata (dry-run for semantic introspection).
     #[arg(long)]
     pub plumbah_info: bool,
@@ -95,10 +180,35 @@ pub struct DiffArgs {
     #[arg(short, long)]
     pub output: Option<PathBuf>,
 
-    /// Checksum algorithm to use for file content.
-    /// Defaults to `xxh3_64`. See `galdi_core::ChecksumAlgorithm`.
-    #[arg(long, default_value = "xxh3_64")]
-    pub checksum: ChecksumAlgorithm,
+    /// Checksum algorithm for sides that are scanned rather than loaded.
+    /// Defaults to the algorithm of the other side when that is a stored
+    /// snapshot, otherwise `xxh3_64`. See `galdi_core::ChecksumAlgorithm`.
+    #[arg(long)]
+    pub checksum: Option<ChecksumAlgorithm>,
+
+    /// Bytes per sample of sampled algorithms (`xxh3_64s`) for sides that are
+    /// scanned. Defaults to what the other side recorded when that is a stored
+    /// snapshot, otherwise 64 KiB.
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub sample_len: Option<u64>,
+
+    /// Read the key for keyed checksums (`blake3_keyed`, `hmac_sha256`) from this file.
+    #[arg(long, value_name = "PATH", conflicts_with = "key_env")]
+    pub key_file: Option<PathBuf>,
+
+    /// Read the key for keyed checksums from this environment variable.
+    #[arg(long, value_name = "VAR")]
+    pub key_env: Option<String>,
+
+    /// Reuse checksums cached in `user.galdi.<algorithm>` extended attributes
+    /// while size and mtime are unchanged, and cache fresh ones.
+    #[arg(long)]
+    pub xattr_cache: bool,
+
+    /// Only accept stored snapshots signed with the Ed25519 public key in this
+    /// file, embedded or as a detached `<snapshot>.sig`.
+    #[arg(long, value_name = "PUBLIC_KEY")]
+    pub require_signature: Option<PathBuf>,
 
     /// Follow symbolic links when scanning.
     #[arg(long)]
@@ -122,6 +232,19 @@ pub struct DiffArgs {
     #[arg(long)]
     pub human: bool,
 
+    /// Encode the report as `json`, `cbor` or `msgpack`.
+    #[arg(long, default_value = "json", conflicts_with = "human")]
+    pub format: Encoding,
+
+    /// Compress the report with `zstd` or `gzip`.
+    #[arg(long, default_value = "none", conflicts_with = "human")]
+    pub compress: Compression,
+
+    /// Run this jq expression over the report, in-process. Error documents
+    /// are never filtered.
+    #[arg(long, value_name = "EXPR", conflicts_with_all = ["human", "format", "compress"])]
+    pub filter: Option<String>,
+
     /// Ignore timestamp differences when comparing entries.
     #[arg(long)]
     pub ignore_time: bool,
@@ -134,6 +257,25 @@ pub struct DiffArgs {
     #[arg(long)]
     pub structure_only: bool,
 
+    /// Compare only this subtree of the source, re-rooted at it: e.g. `opt/app`
+    /// of a whole-host snapshot, to line up with a snapshot of `/opt/app`.
+    #[arg(long, value_name = "PATH")]
+    pub source_prefix: Option<PathBuf>,
+
+    /// Compare only this subtree of the target, re-rooted at it.
+    #[arg(long, value_name = "PATH")]
+    pub target_prefix: Option<PathBuf>,
+
+    /// Store that `@name` sides are loaded from; `$GALDI_STORE`, or else
+    /// `.galdi`, if omitted.
+    #[arg(long, value_name = "DIR")]
+    pub store: Option<PathBuf>,
+
+    /// Repository that `git:` sides are read from. Defaults to the one holding
+    /// the other side when that is a directory, otherwise the current one.
+    #[arg(long, value_name = "DIR")]
+    pub repo: Option<PathBuf>,
+
     /// Timeout in milliseconds for each snapshot operation.
     #[arg(long)]
     pub timeout_ms: Option<u64>,
@@ -147,13 +289,369 @@ pub struct DiffArgs {
     pub plumbah_info: bool,
 }
 
+/// Arguments for the verify subcommand
+#[derive(Parser, Debug)]
+pub struct VerifyArgs {
+    /// Stored snapshot to verify against (`-` for stdin).
+    pub snapshot: PathBuf,
+
+    /// Directory to verify; defaults to the snapshot's `root`.
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+
+    /// Ignore timestamp differences.
+    #[arg(long)]
+    pub ignore_time: bool,
+
+    /// Ignore permission mode differences.
+    #[arg(long)]
+    pub ignore_mode: bool,
+
+    /// Number of threads for parallel scanning (default: auto-detect).
+    #[arg(long)]
+    pub threads: Option<usize>,
+
+    /// Follow symbolic links when scanning. Defaults to the setting recorded
+    /// in the snapshot's provenance.
+    #[arg(long, conflicts_with = "no_follow_symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Do not follow symbolic links, even if the snapshot was taken following them.
+    #[arg(long)]
+    pub no_follow_symlinks: bool,
+
+    /// Maximum recursion depth for the scan. Defaults to the depth recorded
+    /// in the snapshot's provenance, or unlimited.
+    #[arg(long)]
+    pub max_depth: Option<usize>,
+
+    /// Exclude patterns, with the syntax of `galdi snapshot --exclude`.
+    /// Default to the patterns recorded in the snapshot's provenance.
+    #[arg(long)]
+    pub exclude: Vec<String>,
+
+    /// Read the key for keyed checksums (`blake3_keyed`, `hmac_sha256`) from this file.
+    #[arg(long, value_name = "PATH", conflicts_with = "key_env")]
+    pub key_file: Option<PathBuf>,
+
+    /// Read the key for keyed checksums from this environment variable.
+    #[arg(long, value_name = "VAR")]
+    pub key_env: Option<String>,
+
+    /// Only accept a snapshot signed with the Ed25519 public key in this
+    /// file, embedded or as a detached `<snapshot>.sig`.
+    #[arg(long, value_name = "PUBLIC_KEY")]
+    pub require_signature: Option<PathBuf>,
+
+    /// Print one line per failure and a summary instead of JSON.
+    #[arg(long)]
+    pub human: bool,
+}
+
+/// Arguments for the export subcommand
+#[derive(Parser, Debug)]
+pub struct ExportArgs {
+    /// Stored snapshot to export (`-` for stdin), or diff report with `--format sqlite`.
+    pub snapshot: PathBuf,
+
+    /// Output format.
+    #[arg(long)]
+    pub format: ExportFormat,
+
+    /// Write BSD-style `SHA256 (path) = hash` lines, like `sha256sum --tag`.
+    #[arg(long)]
+    pub tag: bool,
+
+    /// Name to record the document under in a SQLite database, e.g. a release.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Write to this file; stdout if omitted. SQLite databases are appended to.
+    #[arg(short, long, required_if_eq("format", "sqlite"))]
+    pub output: Option<PathBuf>,
+}
+
+/// Arguments for the keygen subcommand
+#[derive(Parser, Debug)]
+pub struct KeygenArgs {
+    /// Write the secret key to this file and the public key to `<PATH>.pub`.
+    pub path: PathBuf,
+}
+
+/// Arguments for the sign subcommand
+#[derive(Parser, Debug)]
+pub struct SignArgs {
+    /// Snapshot or diff report JSON to sign (`-` for stdin).
+    pub document: PathBuf,
+
+    /// Secret key file written by `galdi keygen`.
+    #[arg(long, value_name = "PATH")]
+    pub key: PathBuf,
+
+    /// Write only the signature, to be kept next to the document as `<document>.sig`.
+    /// Otherwise the signed document is written with the signature embedded.
+    #[arg(long)]
+    pub detached: bool,
+
+    /// Write to this file; stdout if omitted.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Arguments for the verify-signature subcommand
+#[derive(Parser, Debug)]
+pub struct VerifySignatureArgs {
+    /// Snapshot or diff report JSON to check (`-` for stdin).
+    pub document: PathBuf,
+
+    /// Public key file of the trusted signer.
+    #[arg(long, value_name = "PATH")]
+    pub public_key: PathBuf,
+
+    /// Detached signature file. Defaults to `<document>.sig` when it exists,
+    /// otherwise the embedded signature is checked.
+    #[arg(long, value_name = "PATH")]
+    pub signature: Option<PathBuf>,
+}
+
+/// Arguments for the migrate subcommand
+#[derive(Parser, Debug)]
+pub struct MigrateArgs {
+    /// Stored snapshots to upgrade in place, keeping their encoding and compression.
+    #[arg(required = true)]
+    pub snapshots: Vec<PathBuf>,
+
+    /// Report what would be migrated without rewriting anything.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Arguments for the schema subcommand
+#[derive(Parser, Debug)]
+pub struct SchemaArgs {
+    /// Kind of document to print the schema of.
+    #[arg(value_parser = document_kind())]
+    pub kind: DocumentKind,
+}
+
+/// Arguments for the validate subcommand
+#[derive(Parser, Debug)]
+pub struct ValidateArgs {
+    /// Document to check (`-` for stdin), in any encoding and compression
+    /// galdi writes. JSONL snapshot streams are checked line by line.
+    pub document: PathBuf,
+
+    /// Kind of document; detected from its top-level fields if omitted.
+    #[arg(long, value_parser = document_kind())]
+    pub kind: Option<DocumentKind>,
+}
+
+/// Arguments for the snapshot-ops subcommand
+#[derive(Parser, Debug)]
+pub struct SnapshotOpsArgs {
+    #[command(subcommand)]
+    pub operation: SnapshotOperation,
+
+    /// Write the resulting snapshot to this file; stdout if omitted.
+    #[arg(short, long, global = true)]
+    pub output: Option<PathBuf>,
+
+    /// Encode the resulting snapshot as `json`, `cbor` or `msgpack`.
+    #[arg(long, default_value = "json", global = true)]
+    pub format: Encoding,
+
+    /// Compress the resulting snapshot with `zstd` or `gzip`.
+    #[arg(long, default_value = "none", global = true)]
+    pub compress: Compression,
+}
+
+/// Operations of `galdi snapshot-ops`. Inputs are stored snapshots (`-` for stdin).
+#[derive(Subcommand, Debug)]
+pub enum SnapshotOperation {
+    /// Combine snapshots of disjoint subtrees, e.g. shards of one scan
+    Merge {
+        #[arg(required = true, num_args = 2..)]
+        snapshots: Vec<PathBuf>,
+    },
+    /// Keep the entries that the baseline does not record identically
+    Subtract {
+        snapshot: PathBuf,
+        baseline: PathBuf,
+    },
+    /// Keep the entries whose paths the other snapshot records as well
+    Intersect { snapshot: PathBuf, other: PathBuf },
+    /// Extract a subtree, re-rooted at it
+    Subtree {
+        snapshot: PathBuf,
+
+        /// Directory to extract, relative to the snapshot root or absolute.
+        prefix: PathBuf,
+    },
+    /// Keep the entries selected by glob patterns
+    Filter {
+        snapshot: PathBuf,
+
+        /// Keep only files matching one of these patterns.
+        #[arg(long)]
+        include: Vec<String>,
+
+        /// Drop entries matching these patterns, and everything beneath
+        /// matching directories. Same syntax as `galdi snapshot --exclude`.
+        #[arg(long)]
+        exclude: Vec<String>,
+    },
+}
+
+/// Arguments for the store subcommand
+#[derive(Parser, Debug)]
+pub struct StoreArgs {
+    #[command(subcommand)]
+    pub command: StoreCommand,
+
+    /// Store directory; `$GALDI_STORE`, or else `.galdi`, if omitted.
+    #[arg(long, value_name = "DIR", global = true)]
+    pub store: Option<PathBuf>,
+}
+
+/// Commands of `galdi store`. Snapshots are referred to by tag, by a unique
+/// prefix of their id, or as `latest`, with or without a leading `@`.
+#[derive(Subcommand, Debug)]
+pub enum StoreCommand {
+    /// Add a stored snapshot (`-` for stdin)
+    Add {
+        snapshot: PathBuf,
+
+        /// Tag the snapshot, e.g. `--tag baseline`.
+        #[arg(long, value_name = "NAME")]
+        tag: Vec<String>,
+    },
+    /// List the stored snapshots, oldest first
+    List,
+    /// Print a stored snapshot
+    Show {
+        reference: String,
+
+        /// Write the snapshot to this file; stdout if omitted.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Encode the snapshot as `json`, `cbor` or `msgpack`.
+        #[arg(long, default_value = "json")]
+        format: Encoding,
+
+        /// Compress the snapshot with `zstd` or `gzip`.
+        #[arg(long, default_value = "none")]
+        compress: Compression,
+    },
+    /// Point a tag at a snapshot, moving it if it exists
+    Tag {
+        #[arg(required_unless_present = "delete")]
+        reference: Option<String>,
+
+        #[arg(required_unless_present = "delete")]
+        name: Option<String>,
+
+        /// Remove this tag instead, leaving its snapshot.
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["reference", "name"])]
+        delete: Option<String>,
+    },
+    /// Remove snapshots and their tags
+    Rm {
+        #[arg(required = true)]
+        references: Vec<String>,
+    },
+    /// Remove the snapshots a retention policy does not keep. Tagged
+    /// snapshots and the latest one are always kept.
+    Prune {
+        /// Keep the last N snapshots added.
+        #[arg(long, value_name = "N")]
+        keep_last: Option<usize>,
+
+        /// Keep the snapshots added within this long, e.g. `12h`, `30d` or `8w`.
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        keep_within: Option<chrono::Duration>,
+
+        /// Only report what would be removed.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Arguments for the history subcommand
+#[derive(Parser, Debug)]
+pub struct HistoryArgs {
+    /// Path to follow, relative to the snapshot roots or absolute beneath them.
+    pub path: PathBuf,
+
+    /// Stored snapshots, directories of them, or `@name`s from the store.
+    /// Every snapshot in the store if omitted.
+    pub snapshots: Vec<PathBuf>,
+
+    /// Store directory; `$GALDI_STORE`, or else `.galdi`, if omitted.
+    #[arg(long, value_name = "DIR")]
+    pub store: Option<PathBuf>,
+
+    /// Print one line per event instead of JSON.
+    #[arg(long)]
+    pub human: bool,
+}
+
+/// Parse an age such as `90m`, `12h`, `30d` or `8w`.
+fn parse_age(age: &str) -> Result<chrono::Duration, String> {
+    let split = age.len() - age.chars().last().map_or(0, char::len_utf8);
+    let (count, unit) = age.split_at(split);
+    let count: i64 = count.parse().map_err(|_| {
+        format!(
+            "expected a number followed by s, m, h, d or w, got `{}`",
+            age
+        )
+    })?;
+    match unit {
+        "s" => Ok(chrono::Duration::seconds(count)),
+        "m" => Ok(chrono::Duration::minutes(count)),
+        "h" => Ok(chrono::Duration::hours(count)),
+        "d" => Ok(chrono::Duration::days(count)),
+        "w" => Ok(chrono::Duration::weeks(count)),
+        _ => Err(format!("unknown unit in `{}`: use s, m, h, d or w", age)),
+    }
+}
+
+fn document_kind() -> impl TypedValueParser<Value = DocumentKind> {
+    PossibleValuesParser::new(DocumentKind::ALL.map(|kind| kind.name()))
+        .map(|name| name.parse::<DocumentKind>().expect("listed kinds parse"))
+}
+
+/// Formats `galdi export` can write.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// `sha256sum` manifest
+    Sha256sum,
+    /// `sha512sum` manifest
+    Sha512sum,
+    /// `sha1sum` manifest
+    Sha1sum,
+    /// `md5sum` manifest
+    Md5sum,
+    /// `b3sum` manifest
+    B3sum,
+    /// SQLite database of snapshots and diff reports
+    Sqlite,
+}
+
 // Conversion from facade args to library args
 impl From<SnapshotArgs> for galdi_snapshot::cli::ToolArgs {
     fn from(args: SnapshotArgs) -> Self {
         galdi_snapshot::cli::ToolArgs {
             path: args.path,
+            from_tar: args.from_tar,
+            repo: args.repo,
             output: args.output,
             checksum: args.checksum,
+            extra_checksum: args.extra_checksum,
+            sample_len: args.sample_len,
+            key_file: args.key_file,
+            key_env: args.key_env,
+            xattr_cache: args.xattr_cache,
             follow_symlinks: args.follow_symlinks,
             max_depth: args.max_depth,
             exclude: args.exclude,
@@ -161,8 +659,13 @@ impl From<SnapshotArgs> for galdi_snapshot::cli::ToolArgs {
             human: args.human,
             timeout_ms: args.timeout_ms,
             jsonl: args.jsonl,
+            format: args.format,
+            compress: args.compress,
+            filter: args.filter,
             threads: args.threads,
             normalize_paths: args.normalize_paths,
+            label: args.label,
+            redact: args.redact,
             plumbah_info: args.plumbah_info,
         }
     }
@@ -175,14 +678,26 @@ impl From<DiffArgs> for galdi_diff::cli::ToolArgs {
             target: args.target,
             output: args.output,
             checksum: args.checksum,
+            sample_len: args.sample_len,
+            key_file: args.key_file,
+            key_env: args.key_env,
+            xattr_cache: args.xattr_cache,
+            require_signature: args.require_signature,
             follow_symlinks: args.follow_symlinks,
             max_depth: args.max_depth,
             exclude: args.exclude,
             shallow: args.shallow,
             human: args.human,
+            format: args.format,
+            compress: args.compress,
+            filter: args.filter,
             ignore_time: args.ignore_time,
             ignore_mode: args.ignore_mode,
             structure_only: args.structure_only,
+            source_prefix: args.source_prefix,
+            target_prefix: args.target_prefix,
+            store: args.store,
+            repo: args.repo,
             timeout_ms: args.timeout_ms,
             normalize_paths: args.normalize_paths,
             plumbah_info: args.plumbah_info,
@@ -0,0 +1,131 @@
+//! `galdi export`: write a stored snapshot in another tool's format, or
+//! append snapshots and diff reports to a SQLite database.
+
+use std::fs;
+use std::path::Path;
+
+use chrono::Utc;
+use galdi_core::{
+    ChecksumAlgorithm, DiffResult, DocumentKind, ManifestStyle, Meta, PlumbahObject, Snapshot,
+    SqliteExport, Status, upgraded_snapshot,
+};
+
+use super::{RunResult, error_result, read_document, read_snapshot};
+use crate::cli::{ExportArgs, ExportFormat};
+
+const TOOL: &str = "galdi_export";
+
+pub fn run(args: ExportArgs) -> anyhow::Result<RunResult> {
+    if let ExportFormat::Sqlite = args.format {
+        return run_sqlite(&args);
+    }
+
+    let snapshot = match read_snapshot(&args.snapshot) {
+        Ok(snapshot) => snapshot,
+        Err(err) => return error_result(TOOL, "LOAD_ERROR", err.to_string()),
+    };
+
+    let algorithm = match args.format {
+        ExportFormat::Sha256sum => ChecksumAlgorithm::Sha256,
+        ExportFormat::Sha512sum => ChecksumAlgorithm::Sha512,
+        ExportFormat::Sha1sum => ChecksumAlgorithm::Sha1,
+        ExportFormat::Md5sum => ChecksumAlgorithm::Md5,
+        ExportFormat::B3sum => ChecksumAlgorithm::Blake3,
+        ExportFormat::Sqlite => unreachable!("exported by run_sqlite"),
+    };
+    let style = if args.tag {
+        ManifestStyle::Bsd
+    } else {
+        ManifestStyle::Gnu
+    };
+    let manifest = match snapshot.to_manifest(algorithm, style) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            let error = err.to_plumbah_error();
+            return error_result(TOOL, &error.code, error.message);
+        }
+    };
+
+    // Manifests end in a newline already; `main` adds one when printing.
+    let output = match &args.output {
+        Some(path) => {
+            fs::write(path, manifest)?;
+            String::new()
+        }
+        None => manifest.trim_end_matches('\n').to_string(),
+    };
+
+    Ok(RunResult {
+        exit_code: 0,
+        output,
+    })
+}
+
+/// Append the snapshot or diff report to the database at `--output`, and
+/// report the id it was given.
+fn run_sqlite(args: &ExportArgs) -> anyhow::Result<RunResult> {
+    let database = args.output.as_deref().expect("required by clap");
+    let document = match load(&args.snapshot) {
+        Ok(document) => document,
+        Err(err) => return error_result(TOOL, "LOAD_ERROR", err.to_string()),
+    };
+
+    let exported = SqliteExport::open(database).and_then(|mut export| match &document {
+        Exported::Snapshot(snapshot) => export.add_snapshot(snapshot, args.name.as_deref()),
+        Exported::Diff(diff) => export.add_diff(diff, args.name.as_deref()),
+    });
+    let id = match exported {
+        Ok(id) => id,
+        Err(err) => {
+            let error = err.to_plumbah_error();
+            return error_result(TOOL, &error.code, error.message);
+        }
+    };
+
+    let (kind, rows) = match &document {
+        Exported::Snapshot(snapshot) => ("snapshot", snapshot.entries.len()),
+        Exported::Diff(diff) => ("diff", diff.differences.len()),
+    };
+    let plumbah = PlumbahObject::new(
+        Status::Ok,
+        Meta::new(
+            TOOL,
+            env!("CARGO_PKG_VERSION"),
+            false, // idempotent: every export appends
+            true,  // mutates
+            true,  // safe
+            false, // deterministic: ids depend on what the database holds
+            0,
+            Utc::now(),
+        )
+        .with_default_profiles(),
+    );
+    let output = serde_json::to_string_pretty(&serde_json::json!({
+        "$plumbah": plumbah,
+        "database": database,
+        "id": id,
+        "kind": kind,
+        "name": args.name,
+        "rows": rows,
+    }))?;
+
+    Ok(RunResult {
+        exit_code: 0,
+        output,
+    })
+}
+
+/// A document that can be exported to SQLite.
+enum Exported {
+    Snapshot(Snapshot),
+    Diff(DiffResult),
+}
+
+fn load(path: &Path) -> anyhow::Result<Exported> {
+    let document = read_document(path)?;
+    match DocumentKind::detect(&document) {
+        Some(DocumentKind::Snapshot) => Ok(Exported::Snapshot(upgraded_snapshot(document)?)),
+        Some(DocumentKind::Diff) => Ok(Exported::Diff(serde_json::from_value(document)?)),
+        _ => anyhow::bail!("not a snapshot or a diff report"),
+    }
+}
@@ -0,0 +1,169 @@
+//! `galdi history`: when a path appeared, disappeared or changed across a
+//! series of snapshots.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use chrono::{SecondsFormat, Utc};
+use galdi_core::{
+    HistoryEventKind, Meta, PathHistory, PlumbahObject, SnapshotStore, Status, Timeline,
+    detect_format,
+};
+
+use super::{RunResult, error_result, read_snapshot};
+use crate::cli::HistoryArgs;
+
+const TOOL: &str = "galdi_history";
+
+pub fn run(args: HistoryArgs) -> anyhow::Result<RunResult> {
+    let store = SnapshotStore::locate(args.store.as_deref());
+    let sources = match sources(&args.snapshots, &store) {
+        Ok(sources) => sources,
+        Err(err) => return error_result(TOOL, "LOAD_ERROR", err.to_string()),
+    };
+
+    // One snapshot in memory at a time
+    let mut history = PathHistory::new(&args.path);
+    for source in &sources {
+        let loaded = match source {
+            Source::File(path) => read_snapshot(path)
+                .map(|snapshot| (path.display().to_string(), snapshot))
+                .map_err(|err| format!("{}: {}", path.display(), err)),
+            Source::Stored(reference) => store
+                .load(reference)
+                .map(|snapshot| (format!("@{}", &reference[..12]), snapshot))
+                .map_err(|err| err.to_string()),
+        };
+        match loaded {
+            Ok((label, snapshot)) => history.observe(label, &snapshot),
+            Err(message) => return error_result(TOOL, "LOAD_ERROR", message),
+        }
+    }
+    let timeline = history.timeline();
+
+    let output = if args.human {
+        format_human(&timeline)
+    } else {
+        let plumbah = PlumbahObject::new(
+            Status::Ok,
+            Meta::new(
+                TOOL,
+                env!("CARGO_PKG_VERSION"),
+                true,
+                false,
+                true,
+                true,
+                0,
+                Utc::now(),
+            )
+            .with_default_profiles(),
+        );
+        let mut document = serde_json::json!({ "$plumbah": plumbah });
+        if let (Some(document), serde_json::Value::Object(timeline)) =
+            (document.as_object_mut(), serde_json::to_value(&timeline)?)
+        {
+            document.extend(timeline);
+        }
+        serde_json::to_string_pretty(&document)?
+    };
+
+    Ok(RunResult {
+        exit_code: 0,
+        output,
+    })
+}
+
+/// Where a snapshot of the series is read from.
+enum Source {
+    File(PathBuf),
+    /// Full id in the store.
+    Stored(String),
+}
+
+/// The snapshots named on the command line, with directories expanded to
+/// the stored documents in them, or every snapshot in the store when none are.
+fn sources(arguments: &[PathBuf], store: &SnapshotStore) -> anyhow::Result<Vec<Source>> {
+    if arguments.is_empty() {
+        return Ok(store
+            .list()?
+            .into_iter()
+            .map(|snapshot| Source::Stored(snapshot.id))
+            .collect());
+    }
+
+    let mut sources = Vec::new();
+    for argument in arguments {
+        if SnapshotStore::is_reference(argument) {
+            let reference = argument.to_string_lossy();
+            sources.push(Source::Stored(store.resolve(&reference)?));
+        } else if argument.is_dir() {
+            let mut files: Vec<PathBuf> = fs::read_dir(argument)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<_, _>>()?;
+            files.retain(|path| {
+                path.is_file() && !is_hidden_or_signature(path) && is_stored_document(path)
+            });
+            files.sort();
+            sources.extend(files.into_iter().map(Source::File));
+        } else {
+            sources.push(Source::File(argument.clone()));
+        }
+    }
+    Ok(sources)
+}
+
+/// Dot files and the detached signatures kept next to signed snapshots.
+fn is_hidden_or_signature(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.') || name.ends_with(".sig"))
+}
+
+/// Whether `path` starts like a stored document, so that notes, checksum
+/// lists and other files kept next to the snapshots are passed over.
+fn is_stored_document(path: &Path) -> bool {
+    File::open(path)
+        .and_then(detect_format)
+        .is_ok_and(|format| format.is_some())
+}
+
+/// One line per event, oldest first, followed by a summary line.
+fn format_human(timeline: &Timeline) -> String {
+    let mut lines: Vec<String> = timeline
+        .events
+        .iter()
+        .map(|event| {
+            let label = match event.event {
+                HistoryEventKind::Appeared => "APPEARED",
+                HistoryEventKind::Disappeared => "DISAPPEARED",
+                HistoryEventKind::Changed => "CHANGED",
+            };
+            let changes: Vec<_> = event
+                .changes
+                .iter()
+                .filter_map(|change| serde_json::to_value(change).ok())
+                .filter_map(|change| change.as_str().map(str::to_string))
+                .collect();
+            let detail = if changes.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", changes.join(", "))
+            };
+            format!(
+                "{}  {:<11} {}{}",
+                event.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+                label,
+                event.source,
+                detail
+            )
+        })
+        .collect();
+    lines.push(format!(
+        "{}: recorded in {} of {} snapshots, {} events",
+        timeline.path.display(),
+        timeline.recorded_in,
+        timeline.snapshots,
+        timeline.events.len()
+    ));
+    lines.join("\n")
+}
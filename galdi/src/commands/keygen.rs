@@ -0,0 +1,71 @@
+//! `galdi keygen`: generate an Ed25519 key pair for `galdi sign`.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use galdi_core::{Meta, PlumbahObject, SigningKey, Status};
+
+use super::{RunResult, error_result};
+use crate::cli::KeygenArgs;
+
+const TOOL: &str = "galdi_keygen";
+
+pub fn run(args: KeygenArgs) -> anyhow::Result<RunResult> {
+    let key = SigningKey::generate()?;
+    let public_key_file = public_key_path(&args.path);
+
+    // Never overwrite an existing key; the secret half is only readable by its owner.
+    if let Err(err) = write_new(&args.path, &key.to_hex(), 0o600)
+        .and_then(|()| write_new(&public_key_file, &key.verifying_key().to_hex(), 0o644))
+    {
+        return error_result(TOOL, "IO_ERROR", err.to_string());
+    }
+
+    let plumbah = PlumbahObject::new(
+        Status::Ok,
+        Meta::new(
+            TOOL,
+            env!("CARGO_PKG_VERSION"),
+            false, // idempotent
+            true,  // mutates
+            true,  // safe
+            false, // deterministic
+            0,
+            Utc::now(),
+        )
+        .with_default_profiles(),
+    );
+    let output = serde_json::to_string_pretty(&serde_json::json!({
+        "$plumbah": plumbah,
+        "key_file": args.path,
+        "public_key_file": public_key_file,
+        "public_key": key.verifying_key().to_hex(),
+    }))?;
+
+    Ok(RunResult {
+        exit_code: 0,
+        output,
+    })
+}
+
+/// `<path>.pub`, next to the secret key.
+fn public_key_path(path: &Path) -> PathBuf {
+    let mut public = path.as_os_str().to_owned();
+    public.push(".pub");
+    PathBuf::from(public)
+}
+
+fn write_new(path: &Path, contents: &str, mode: u32) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+    let mut file = options
+        .open(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+    writeln!(file, "{}", contents)
+}
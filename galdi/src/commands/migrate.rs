@@ -0,0 +1,207 @@
+//! `galdi migrate`: rewrite stored snapshots in the current format version.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use galdi_core::{
+    Compression, Encoding, Meta, PlumbahError, PlumbahObject, SNAPSHOT_VERSION, ScanError,
+    Snapshot, Status, decompressing_reader, detached_signature_path, detect_format, is_stream_head,
+    read_stored, upgrade_document, write_encoded,
+};
+
+use super::RunResult;
+use crate::cli::MigrateArgs;
+
+const TOOL: &str = "galdi_migrate";
+
+pub fn run(args: MigrateArgs) -> anyhow::Result<RunResult> {
+    let mut migrated = Vec::new();
+    let mut unchanged = Vec::new();
+    let mut errors = Vec::new();
+    for path in &args.snapshots {
+        match migrate(path, args.dry_run) {
+            Ok(Some(from)) => migrated.push(serde_json::json!({
+                "path": path,
+                "from": from,
+                "to": SNAPSHOT_VERSION,
+            })),
+            Ok(None) => unchanged.push(path),
+            Err(error) => errors.push(PlumbahError {
+                path: Some(path.clone()),
+                ..error.to_plumbah_error()
+            }),
+        }
+    }
+
+    let status = match errors.len() {
+        0 => Status::Ok,
+        n if n == args.snapshots.len() => Status::Error,
+        _ => Status::Partial,
+    };
+    let exit_code = match status {
+        Status::Ok => 0,
+        Status::Partial => 2,
+        Status::Error => 1,
+    };
+    let mut plumbah = PlumbahObject::new(
+        status,
+        Meta::new(
+            TOOL,
+            env!("CARGO_PKG_VERSION"),
+            true,          // idempotent
+            !args.dry_run, // mutates
+            true,          // safe
+            true,          // deterministic
+            0,
+            Utc::now(),
+        )
+        .with_default_profiles(),
+    );
+    if !errors.is_empty() {
+        plumbah = plumbah.with_errors(errors);
+    }
+
+    let output = serde_json::to_string_pretty(&serde_json::json!({
+        "$plumbah": plumbah,
+        "dry_run": args.dry_run,
+        "migrated": migrated,
+        "unchanged": unchanged,
+    }))?;
+
+    Ok(RunResult { exit_code, output })
+}
+
+/// Upgrade the snapshot at `path` and rewrite it in its own encoding and
+/// compression. Returns the version it had, or `None` if it was current.
+/// JSONL streams are read, but refused when they would need rewriting.
+fn migrate(path: &Path, dry_run: bool) -> Result<Option<String>, ScanError> {
+    let Some((encoding, compression)) = File::open(path)
+        .and_then(detect_format)
+        .map_err(|err| ScanError::from_io(err, path))?
+    else {
+        return Err(ScanError::Schema(format!(
+            "{} is not a stored snapshot",
+            path.display()
+        )));
+    };
+    let file = File::open(path).map_err(|err| ScanError::from_io(err, path))?;
+    let mut document: serde_json::Value = read_stored(file)
+        .map_err(|err| ScanError::Schema(format!("{}: {}", path.display(), err)))?;
+
+    let signed = document["$plumbah"]["meta"].get("signature").is_some()
+        || detached_signature_path(path).exists();
+    let Some(from) = upgrade_document(&mut document)? else {
+        return Ok(None);
+    };
+    // Signed baselines stay usable as they are: they are upgraded whenever loaded.
+    if signed {
+        return Err(ScanError::Signature(format!(
+            "{} is signed; rewriting it would invalidate the signature",
+            path.display()
+        )));
+    }
+    // Streams are upgraded whenever loaded, too
+    if is_stream(path).map_err(|err| ScanError::from_io(err, path))? {
+        return Err(ScanError::Schema(format!(
+            "{} is a JSONL stream; only single documents can be migrated in place",
+            path.display()
+        )));
+    }
+
+    let snapshot: Snapshot = serde_json::from_value(document)
+        .map_err(|err| ScanError::Schema(format!("{}: {}", path.display(), err)))?;
+    if !dry_run {
+        rewrite(path, &snapshot, encoding, compression)
+            .map_err(|err| ScanError::from_io(err, path))?;
+    }
+    Ok(Some(from))
+}
+
+/// Whether the file at `path` is a JSONL snapshot stream, told by its head line.
+fn is_stream(path: &Path) -> io::Result<bool> {
+    let mut first_line = Vec::new();
+    BufReader::new(decompressing_reader(File::open(path)?)?).read_until(b'\n', &mut first_line)?;
+    Ok(is_stream_head(&first_line))
+}
+
+/// Replace the file at `path` with `snapshot`, keeping its permissions. The
+/// new content is written next to it first, so the old one is never lost.
+fn rewrite(
+    path: &Path,
+    snapshot: &Snapshot,
+    encoding: Encoding,
+    compression: Compression,
+) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".migrating");
+    let temp = PathBuf::from(temp);
+
+    let permissions = fs::metadata(path)?.permissions();
+    let result = File::create(&temp)
+        .and_then(|file| write_encoded(snapshot, file, encoding, compression))
+        .and_then(|()| fs::set_permissions(&temp, permissions))
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galdi_core::SnapshotEntry;
+    use tempfile::TempDir;
+
+    fn stored(dir: &TempDir, name: &str, content: &[u8]) -> PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn jsonl(version: &str) -> String {
+        let head = format!(
+            r#"{{"$plumbah":{{"version":"1.0","stream":"head","status":"ok","meta":{{"idempotent":true,"mutates":false,"safe":true,"deterministic":false,"plumbah_level":2,"execution_time_ms":0,"tool":"galdi_snapshot","tool_version":"0.1.0","timestamp":"2026-01-01T00:00:00Z"}}}},"version":"{}","root":"/data","checksum_algorithm":"sha256"}}"#,
+            version
+        );
+        let entry = r#"{"path":"a.txt","type":"file","size":3,"mtime":"2026-01-01T00:00:00Z","checksum":"sha256:00"}"#;
+        let tail = r#"{"$plumbah":{"version":"1.0","stream":"tail","status":"ok","summary":{"total":1,"processed":1,"errors":0}}}"#;
+        format!("{}\n{}\n{}\n", head, entry, tail)
+    }
+
+    #[test]
+    fn test_document_is_rewritten_in_its_encoding() {
+        let dir = TempDir::new().unwrap();
+        let snapshot = Snapshot {
+            version: "1.0".to_string(),
+            ..Snapshot::test_of(vec![SnapshotEntry::test_file("a.txt", "xxh3_64:00")])
+        };
+        let mut content = Vec::new();
+        write_encoded(&snapshot, &mut content, Encoding::Cbor, Compression::Zstd).unwrap();
+        let path = stored(&dir, "old.cbor.zst", &content);
+
+        assert_eq!(migrate(&path, false).unwrap().as_deref(), Some("1.0"));
+        assert_eq!(
+            File::open(&path).and_then(detect_format).unwrap(),
+            Some((Encoding::Cbor, Compression::Zstd))
+        );
+        let migrated: Snapshot = read_stored(File::open(&path).unwrap()).unwrap();
+        assert_eq!(migrated.version, SNAPSHOT_VERSION);
+        assert_eq!(migrate(&path, false).unwrap(), None);
+    }
+
+    #[test]
+    fn test_jsonl_stream_is_read_but_not_rewritten() {
+        let dir = TempDir::new().unwrap();
+        let current = stored(&dir, "current.jsonl", jsonl(SNAPSHOT_VERSION).as_bytes());
+        assert_eq!(migrate(&current, false).unwrap(), None);
+
+        let old = stored(&dir, "old.jsonl", jsonl("1.0").as_bytes());
+        let error = migrate(&old, false).unwrap_err();
+        assert!(matches!(error, ScanError::Schema(_)));
+        assert!(error.to_string().contains("JSONL stream"));
+        assert_eq!(fs::read_to_string(&old).unwrap(), jsonl("1.0"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Subcommands implemented directly in the facade, on top of `galdi_core`.
+//!
+//! Each module exposes a `run()` returning a `RunResult`, mirroring the
+//! `app::run()` functions of the `galdi_snapshot` and `galdi_diff` crates.
+
+use std::{fs::File, io, path::Path};
+
+use chrono::Utc;
+use galdi_core::{
+    Meta, PlumbahError, PlumbahObject, Snapshot, Status, VerifyingKey, read_stored,
+    upgraded_snapshot, verify_document_at,
+};
+
+pub mod export;
+pub mod history;
+pub mod keygen;
+pub mod migrate;
+pub mod schema;
+pub mod sign;
+pub mod snapshot_ops;
+pub mod store;
+pub mod validate;
+pub mod verify;
+pub mod verify_signature;
+
+/// Exit code and rendered output of a subcommand.
+pub struct RunResult {
+    pub exit_code: i32,
+    pub output: String,
+}
+
+/// A Plumbah error document for `tool`, exiting with code 1.
+pub fn error_result(tool: &str, code: &str, message: String) -> anyhow::Result<RunResult> {
+    let plumbah = PlumbahObject::new(
+        Status::Error,
+        Meta::new(
+            tool,
+            env!("CARGO_PKG_VERSION"),
+            true,
+            false,
+            true,
+            true,
+            0,
+            Utc::now(),
+        )
+        .with_default_profiles(),
+    )
+    .with_errors(vec![PlumbahError {
+        code: code.to_string(),
+        message,
+        path: None,
+        recoverable: false,
+        context: None,
+    }]);
+
+    Ok(RunResult {
+        exit_code: 1,
+        output: serde_json::to_string_pretty(&serde_json::json!({ "$plumbah": plumbah }))?,
+    })
+}
+
+/// Read a stored snapshot from a file, or from stdin for `-`, in whichever
+/// encoding and compression it was written or as a JSONL stream, upgraded to
+/// the current format version.
+pub fn read_snapshot(path: &Path) -> anyhow::Result<Snapshot> {
+    Ok(upgraded_snapshot(read_document(path)?)?)
+}
+
+/// Read a stored snapshot like `read_snapshot`, but only once its signature
+/// checks out against the public key in `key_file`.
+pub fn read_signed_snapshot(path: &Path, key_file: &Path) -> anyhow::Result<Snapshot> {
+    let key = VerifyingKey::from_file(key_file)?;
+    let document = read_document(path)?;
+    verify_document_at(&document, path, &key)?;
+    Ok(upgraded_snapshot(document)?)
+}
+
+/// Read any stored document from a file, or from stdin for `-`. A JSONL
+/// stream is read as the snapshot it describes.
+pub fn read_document(path: &Path) -> anyhow::Result<serde_json::Value> {
+    if path == Path::new("-") {
+        Ok(read_stored(io::stdin().lock())?)
+    } else {
+        Ok(read_stored(File::open(path)?)?)
+    }
+}
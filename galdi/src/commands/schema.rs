@@ -0,0 +1,11 @@
+//! `galdi schema`: print the JSON Schema of a kind of document.
+
+use super::RunResult;
+use crate::cli::SchemaArgs;
+
+pub fn run(args: SchemaArgs) -> anyhow::Result<RunResult> {
+    Ok(RunResult {
+        exit_code: 0,
+        output: serde_json::to_string_pretty(&args.kind.json_schema())?,
+    })
+}
@@ -0,0 +1,58 @@
+//! `galdi sign`: sign a snapshot or diff report with an Ed25519 key.
+
+use std::fs;
+
+use galdi_core::{SigningKey, embed_signature};
+
+use super::{RunResult, error_result, read_document};
+use crate::cli::SignArgs;
+
+const TOOL: &str = "galdi_sign";
+
+pub fn run(args: SignArgs) -> anyhow::Result<RunResult> {
+    let key = match SigningKey::from_file(&args.key) {
+        Ok(key) => key,
+        Err(err) => {
+            return error_result(
+                TOOL,
+                "KEY_ERROR",
+                format!("{}: {}", args.key.display(), err),
+            );
+        }
+    };
+    let mut document = match read_document(&args.document) {
+        Ok(document) => document,
+        Err(err) => return error_result(TOOL, "LOAD_ERROR", err.to_string()),
+    };
+    if document.get("$plumbah").is_none() {
+        return error_result(
+            TOOL,
+            "LOAD_ERROR",
+            "only galdi snapshots and diff reports can be signed".to_string(),
+        );
+    }
+
+    let signature = key.sign(&document);
+    let output = if args.detached {
+        serde_json::to_string_pretty(&signature)?
+    } else {
+        if let Err(err) = embed_signature(&mut document, &signature) {
+            let error = err.to_plumbah_error();
+            return error_result(TOOL, &error.code, error.message);
+        }
+        serde_json::to_string_pretty(&document)?
+    };
+
+    let output = match &args.output {
+        Some(path) => {
+            fs::write(path, output + "\n")?;
+            String::new()
+        }
+        None => output,
+    };
+
+    Ok(RunResult {
+        exit_code: 0,
+        output,
+    })
+}
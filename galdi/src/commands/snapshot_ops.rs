@@ -0,0 +1,82 @@
+//! `galdi snapshot-ops`: set operations and subtree extraction over stored
+//! snapshots.
+
+use std::path::PathBuf;
+
+use galdi_core::{Snapshot, render_or_write};
+use serde_json::Value;
+
+use super::{RunResult, error_result, read_snapshot};
+use crate::cli::{SnapshotOperation, SnapshotOpsArgs};
+
+const TOOL: &str = "galdi_snapshot_ops";
+
+pub fn run(args: SnapshotOpsArgs) -> anyhow::Result<RunResult> {
+    let paths: Vec<PathBuf> = match &args.operation {
+        SnapshotOperation::Merge { snapshots } => snapshots.clone(),
+        SnapshotOperation::Subtract { snapshot, baseline } => {
+            vec![snapshot.clone(), baseline.clone()]
+        }
+        SnapshotOperation::Intersect { snapshot, other } => vec![snapshot.clone(), other.clone()],
+        SnapshotOperation::Subtree { snapshot, .. }
+        | SnapshotOperation::Filter { snapshot, .. } => {
+            vec![snapshot.clone()]
+        }
+    };
+    let mut snapshots = Vec::with_capacity(paths.len());
+    for path in &paths {
+        match read_snapshot(path) {
+            Ok(snapshot) => snapshots.push(snapshot),
+            Err(err) => {
+                return error_result(TOOL, "LOAD_ERROR", format!("{}: {}", path.display(), err));
+            }
+        }
+    }
+
+    let result = match &args.operation {
+        SnapshotOperation::Merge { .. } => Snapshot::merge(&snapshots),
+        SnapshotOperation::Subtract { .. } => snapshots[0].subtract(&snapshots[1]),
+        SnapshotOperation::Intersect { .. } => snapshots[0].intersect(&snapshots[1]),
+        SnapshotOperation::Subtree { prefix, .. } => snapshots[0].subtree(prefix),
+        SnapshotOperation::Filter {
+            include, exclude, ..
+        } => snapshots[0].filter(include, exclude),
+    };
+    let mut snapshot = match result {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            let error = err.to_plumbah_error();
+            return error_result(TOOL, &error.code, error.message);
+        }
+    };
+    record_input_paths(&mut snapshot, &paths);
+
+    let output = render_or_write(
+        &snapshot,
+        args.output.as_deref(),
+        args.format,
+        args.compress,
+    )?;
+
+    Ok(RunResult {
+        exit_code: 0,
+        output,
+    })
+}
+
+/// Name the files the inputs were read from in the provenance the library
+/// recorded, which only knows the snapshots themselves.
+fn record_input_paths(snapshot: &mut Snapshot, paths: &[PathBuf]) {
+    let inputs = snapshot
+        .plumbah
+        .meta
+        .as_mut()
+        .and_then(|meta| meta.extra.get_mut("provenance"))
+        .and_then(|provenance| provenance.get_mut("inputs"))
+        .and_then(Value::as_array_mut);
+    for (input, path) in inputs.into_iter().flatten().zip(paths) {
+        if let Value::Object(input) = input {
+            input.insert("path".to_string(), serde_json::json!(path));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! `galdi store`: keep snapshots in a local content-addressed store.
+
+use chrono::Utc;
+use galdi_core::{
+    Compression, DocumentKind, Encoding, Meta, PlumbahObject, RetentionPolicy, SnapshotStore,
+    Status, render_or_write,
+};
+use serde_json::{Value, json};
+
+use super::{RunResult, error_result, read_document};
+use crate::cli::{StoreArgs, StoreCommand};
+
+const TOOL: &str = "galdi_store";
+
+pub fn run(args: StoreArgs) -> anyhow::Result<RunResult> {
+    let store = SnapshotStore::locate(args.store.as_deref());
+
+    let result = match args.command {
+        StoreCommand::Add { snapshot, tag } => {
+            let document = match read_document(&snapshot) {
+                Ok(document) if DocumentKind::detect(&document) == Some(DocumentKind::Snapshot) => {
+                    document
+                }
+                Ok(_) => {
+                    let message = format!("{}: not a snapshot", snapshot.display());
+                    return error_result(TOOL, "LOAD_ERROR", message);
+                }
+                Err(err) => {
+                    let message = format!("{}: {}", snapshot.display(), err);
+                    return error_result(TOOL, "LOAD_ERROR", message);
+                }
+            };
+            store.add_document(document).and_then(|mut added| {
+                for name in &tag {
+                    store.tag(&added.snapshot.id, name)?;
+                    added.snapshot.tags.push(name.clone());
+                }
+                added.snapshot.tags.sort();
+                added.snapshot.tags.dedup();
+                Ok((true, json!(added)))
+            })
+        }
+        StoreCommand::List => store
+            .list()
+            .map(|snapshots| (false, json!({ "snapshots": snapshots }))),
+        StoreCommand::Show {
+            reference,
+            output,
+            format,
+            compress,
+        } => {
+            let document = match store.load_document(&reference) {
+                Ok(document) => document,
+                Err(err) => {
+                    let error = err.to_plumbah_error();
+                    return error_result(TOOL, &error.code, error.message);
+                }
+            };
+            return show(&document, output.as_deref(), format, compress);
+        }
+        StoreCommand::Tag {
+            reference,
+            name,
+            delete: Some(delete),
+        } => {
+            debug_assert!(reference.is_none() && name.is_none());
+            store
+                .untag(&delete)
+                .map(|()| (true, json!({ "untagged": delete })))
+        }
+        StoreCommand::Tag {
+            reference: Some(reference),
+            name: Some(name),
+            delete: None,
+        } => store
+            .tag(&reference, &name)
+            .map(|id| (true, json!({ "id": id, "tag": name }))),
+        StoreCommand::Tag { .. } => unreachable!("required by clap"),
+        StoreCommand::Rm { references } => references
+            .iter()
+            .map(|reference| store.remove(reference))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|removed| (true, json!({ "removed": removed }))),
+        StoreCommand::Prune {
+            keep_last,
+            keep_within,
+            dry_run,
+        } => {
+            let policy = RetentionPolicy {
+                keep_last,
+                keep_within,
+            };
+            store
+                .prune(&policy, dry_run)
+                .map(|report| (!dry_run, json!(report)))
+        }
+    };
+
+    let (mutates, body) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            let error = err.to_plumbah_error();
+            return error_result(TOOL, &error.code, error.message);
+        }
+    };
+    let plumbah = PlumbahObject::new(
+        Status::Ok,
+        Meta::new(
+            TOOL,
+            env!("CARGO_PKG_VERSION"),
+            !mutates, // idempotent
+            mutates,  // mutates
+            true,     // safe
+            false,    // deterministic: depends on what the store holds
+            0,
+            Utc::now(),
+        )
+        .with_default_profiles(),
+    );
+    let mut document = json!({ "$plumbah": plumbah, "store": store.root() });
+    if let (Some(document), Value::Object(body)) = (document.as_object_mut(), body) {
+        document.extend(body);
+    }
+
+    Ok(RunResult {
+        exit_code: 0,
+        output: serde_json::to_string_pretty(&document)?,
+    })
+}
+
+/// Print the snapshot, or write it out like `galdi snapshot` does.
+fn show(
+    document: &Value,
+    output: Option<&std::path::Path>,
+    format: Encoding,
+    compress: Compression,
+) -> anyhow::Result<RunResult> {
+    Ok(RunResult {
+        exit_code: 0,
+        output: render_or_write(document, output, format, compress)?,
+    })
+}
@@ -0,0 +1,168 @@
+//! `galdi validate`: check a document against its published JSON Schema.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use chrono::Utc;
+use galdi_core::{
+    DocumentKind, Meta, PlumbahError, PlumbahObject, ScanError, Status, decompressing_reader,
+    read_encoded,
+};
+use serde_json::Value;
+
+use super::{RunResult, error_result};
+use crate::cli::ValidateArgs;
+
+const TOOL: &str = "galdi_validate";
+
+/// A document to validate, with its line number when it came from a JSONL stream.
+struct Document {
+    line: Option<usize>,
+    kind: DocumentKind,
+    value: Value,
+}
+
+pub fn run(args: ValidateArgs) -> anyhow::Result<RunResult> {
+    let bytes = match read_bytes(&args.document) {
+        Ok(bytes) => bytes,
+        Err(err) => return error_result(TOOL, "LOAD_ERROR", err.to_string()),
+    };
+    let (kind, documents) = match parse(&bytes, args.kind) {
+        Ok(parsed) => parsed,
+        Err(message) => return error_result(TOOL, "LOAD_ERROR", message),
+    };
+
+    let mut validators = HashMap::new();
+    let mut errors = Vec::new();
+    for document in &documents {
+        let validator = validators.entry(document.kind).or_insert_with(|| {
+            jsonschema::validator_for(&document.kind.json_schema())
+                .expect("derived schemas are valid")
+        });
+        // The annotation is validated on its own wherever it appears
+        let instance = match document.kind {
+            DocumentKind::Plumbah => document.value.get("$plumbah").unwrap_or(&document.value),
+            _ => &document.value,
+        };
+        for error in validator.iter_errors(instance) {
+            let mut context = HashMap::from([(
+                "instance_path".to_string(),
+                Value::from(error.instance_path().as_str()),
+            )]);
+            if let Some(line) = document.line {
+                context.insert("line".to_string(), Value::from(line));
+            }
+            errors.push(PlumbahError {
+                path: Some(args.document.clone()),
+                context: Some(context),
+                ..ScanError::Schema(error.to_string()).to_plumbah_error()
+            });
+        }
+    }
+
+    let valid = errors.is_empty();
+    let mut plumbah = PlumbahObject::new(
+        if valid { Status::Ok } else { Status::Error },
+        Meta::new(
+            TOOL,
+            env!("CARGO_PKG_VERSION"),
+            true,
+            false,
+            true,
+            true,
+            0,
+            Utc::now(),
+        )
+        .with_default_profiles(),
+    );
+    if !valid {
+        plumbah = plumbah.with_errors(errors);
+    }
+
+    let output = serde_json::to_string_pretty(&serde_json::json!({
+        "$plumbah": plumbah,
+        "valid": valid,
+        "kind": kind,
+        "documents": documents.len(),
+    }))?;
+
+    Ok(RunResult {
+        exit_code: if valid { 0 } else { 1 },
+        output,
+    })
+}
+
+/// The whole input, decompressed, from a file or from stdin for `-`.
+fn read_bytes(path: &Path) -> io::Result<Vec<u8>> {
+    let reader: Box<dyn Read> = if path == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(File::open(path)?)
+    };
+    let mut bytes = Vec::new();
+    decompressing_reader(reader)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Split the input into the documents to validate: the lines of a JSONL
+/// stream, or a single document of `kind` (detected when not given).
+/// Returns them with the name of what the input was found to be.
+fn parse(bytes: &[u8], kind: Option<DocumentKind>) -> Result<(String, Vec<Document>), String> {
+    if kind.is_none() && is_stream(bytes) {
+        return parse_stream(bytes).map(|documents| ("jsonl".to_string(), documents));
+    }
+
+    let value: Value = read_encoded(bytes).map_err(|err| err.to_string())?;
+    let kind = kind
+        .or_else(|| DocumentKind::detect(&value))
+        .ok_or("cannot tell what kind of document this is; pass --kind")?;
+    Ok((
+        kind.name().to_string(),
+        vec![Document {
+            line: None,
+            kind,
+            value,
+        }],
+    ))
+}
+
+/// Whether the input starts with the head line of a JSONL snapshot stream.
+fn is_stream(bytes: &[u8]) -> bool {
+    let first = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+    serde_json::from_slice::<Value>(first)
+        .is_ok_and(|head| head.pointer("/$plumbah/stream") == Some(&Value::from("head")))
+}
+
+fn parse_stream(bytes: &[u8]) -> Result<Vec<Document>, String> {
+    let lines: Vec<(usize, &[u8])> = bytes
+        .split(|&b| b == b'\n')
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim_ascii().is_empty())
+        .collect();
+    if lines.len() < 2 {
+        return Err("a JSONL stream needs a head and a tail line".to_string());
+    }
+
+    let last = lines.len() - 1;
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, (line, text))| {
+            let value = serde_json::from_slice(text)
+                .map_err(|err| format!("line {}: invalid JSON: {}", line, err))?;
+            let kind = match i {
+                0 => DocumentKind::StreamHead,
+                i if i == last => DocumentKind::StreamTail,
+                _ => DocumentKind::StreamLine,
+            };
+            Ok(Document {
+                line: Some(line),
+                kind,
+                value,
+            })
+        })
+        .collect()
+}
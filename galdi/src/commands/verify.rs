@@ -0,0 +1,111 @@
+//! `galdi verify`: check a live tree against a stored snapshot.
+
+use galdi_core::{
+    ChecksumKey, ScanError, VerifyOptions, VerifyReport, VerifyStatus, verify_snapshot,
+};
+
+use super::{RunResult, error_result, read_signed_snapshot, read_snapshot};
+use crate::cli::VerifyArgs;
+
+const TOOL: &str = "galdi_verify";
+
+pub fn run(args: VerifyArgs) -> anyhow::Result<RunResult> {
+    let snapshot = match &args.require_signature {
+        Some(key_file) => read_signed_snapshot(&args.snapshot, key_file),
+        None => read_snapshot(&args.snapshot),
+    };
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(err) => match err.downcast_ref::<ScanError>() {
+            Some(error @ (ScanError::Signature(_) | ScanError::Schema(_))) => {
+                let error = error.to_plumbah_error();
+                return error_result(TOOL, &error.code, error.message);
+            }
+            _ => return error_result(TOOL, "LOAD_ERROR", err.to_string()),
+        },
+    };
+
+    let checksum_key = match ChecksumKey::load(args.key_file.as_deref(), args.key_env.as_deref()) {
+        Ok(checksum_key) => checksum_key,
+        Err(err) => return error_result(TOOL, "KEY_ERROR", err.to_string()),
+    };
+    let options = VerifyOptions {
+        root: args.root,
+        ignore_time: args.ignore_time,
+        ignore_mode: args.ignore_mode,
+        threads: args.threads,
+        checksum_key,
+        follow_symlinks: match (args.follow_symlinks, args.no_follow_symlinks) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        max_depth: args.max_depth,
+        exclude_patterns: (!args.exclude.is_empty()).then_some(args.exclude),
+    };
+    let report = match verify_snapshot(&snapshot, &options) {
+        Ok(report) => report,
+        Err(err) => {
+            let error = err.to_plumbah_error();
+            return error_result(TOOL, &error.code, error.message);
+        }
+    };
+
+    let output = if args.human {
+        format_human(&report)
+    } else {
+        serde_json::to_string_pretty(&report)?
+    };
+
+    Ok(RunResult {
+        exit_code: report.exit_code(),
+        output,
+    })
+}
+
+/// One line per failure, `sha256sum -c` style, followed by a summary line.
+fn format_human(report: &VerifyReport) -> String {
+    let mut lines: Vec<String> = report
+        .failures
+        .iter()
+        .map(|failure| {
+            let label = match failure.status {
+                VerifyStatus::Missing => "MISSING",
+                VerifyStatus::Extra => "EXTRA",
+                VerifyStatus::Modified => "MODIFIED",
+                VerifyStatus::Unreadable => "UNREADABLE",
+            };
+            let detail = if !failure.changes.is_empty() {
+                let changes: Vec<_> = failure
+                    .changes
+                    .iter()
+                    .filter_map(|change| serde_json::to_value(change).ok())
+                    .filter_map(|change| change.as_str().map(str::to_string))
+                    .collect();
+                format!(" ({})", changes.join(", "))
+            } else if let Some(error) = &failure.error {
+                format!(" ({})", error)
+            } else {
+                String::new()
+            };
+            format!("{:<10} {}{}", label, failure.path.display(), detail)
+        })
+        .collect();
+
+    let summary = &report.summary;
+    let ok = match summary.probably_ok {
+        0 => summary.ok.to_string(),
+        probably => format!("{} ({} probably)", summary.ok, probably),
+    };
+    lines.push(format!(
+        "{}: {} ({} ok, {} missing, {} extra, {} modified, {} unreadable)",
+        report.root.display(),
+        if report.passed { "OK" } else { "FAILED" },
+        ok,
+        summary.missing,
+        summary.extra,
+        summary.modified,
+        summary.unreadable,
+    ));
+    lines.join("\n")
+}
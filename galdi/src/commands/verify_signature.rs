@@ -0,0 +1,70 @@
+//! `galdi verify-signature`: check a signed snapshot or diff report.
+
+use chrono::Utc;
+use galdi_core::{
+    DocumentSignature, Meta, PlumbahObject, Status, VerifyingKey, verify_document,
+    verify_document_at,
+};
+
+use super::{RunResult, error_result, read_document};
+use crate::cli::VerifySignatureArgs;
+
+const TOOL: &str = "galdi_verify_signature";
+
+pub fn run(args: VerifySignatureArgs) -> anyhow::Result<RunResult> {
+    let key = match VerifyingKey::from_file(&args.public_key) {
+        Ok(key) => key,
+        Err(err) => {
+            return error_result(
+                TOOL,
+                "KEY_ERROR",
+                format!("{}: {}", args.public_key.display(), err),
+            );
+        }
+    };
+    let document = match read_document(&args.document) {
+        Ok(document) => document,
+        Err(err) => return error_result(TOOL, "LOAD_ERROR", err.to_string()),
+    };
+
+    let verified = match &args.signature {
+        Some(path) => match read_signature(path) {
+            Ok(signature) => verify_document(&document, Some(&signature), &key),
+            Err(err) => return error_result(TOOL, "LOAD_ERROR", err.to_string()),
+        },
+        None => verify_document_at(&document, &args.document, &key),
+    };
+    if let Err(err) = verified {
+        let error = err.to_plumbah_error();
+        return error_result(TOOL, &error.code, error.message);
+    }
+
+    let plumbah = PlumbahObject::new(
+        Status::Ok,
+        Meta::new(
+            TOOL,
+            env!("CARGO_PKG_VERSION"),
+            true,
+            false,
+            true,
+            true,
+            0,
+            Utc::now(),
+        )
+        .with_default_profiles(),
+    );
+    let output = serde_json::to_string_pretty(&serde_json::json!({
+        "$plumbah": plumbah,
+        "valid": true,
+        "public_key": key.to_hex(),
+    }))?;
+
+    Ok(RunResult {
+        exit_code: 0,
+        output,
+    })
+}
+
+fn read_signature(path: &std::path::Path) -> anyhow::Result<DocumentSignature> {
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
@@ -7,6 +7,7 @@ use clap::Parser;
 use rmcp::ServiceExt;
 
 mod cli;
+mod commands;
 mod mcp;
 
 use cli::{Args, Command};
@@ -18,6 +19,17 @@ use cli::{Args, Command};
 /// 3. Otherwise, routes to appropriate subcommand:
 ///    - `galdi snapshot` → delegates to galdi_snapshot::app::run()
 ///    - `galdi diff` → delegates to galdi_diff::app::run()
+///    - `galdi verify` → runs commands::verify::run()
+///    - `galdi export` → runs commands::export::run()
+///    - `galdi keygen` → runs commands::keygen::run()
+///    - `galdi sign` → runs commands::sign::run()
+///    - `galdi verify-signature` → runs commands::verify_signature::run()
+///    - `galdi migrate` → runs commands::migrate::run()
+///    - `galdi schema` → runs commands::schema::run()
+///    - `galdi validate` → runs commands::validate::run()
+///    - `galdi snapshot-ops` → runs commands::snapshot_ops::run()
+///    - `galdi store` → runs commands::store::run()
+///    - `galdi history` → runs commands::history::run()
 /// 4. Prints output to stdout
 /// 5. Exits with appropriate code (0=ok, 1=error, 2=partial)
 #[tokio::main]
@@ -39,16 +51,79 @@ async fn main() -> anyhow::Result<()> {
             // Convert facade args to library args and delegate
             let lib_args: galdi_snapshot::cli::ToolArgs = snapshot_args.into();
             let result = galdi_snapshot::app::run(lib_args)?;
-            println!("{}", result.output);
+            if !result.output.is_empty() {
+                println!("{}", result.output);
+            }
             std::process::exit(result.exit_code);
         }
         Some(Command::Diff(diff_args)) => {
             // Convert facade args to library args and delegate
             let lib_args: galdi_diff::cli::ToolArgs = diff_args.into();
             let result = galdi_diff::app::run(lib_args)?;
+            if !result.output.is_empty() {
+                println!("{}", result.output);
+            }
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::Verify(verify_args)) => {
+            let result = commands::verify::run(verify_args)?;
+            println!("{}", result.output);
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::Export(export_args)) => {
+            let result = commands::export::run(export_args)?;
+            println!("{}", result.output);
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::Keygen(keygen_args)) => {
+            let result = commands::keygen::run(keygen_args)?;
+            println!("{}", result.output);
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::Sign(sign_args)) => {
+            let result = commands::sign::run(sign_args)?;
             println!("{}", result.output);
             std::process::exit(result.exit_code);
         }
+        Some(Command::VerifySignature(verify_signature_args)) => {
+            let result = commands::verify_signature::run(verify_signature_args)?;
+            println!("{}", result.output);
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::Migrate(migrate_args)) => {
+            let result = commands::migrate::run(migrate_args)?;
+            println!("{}", result.output);
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::Schema(schema_args)) => {
+            let result = commands::schema::run(schema_args)?;
+            println!("{}", result.output);
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::Validate(validate_args)) => {
+            let result = commands::validate::run(validate_args)?;
+            println!("{}", result.output);
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::SnapshotOps(snapshot_ops_args)) => {
+            let result = commands::snapshot_ops::run(snapshot_ops_args)?;
+            if !result.output.is_empty() {
+                println!("{}", result.output);
+            }
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::History(history_args)) => {
+            let result = commands::history::run(history_args)?;
+            println!("{}", result.output);
+            std::process::exit(result.exit_code);
+        }
+        Some(Command::Store(store_args)) => {
+            let result = commands::store::run(store_args)?;
+            if !result.output.is_empty() {
+                println!("{}", result.output);
+            }
+            std::process::exit(result.exit_code);
+        }
         None => {
             // No subcommand provided - show help
             eprintln!("Error: No subcommand provided");
@@ -58,6 +133,17 @@ async fn main() -> anyhow::Result<()> {
             eprintln!("Commands:");
             eprintln!("  snapshot  Create a filesystem snapshot");
             eprintln!("  diff      Compare two snapshots or directories");
+            eprintln!("  verify    Check a live tree against a stored snapshot");
+            eprintln!("  export    Write a stored snapshot in another tool's format, or to SQLite");
+            eprintln!("  keygen    Generate an Ed25519 key pair for signing");
+            eprintln!("  sign      Sign a snapshot or diff report");
+            eprintln!("  verify-signature  Check the signature of a snapshot or diff report");
+            eprintln!("  migrate   Rewrite stored snapshots in the current format version");
+            eprintln!("  schema    Print the JSON Schema of a kind of document");
+            eprintln!("  validate  Check a document against its JSON Schema");
+            eprintln!("  snapshot-ops  Merge, subtract, intersect or filter stored snapshots");
+            eprintln!("  store     Keep snapshots in a local store, with tags and retention");
+            eprintln!("  history   Show when a path appeared, disappeared or changed");
             eprintln!();
             eprintln!("Options:");
             eprintln!("  --serve   Start MCP server mode");
@@ -3,7 +3,7 @@
 //! Exposes both `take_filesystem_snapshot` and `compare_snapshots` tools
 //! through a single MCP server instance.
 
-use galdi_core::ChecksumAlgorithm;
+use galdi_core::{ChecksumAlgorithm, Compression, DEFAULT_SAMPLE_LEN, Encoding};
 use rmcp::{
     ServerHandler,
     handler::server::{router::tool::ToolRouter, wrapper::Parameters},
@@ -40,14 +40,31 @@ pub struct TakeFilesystemSnapshotRequest {
         description = "Number of threads for parallel scanning (default: auto-detect based on CPU cores)"
     )]
     pub threads: Option<usize>,
+
+    #[schemars(
+        description = "jq expression run over the snapshot (or over each entry line in JSONL format) before it is returned"
+    )]
+    pub filter: Option<String>,
+
+    #[schemars(description = "Labels recorded in the provenance of the snapshot")]
+    pub labels: Option<std::collections::BTreeMap<String, String>>,
+
+    #[schemars(
+        description = "Leave the hostname, username and command line out of the provenance"
+    )]
+    pub redact: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct CompareSnapshotsRequest {
-    #[schemars(description = "Path to the source snapshot (JSON file) or live directory")]
+    #[schemars(
+        description = "Path to the source snapshot (JSON file), @name from the snapshot store, or live directory"
+    )]
     pub source: String,
 
-    #[schemars(description = "Path to the target snapshot (JSON file) or live directory")]
+    #[schemars(
+        description = "Path to the target snapshot (JSON file), @name from the snapshot store, or live directory"
+    )]
     pub target: String,
 
     #[schemars(description = "Ignore timestamp differences when comparing entries")]
@@ -61,6 +78,22 @@ pub struct CompareSnapshotsRequest {
     )]
     pub structure_only: Option<bool>,
 
+    #[schemars(
+        description = "Compare only this subtree of the source, re-rooted at it (relative to the source root)"
+    )]
+    pub source_prefix: Option<String>,
+
+    #[schemars(
+        description = "Compare only this subtree of the target, re-rooted at it (relative to the target root)"
+    )]
+    pub target_prefix: Option<String>,
+
+    #[schemars(description = "Snapshot store that @name sides are loaded from")]
+    pub store: Option<String>,
+
+    #[schemars(description = "jq expression run over the diff report before it is returned")]
+    pub filter: Option<String>,
+
     #[schemars(description = "Glob patterns to exclude when scanning live directories")]
     pub exclude_patterns: Option<Vec<String>>,
 
@@ -96,18 +129,30 @@ impl GaldiUnifiedService {
 
         // Delegate to galdi_snapshot::app::run
         let result = galdi_snapshot::app::run(galdi_snapshot::cli::ToolArgs {
-            path: std::path::PathBuf::from(request.path),
+            path: Some(std::path::PathBuf::from(request.path)),
+            from_tar: None,
+            repo: None,
             output: None,
             shallow: false,
             checksum: ChecksumAlgorithm::XXH3_64,
+            extra_checksum: vec![],
+            sample_len: DEFAULT_SAMPLE_LEN,
+            key_file: None,
+            key_env: None,
+            xattr_cache: false,
             follow_symlinks: false,
             human: false,
             max_depth: None,
             exclude: request.exclude_patterns.unwrap_or_default(),
             timeout_ms: request.timeout_ms,
             jsonl: use_jsonl,
+            format: Encoding::Json,
+            compress: Compression::None,
+            filter: request.filter,
             threads: request.threads,
             normalize_paths: false,
+            label: request.labels.into_iter().flatten().collect(),
+            redact: request.redact.unwrap_or(false),
             plumbah_info: false,
         });
 
@@ -133,15 +178,27 @@ impl GaldiUnifiedService {
             source: std::path::PathBuf::from(request.source),
             target: std::path::PathBuf::from(request.target),
             output: None,
-            checksum: ChecksumAlgorithm::XXH3_64,
+            checksum: None,
+            sample_len: None,
+            key_file: None,
+            key_env: None,
+            xattr_cache: false,
+            require_signature: None,
             follow_symlinks: request.follow_symlinks.unwrap_or(false),
             max_depth: request.max_depth,
             exclude: request.exclude_patterns.unwrap_or_default(),
             shallow: false,
             human: false,
+            format: Encoding::Json,
+            compress: Compression::None,
+            filter: request.filter,
             ignore_time: request.ignore_time.unwrap_or(false),
             ignore_mode: request.ignore_mode.unwrap_or(false),
             structure_only: request.structure_only.unwrap_or(false),
+            source_prefix: request.source_prefix.map(std::path::PathBuf::from),
+            target_prefix: request.target_prefix.map(std::path::PathBuf::from),
+            store: request.store.map(std::path::PathBuf::from),
+            repo: None,
             timeout_ms: request.timeout_ms,
             normalize_paths: request.normalize_paths.unwrap_or(false),
             plumbah_info: false,
@@ -0,0 +1,524 @@
+//! Set operations over snapshots: merge, subtract, intersect and filter, and
+//! the extraction of subtrees.
+//!
+//! Each operation returns a new, complete snapshot: entries sorted by path,
+//! `count` matching them, and `$plumbah.meta.provenance` recording the
+//! operation and the snapshots it was computed from.
+
+use std::path::{Component, Path, PathBuf};
+
+use chrono::Utc;
+use ignore::overrides::{Override, OverrideBuilder};
+use serde_json::{Value, json};
+
+use crate::error::ScanError;
+use crate::migration::SNAPSHOT_VERSION;
+use crate::plumbah::{Meta, PlumbahObject, Status};
+use crate::snapshot::{ChecksumAlgorithm, EntryType, Snapshot, SnapshotEntry};
+
+/// Tool recorded in the metadata of computed snapshots.
+const TOOL: &str = "galdi_snapshot_ops";
+
+impl Snapshot {
+    /// Combine partial scans of disjoint subtrees into one snapshot.
+    ///
+    /// Snapshots rooted at different directories are re-rooted at their
+    /// closest common ancestor, their paths prefixed with where they were
+    /// taken from. Directories may appear in several inputs; any other path
+    /// must either be recorded identically or by one input only. All inputs
+    /// must share their checksum algorithm and key, and only the extra
+    /// checksums they all carry are kept.
+    pub fn merge(snapshots: &[Snapshot]) -> Result<Snapshot, ScanError> {
+        let Some((first, rest)) = snapshots.split_first() else {
+            return Err(ScanError::Operation("nothing to merge".to_string()));
+        };
+        for other in rest {
+            if other.checksum_algorithm != first.checksum_algorithm {
+                return Err(ScanError::Operation(format!(
+                    "cannot merge {} checksums with {} checksums",
+                    first.checksum_algorithm.name(),
+                    other.checksum_algorithm.name()
+                )));
+            }
+            first.check_comparable(other)?;
+        }
+
+        let root = snapshots
+            .iter()
+            .skip(1)
+            .fold(first.root.clone(), |common, snapshot| {
+                common_ancestor(&common, &snapshot.root)
+            });
+        let extra_checksums: Vec<ChecksumAlgorithm> = first
+            .extra_checksums
+            .iter()
+            .copied()
+            .filter(|algorithm| rest.iter().all(|s| s.extra_checksums.contains(algorithm)))
+            .collect();
+
+        let mut merged: Vec<(usize, SnapshotEntry)> = Vec::new();
+        for (input, snapshot) in snapshots.iter().enumerate() {
+            let prefix = snapshot.root.strip_prefix(&root).unwrap_or(Path::new(""));
+            for entry in snapshot.index().iter() {
+                let mut entry = entry.clone();
+                if !entry.path.as_os_str().is_empty() {
+                    entry.path = prefix.join(&entry.path);
+                } else {
+                    // `join` would add a trailing separator
+                    entry.path = prefix.to_path_buf();
+                }
+                entry
+                    .checksums
+                    .retain(|algorithm, _| extra_checksums.contains(algorithm));
+                merged.push((input, entry));
+            }
+        }
+        merged.sort_by(|(i, a), (j, b)| a.path.cmp(&b.path).then(i.cmp(j)));
+
+        let mut entries: Vec<SnapshotEntry> = Vec::with_capacity(merged.len());
+        let mut from: Vec<usize> = Vec::with_capacity(merged.len());
+        for (input, entry) in merged {
+            if let Some(kept) = entries.last()
+                && kept.path == entry.path
+            {
+                let both_directories = kept.entry_type == EntryType::Directory
+                    && entry.entry_type == EntryType::Directory;
+                if !both_directories && !same_entry(kept, &entry) {
+                    return Err(ScanError::Operation(format!(
+                        "{} differs between inputs {} and {}; merged snapshots must cover disjoint subtrees",
+                        entry.path.display(),
+                        from.last().unwrap() + 1,
+                        input + 1
+                    )));
+                }
+                continue;
+            }
+            entries.push(entry);
+            from.push(input);
+        }
+
+        let mut result = computed(
+            first,
+            "merge",
+            &snapshots.iter().collect::<Vec<_>>(),
+            entries,
+        );
+        result.root = root;
+        result.extra_checksums = extra_checksums;
+        // Errors met while scanning any of the pieces stay with the whole
+        let errors: Vec<_> = snapshots
+            .iter()
+            .flat_map(|s| s.plumbah.errors.iter().flatten().cloned())
+            .collect();
+        if snapshots
+            .iter()
+            .any(|s| !matches!(s.plumbah.status, Status::Ok))
+        {
+            result.plumbah.status = Status::Partial;
+        }
+        result.plumbah.errors = (!errors.is_empty()).then_some(errors);
+        Ok(result)
+    }
+
+    /// The entries of this snapshot that are not recorded identically in
+    /// `baseline`: what was added or changed since. Directories count as
+    /// identical whenever both record them.
+    pub fn subtract(&self, baseline: &Snapshot) -> Result<Snapshot, ScanError> {
+        self.check_comparable(baseline)?;
+        let baseline_index = baseline.index();
+        let entries = self
+            .index()
+            .iter()
+            .filter(|entry| {
+                !baseline_index
+                    .get(&entry.path)
+                    .is_some_and(|other| same_entry(entry, other))
+            })
+            .cloned()
+            .collect();
+        Ok(computed(self, "subtract", &[self, baseline], entries))
+    }
+
+    /// The entries of this snapshot whose paths `other` records as well,
+    /// whatever their content there.
+    pub fn intersect(&self, other: &Snapshot) -> Result<Snapshot, ScanError> {
+        let other_index = other.index();
+        let entries = self
+            .index()
+            .iter()
+            .filter(|entry| other_index.get(&entry.path).is_some())
+            .cloned()
+            .collect();
+        Ok(computed(self, "intersect", &[self, other], entries))
+    }
+
+    /// The entries of this snapshot selected by glob patterns, with the
+    /// syntax of `ScanOptions::exclude_patterns`: `exclude` drops matching
+    /// entries, and everything beneath matching directories. When `include`
+    /// is not empty, only files matching one of its patterns are kept. The
+    /// root entry is always kept.
+    pub fn filter(&self, include: &[String], exclude: &[String]) -> Result<Snapshot, ScanError> {
+        let overrides = build_overrides(include, exclude)?;
+        let excluded = |path: &Path, is_dir: bool| {
+            !path.as_os_str().is_empty() && overrides.matched(path, is_dir).is_ignore()
+        };
+        let entries = self
+            .index()
+            .iter()
+            .filter(|entry| {
+                let is_dir = entry.entry_type == EntryType::Directory;
+                !excluded(&entry.path, is_dir)
+                    && !entry
+                        .path
+                        .ancestors()
+                        .skip(1)
+                        .any(|ancestor| excluded(ancestor, true))
+            })
+            .cloned()
+            .collect();
+
+        let mut result = computed(self, "filter", &[self], entries);
+        record_provenance(&mut result, "include", json!(include));
+        record_provenance(&mut result, "exclude", json!(exclude));
+        Ok(result)
+    }
+}
+
+impl Snapshot {
+    /// The subtree of this snapshot under `prefix`, re-rooted at it: `root`
+    /// becomes `<root>/<prefix>` and paths become relative to it, so that it
+    /// lines up with a snapshot taken of that directory alone.
+    ///
+    /// `prefix` is relative to the snapshot root, or an absolute path under it.
+    pub fn subtree(&self, prefix: impl AsRef<Path>) -> Result<Snapshot, ScanError> {
+        let prefix = prefix.as_ref();
+        let relative = if prefix.is_absolute() {
+            prefix.strip_prefix(&self.root).map_err(|_| {
+                ScanError::Operation(format!(
+                    "{} is not under the snapshot root {}",
+                    prefix.display(),
+                    self.root.display()
+                ))
+            })?
+        } else {
+            prefix
+        };
+        if relative
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(ScanError::Operation(format!(
+                "subtree {} must stay within the snapshot root",
+                prefix.display()
+            )));
+        }
+        // Drop `.` components and trailing separators
+        let relative: PathBuf = relative
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect();
+
+        let entries: Vec<SnapshotEntry> = self
+            .index()
+            .walk(&relative)
+            .map(|entry| {
+                let mut entry = entry.clone();
+                entry.path = entry.path.strip_prefix(&relative).unwrap().to_path_buf();
+                entry
+            })
+            .collect();
+        if entries.is_empty() {
+            return Err(ScanError::Operation(format!(
+                "{} records nothing under {}",
+                self.root.display(),
+                relative.display()
+            )));
+        }
+
+        let mut result = computed(self, "subtree", &[self], entries);
+        result.root = self.root.join(&relative);
+        record_provenance(&mut result, "prefix", json!(relative));
+        Ok(result)
+    }
+}
+
+/// Whether two entries record the same thing: same type, and for anything
+/// but directories the same size, content and link target.
+fn same_entry(a: &SnapshotEntry, b: &SnapshotEntry) -> bool {
+    if a.entry_type != b.entry_type {
+        return false;
+    }
+    if a.entry_type == EntryType::Directory {
+        return true;
+    }
+    let sizes_differ = matches!((a.size, b.size), (Some(a), Some(b)) if a != b);
+    !sizes_differ && !a.content_differs(b) && a.target == b.target
+}
+
+/// The snapshot computed by `operation` over `inputs`, with the header of
+/// `base` and `entries` in path order.
+fn computed(
+    base: &Snapshot,
+    operation: &str,
+    inputs: &[&Snapshot],
+    entries: Vec<SnapshotEntry>,
+) -> Snapshot {
+    let mut meta = Meta::new(
+        TOOL,
+        env!("CARGO_PKG_VERSION"),
+        true,
+        false,
+        true,
+        true, // a pure function of the inputs
+        0,
+        Utc::now(),
+    );
+    let inputs: Vec<Value> = inputs.iter().map(|input| provenance_of(input)).collect();
+    meta.extra.insert(
+        "provenance".to_string(),
+        json!({ "operation": operation, "inputs": inputs }),
+    );
+
+    let mut plumbah = PlumbahObject::new(
+        match base.plumbah.status {
+            Status::Ok => Status::Ok,
+            _ => Status::Partial,
+        },
+        meta,
+    );
+    plumbah.errors = base.plumbah.errors.clone();
+
+    Snapshot {
+        plumbah,
+        version: SNAPSHOT_VERSION.to_string(),
+        root: base.root.clone(),
+        checksum_algorithm: base.checksum_algorithm,
+        extra_checksums: base.extra_checksums.clone(),
+        checksum_key_id: base.checksum_key_id.clone(),
+        sample_len: base.sample_len,
+        count: entries.len(),
+        entries,
+    }
+}
+
+/// Add a parameter of the operation to the provenance of `computed`.
+fn record_provenance(snapshot: &mut Snapshot, key: &str, value: Value) {
+    if let Some(Value::Object(provenance)) = snapshot
+        .plumbah
+        .meta
+        .as_mut()
+        .and_then(|meta| meta.extra.get_mut("provenance"))
+    {
+        provenance.insert(key.to_string(), value);
+    }
+}
+
+/// What identifies an input snapshot in the provenance of a computed one.
+fn provenance_of(snapshot: &Snapshot) -> Value {
+    let meta = snapshot.plumbah.meta.as_ref();
+    json!({
+        "root": snapshot.root,
+        "count": snapshot.count,
+        "tool": meta.map(|meta| meta.tool.as_str()),
+        "timestamp": meta.map(|meta| meta.timestamp),
+    })
+}
+
+fn common_ancestor(a: &Path, b: &Path) -> PathBuf {
+    a.components()
+        .zip(b.components())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a)
+        .collect()
+}
+
+/// Include patterns are whitelist globs and exclude patterns ignore globs,
+/// as for the scanner's overrides.
+fn build_overrides(include: &[String], exclude: &[String]) -> Result<Override, ScanError> {
+    let mut builder = OverrideBuilder::new("");
+    let patterns = include
+        .iter()
+        .cloned()
+        .chain(exclude.iter().map(|pattern| format!("!{}", pattern)));
+    for pattern in patterns {
+        builder.add(&pattern).map_err(|err| {
+            ScanError::Operation(format!("invalid pattern '{}': {}", pattern, err))
+        })?;
+    }
+    builder
+        .build()
+        .map_err(|err| ScanError::Operation(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(root: &str, entries: Vec<SnapshotEntry>) -> Snapshot {
+        Snapshot {
+            root: PathBuf::from(root),
+            ..Snapshot::test_of(entries)
+        }
+    }
+
+    fn paths(snapshot: &Snapshot) -> Vec<&str> {
+        assert_eq!(snapshot.count, snapshot.entries.len());
+        snapshot
+            .entries
+            .iter()
+            .map(|entry| entry.path.to_str().unwrap())
+            .collect()
+    }
+
+    fn provenance(snapshot: &Snapshot) -> &Value {
+        &snapshot.plumbah.meta.as_ref().unwrap().extra["provenance"]
+    }
+
+    #[test]
+    fn test_merge_shards_of_one_root() {
+        let a = snapshot(
+            "/data",
+            vec![
+                SnapshotEntry::test_dir(""),
+                SnapshotEntry::test_dir("a"),
+                SnapshotEntry::test_file("a/1", "xxh3_64:01"),
+            ],
+        );
+        let b = snapshot(
+            "/data",
+            vec![
+                SnapshotEntry::test_dir(""),
+                SnapshotEntry::test_dir("b"),
+                SnapshotEntry::test_file("b/2", "xxh3_64:02"),
+            ],
+        );
+
+        let merged = Snapshot::merge(&[a, b]).unwrap();
+        assert_eq!(merged.root, PathBuf::from("/data"));
+        assert_eq!(paths(&merged), vec!["", "a", "a/1", "b", "b/2"]);
+        assert_eq!(provenance(&merged)["operation"], "merge");
+        assert_eq!(provenance(&merged)["inputs"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_merge_reroots_subtree_scans() {
+        let a = snapshot(
+            "/data/a",
+            vec![
+                SnapshotEntry::test_dir(""),
+                SnapshotEntry::test_file("1", "xxh3_64:01"),
+            ],
+        );
+        let b = snapshot(
+            "/data/b",
+            vec![
+                SnapshotEntry::test_dir(""),
+                SnapshotEntry::test_file("2", "xxh3_64:02"),
+            ],
+        );
+
+        let merged = Snapshot::merge(&[a, b]).unwrap();
+        assert_eq!(merged.root, PathBuf::from("/data"));
+        assert_eq!(paths(&merged), vec!["a", "a/1", "b", "b/2"]);
+    }
+
+    #[test]
+    fn test_merge_refuses_overlaps() {
+        let x = |checksum| snapshot("/data", vec![SnapshotEntry::test_file("x", checksum)]);
+
+        let merged = Snapshot::merge(&[x("xxh3_64:01"), x("xxh3_64:01")]).unwrap();
+        assert_eq!(paths(&merged), vec!["x"]);
+        let err = Snapshot::merge(&[x("xxh3_64:01"), x("xxh3_64:02")]).unwrap_err();
+        assert!(matches!(err, ScanError::Operation(_)), "{:?}", err);
+        assert!(Snapshot::merge(&[]).is_err());
+    }
+
+    #[test]
+    fn test_subtract_and_intersect() {
+        let baseline = snapshot(
+            "/data",
+            vec![
+                SnapshotEntry::test_dir(""),
+                SnapshotEntry::test_file("kept", "xxh3_64:01"),
+                SnapshotEntry::test_file("changed", "xxh3_64:02"),
+            ],
+        );
+        let current = snapshot(
+            "/data",
+            vec![
+                SnapshotEntry::test_dir(""),
+                SnapshotEntry::test_file("kept", "xxh3_64:01"),
+                SnapshotEntry::test_file("changed", "xxh3_64:ff"),
+                SnapshotEntry::test_file("new", "xxh3_64:03"),
+            ],
+        );
+
+        let subtracted = current.subtract(&baseline).unwrap();
+        assert_eq!(paths(&subtracted), vec!["changed", "new"]);
+        assert_eq!(provenance(&subtracted)["operation"], "subtract");
+
+        let intersected = current.intersect(&baseline).unwrap();
+        assert_eq!(paths(&intersected), vec!["", "changed", "kept"]);
+    }
+
+    #[test]
+    fn test_subtree_reroots() {
+        let host = snapshot(
+            "/",
+            vec![
+                SnapshotEntry::test_dir(""),
+                SnapshotEntry::test_dir("opt"),
+                SnapshotEntry::test_dir("opt/app"),
+                SnapshotEntry::test_file("opt/app/bin", "xxh3_64:01"),
+                SnapshotEntry::test_file("opt/application", "xxh3_64:02"),
+            ],
+        );
+
+        for prefix in ["opt/app", "./opt/app/", "/opt/app"] {
+            let app = host.subtree(prefix).unwrap();
+            assert_eq!(app.root, PathBuf::from("/opt/app"), "{}", prefix);
+            assert_eq!(paths(&app), vec!["", "bin"]);
+            assert_eq!(provenance(&app)["prefix"], "opt/app");
+        }
+        assert_eq!(paths(&host.subtree("").unwrap()).len(), 5);
+
+        for prefix in ["opt/missing", "../etc", "/srv"] {
+            let err = host.subtree(prefix).unwrap_err();
+            assert!(
+                matches!(err, ScanError::Operation(_)),
+                "{}: {:?}",
+                prefix,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_include_and_exclude() {
+        let current = snapshot(
+            "/data",
+            vec![
+                SnapshotEntry::test_dir(""),
+                SnapshotEntry::test_dir("logs"),
+                SnapshotEntry::test_file("logs/app.log", "xxh3_64:01"),
+                SnapshotEntry::test_dir("tmp"),
+                SnapshotEntry::test_file("tmp/cache.log", "xxh3_64:02"),
+                SnapshotEntry::test_file("readme.md", "xxh3_64:03"),
+            ],
+        );
+
+        let excluded = current.filter(&[], &["tmp/".to_string()]).unwrap();
+        assert_eq!(
+            paths(&excluded),
+            vec!["", "logs", "logs/app.log", "readme.md"]
+        );
+
+        let included = current
+            .filter(&["*.log".to_string()], &["tmp/".to_string()])
+            .unwrap();
+        assert_eq!(paths(&included), vec!["", "logs", "logs/app.log"]);
+        assert_eq!(provenance(&included)["exclude"], json!(["tmp/"]));
+
+        assert!(current.filter(&["a{".to_string()], &[]).is_err());
+    }
+}
@@ -1,66 +1,550 @@
 use blake3::Hasher as Blake3Impl;
-use sha2::{Digest, Sha256};
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
 use std::{
+    collections::BTreeMap,
+    fmt,
     fs::File,
     hash::Hasher,
-    io::{self, Read},
+    io::{self, Read, Seek, SeekFrom},
     path::Path,
+    str::FromStr,
+    sync::{Arc, RwLock},
 };
+use xxhash_rust::xxh3::Xxh3Default;
 
 use crate::snapshot::ChecksumAlgorithm;
 
-pub trait GaldiHasher {
-    fn hash_file(&self, path: &Path) -> io::Result<String>;
+/// An in-progress hash computation, fed one chunk at a time.
+///
+/// Obtained from `GaldiHasher::start` and turned into a checksum string with
+/// `GaldiHasher::finish`.
+pub trait GaldiDigest: Send {
+    fn update(&mut self, data: &[u8]);
+
+    /// Lowercase hex of the digest, without the algorithm prefix.
+    fn finalize(self: Box<Self>) -> String;
+}
+
+/// A checksum algorithm producing galdi's `algo:hex` checksum strings.
+///
+/// Implementors only provide `digest_name` and `start`; the remaining methods
+/// are derived from them. Algorithms other than `ChecksumAlgorithm` can be made
+/// available by name with `register_hasher`.
+pub trait GaldiHasher: Send + Sync {
+    /// Name of the algorithm, used as the prefix of its checksum strings.
+    fn digest_name(&self) -> &str;
+
+    /// Start an incremental hash computation.
+    fn start(&self) -> Box<dyn GaldiDigest>;
+
+    /// Finish an incremental computation started with `start`, returning the
+    /// `algo:hex` checksum string.
+    fn finish(&self, digest: Box<dyn GaldiDigest>) -> String {
+        format!("{}:{}", self.digest_name(), digest.finalize())
+    }
+
+    /// Hash everything readable from `reader`, returning the `algo:hex` checksum string.
+    fn hash_reader(&self, reader: &mut dyn Read) -> io::Result<String> {
+        let mut digest = self.start();
+        for_each_chunk(reader, |chunk| digest.update(chunk))?;
+        Ok(self.finish(digest))
+    }
+
+    /// Hash an in-memory buffer, returning the `algo:hex` checksum string.
+    fn hash_bytes(&self, data: &[u8]) -> String {
+        let mut digest = self.start();
+        digest.update(data);
+        self.finish(digest)
+    }
+
+    fn hash_file(&self, path: &Path) -> io::Result<String> {
+        let mut file = File::open(path)?;
+        self.hash_reader(&mut file)
+    }
+}
+
+/// Secret key material for keyed checksums (`blake3_keyed`, `hmac_sha256`).
+///
+/// Only the key's identity, a short fingerprint that does not reveal the key,
+/// is ever written to a snapshot.
+#[derive(Clone)]
+pub struct ChecksumKey {
+    material: Vec<u8>,
+}
+
+impl ChecksumKey {
+    /// Use `material` as the key. BLAKE3 derives its 32-byte key from it;
+    /// HMAC uses it as is.
+    pub fn new(material: impl Into<Vec<u8>>) -> io::Result<Self> {
+        let material = material.into();
+        if material.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "checksum key is empty",
+            ));
+        }
+        Ok(ChecksumKey { material })
+    }
+
+    /// Read the key from a file, byte for byte.
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Self::new(std::fs::read(path)?)
+    }
+
+    /// Read the key from an environment variable.
+    pub fn from_env(var: &str) -> io::Result<Self> {
+        let value = std::env::var(var)
+            .map_err(|err| io::Error::new(io::ErrorKind::NotFound, format!("{}: {}", var, err)))?;
+        Self::new(value)
+    }
+
+    /// The key named on the command line, if any: read from `file`, or else
+    /// from the environment variable `env`.
+    pub fn load(file: Option<&Path>, env: Option<&str>) -> io::Result<Option<Self>> {
+        match (file, env) {
+            (Some(file), _) => Self::from_file(file).map(Some),
+            (None, Some(env)) => Self::from_env(env).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Fingerprint identifying the key, recorded as `Snapshot::checksum_key_id`.
+    pub fn id(&self) -> String {
+        let fingerprint = blake3::derive_key("galdi checksum key id v1", &self.material);
+        to_hex(&fingerprint[..8])
+    }
+
+    fn blake3_key(&self) -> [u8; 32] {
+        blake3::derive_key("galdi keyed checksum v1", &self.material)
+    }
+}
+
+impl fmt::Debug for ChecksumKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChecksumKey")
+            .field("id", &self.id())
+            .finish_non_exhaustive()
+    }
 }
 
 pub struct XXH3_64Hasher;
 pub struct Sha256Hasher;
 pub struct Blake3Hasher;
+pub struct XXH128Hasher;
+pub struct Sha512Hasher;
+pub struct Sha1Hasher;
+pub struct Md5Hasher;
+pub struct Crc32cHasher;
+pub struct Blake3KeyedHasher {
+    pub key: ChecksumKey,
+}
+pub struct HmacSha256Hasher {
+    pub key: ChecksumKey,
+}
 
-impl GaldiHasher for XXH3_64Hasher {
-    fn hash_file(&self, path: &Path) -> io::Result<String> {
-        let mut file = File::open(path)?;
-        let mut hasher = xxhash_rust::xxh3::Xxh3Default::new();
-        let mut buffer = [0u8; 8192];
+/// In-progress hash computation for one algorithm.
+enum HashState {
+    XXH3_64(Box<Xxh3Default>),
+    Sha256(Sha256),
+    Blake3(Box<Blake3Impl>),
+    XXH128(Box<Xxh3Default>),
+    Sha512(Sha512),
+    Sha1(Sha1),
+    Md5(Md5),
+    Crc32c(u32),
+    HmacSha256(Box<Hmac<Sha256>>),
+}
 
-        loop {
-            let bytes_read = file.read(&mut buffer)?;
-            if bytes_read == 0 {
-                break;
+impl HashState {
+    /// The state for `algorithm`, keyed with `key` where it needs one.
+    /// Keyed algorithms without a key, and sampled algorithms, are an error.
+    fn new(algorithm: ChecksumAlgorithm, key: Option<&ChecksumKey>) -> io::Result<Self> {
+        let key = || key.ok_or_else(|| needs_key(algorithm));
+        Ok(match algorithm {
+            ChecksumAlgorithm::XXH3_64 => HashState::XXH3_64(Box::default()),
+            ChecksumAlgorithm::Sha256 => HashState::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Blake3 => HashState::Blake3(Box::default()),
+            ChecksumAlgorithm::XXH128 => HashState::XXH128(Box::default()),
+            ChecksumAlgorithm::Sha512 => HashState::Sha512(Sha512::new()),
+            ChecksumAlgorithm::Sha1 => HashState::Sha1(Sha1::new()),
+            ChecksumAlgorithm::Md5 => HashState::Md5(Md5::new()),
+            ChecksumAlgorithm::Crc32c => HashState::Crc32c(0),
+            ChecksumAlgorithm::Blake3Keyed => {
+                HashState::Blake3(Box::new(Blake3Impl::new_keyed(&key()?.blake3_key())))
             }
-            hasher.update(&buffer[..bytes_read]);
+            ChecksumAlgorithm::HmacSha256 => HashState::HmacSha256(Box::new(
+                Hmac::<Sha256>::new_from_slice(&key()?.material)
+                    .expect("HMAC accepts keys of any length"),
+            )),
+            ChecksumAlgorithm::XXH3_64Sampled => return Err(not_streamable(algorithm)),
+        })
+    }
+
+    fn update(&mut self, chunk: &[u8]) {
+        match self {
+            HashState::XXH3_64(hasher) | HashState::XXH128(hasher) => hasher.update(chunk),
+            HashState::Sha256(hasher) => hasher.update(chunk),
+            HashState::Blake3(hasher) => {
+                hasher.update(chunk);
+            }
+            HashState::Sha512(hasher) => hasher.update(chunk),
+            HashState::Sha1(hasher) => hasher.update(chunk),
+            HashState::Md5(hasher) => hasher.update(chunk),
+            HashState::Crc32c(crc) => *crc = crc32c::crc32c_append(*crc, chunk),
+            HashState::HmacSha256(mac) => mac.update(chunk),
         }
+    }
 
-        Ok(format!("xxh3_64:{:016x}", hasher.finish()))
+    /// Lowercase hex of the digest.
+    fn into_hex(self) -> String {
+        match self {
+            HashState::XXH3_64(hasher) => format!("{:016x}", hasher.finish()),
+            HashState::Sha256(hasher) => format!("{:064x}", hasher.finalize()),
+            HashState::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            HashState::XXH128(hasher) => format!("{:032x}", hasher.digest128()),
+            HashState::Sha512(hasher) => to_hex(&hasher.finalize()),
+            HashState::Sha1(hasher) => to_hex(&hasher.finalize()),
+            HashState::Md5(hasher) => to_hex(&hasher.finalize()),
+            HashState::Crc32c(crc) => format!("{:08x}", crc),
+            HashState::HmacSha256(mac) => to_hex(&mac.finalize().into_bytes()),
+        }
+    }
+}
+
+impl GaldiDigest for HashState {
+    fn update(&mut self, data: &[u8]) {
+        HashState::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> String {
+        self.into_hex()
+    }
+}
+
+/// Feed `reader` to `update` in fixed-size chunks.
+fn for_each_chunk(reader: &mut dyn Read, mut update: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+        update(&buffer[..bytes_read]);
+    }
+}
+
+/// Sampled checksums read from several places in a file, so streams cannot
+/// be hashed with them.
+fn needs_key(algorithm: ChecksumAlgorithm) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{} checksums need a key", algorithm.name()),
+    )
+}
+
+fn not_streamable(algorithm: ChecksumAlgorithm) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "{} checksums can only be taken of files on disk",
+            algorithm.name()
+        ),
+    )
+}
+
+/// Bytes taken from each of the start, middle and end of a file by `xxh3_64s`,
+/// unless a scan asks for another `ScanOptions::sample_len`.
+pub const DEFAULT_SAMPLE_LEN: u64 = 64 * 1024;
+
+/// The `xxh3_64s` checksum of a seekable file: XXH3 over its size and its
+/// first, middle and last `sample_len` bytes. Files of up to three samples
+/// are hashed whole.
+///
+/// Only a fraction of large files is read, so a different checksum proves a
+/// change while an equal one only makes it unlikely. Checksums taken with
+/// different sample lengths are not comparable.
+pub fn hash_sampled<R: Read + Seek>(reader: &mut R, sample_len: u64) -> io::Result<String> {
+    let size = reader.seek(SeekFrom::End(0))?;
+    let mut hasher = Xxh3Default::new();
+    hasher.update(&size.to_le_bytes());
+    if size <= 3 * sample_len {
+        reader.seek(SeekFrom::Start(0))?;
+        for_each_chunk(reader, |chunk| hasher.update(chunk))?;
+    } else {
+        let mut sample = vec![0u8; sample_len as usize];
+        for offset in [0, (size - sample_len) / 2, size - sample_len] {
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut sample)?;
+            hasher.update(&sample);
+        }
+    }
+    Ok(format!(
+        "{}:{:016x}",
+        ChecksumAlgorithm::XXH3_64Sampled.name(),
+        hasher.digest()
+    ))
+}
+
+/// Lowercase hex of a digest output.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl GaldiHasher for XXH3_64Hasher {
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::XXH3_64.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(HashState::XXH3_64(Box::default()))
     }
 }
 
 impl GaldiHasher for Sha256Hasher {
-    fn hash_file(&self, path: &Path) -> io::Result<String> {
-        let mut file = File::open(path)?;
-        let mut hasher = Sha256::new();
-        io::copy(&mut file, &mut hasher)?;
-        Ok(format!("sha256:{:064x}", hasher.finalize()))
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::Sha256.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(HashState::Sha256(Sha256::new()))
     }
 }
 
 impl GaldiHasher for Blake3Hasher {
-    fn hash_file(&self, path: &Path) -> io::Result<String> {
-        let mut file = File::open(path)?;
-        let mut hasher = Blake3Impl::new();
-        io::copy(&mut file, &mut hasher)?;
-        Ok(format!("blake3:{}", hasher.finalize().to_hex()))
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::Blake3.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(HashState::Blake3(Box::default()))
+    }
+}
+
+impl GaldiHasher for XXH128Hasher {
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::XXH128.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(HashState::XXH128(Box::default()))
+    }
+}
+
+impl GaldiHasher for Sha512Hasher {
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::Sha512.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(HashState::Sha512(Sha512::new()))
+    }
+}
+
+impl GaldiHasher for Sha1Hasher {
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::Sha1.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(HashState::Sha1(Sha1::new()))
     }
 }
 
-pub fn get_hasher(algorithm: ChecksumAlgorithm) -> Box<dyn GaldiHasher> {
-    match algorithm {
+impl GaldiHasher for Md5Hasher {
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::Md5.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(HashState::Md5(Md5::new()))
+    }
+}
+
+impl GaldiHasher for Crc32cHasher {
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::Crc32c.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(HashState::Crc32c(0))
+    }
+}
+
+impl GaldiHasher for Blake3KeyedHasher {
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::Blake3Keyed.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(HashState::Blake3(Box::new(Blake3Impl::new_keyed(
+            &self.key.blake3_key(),
+        ))))
+    }
+}
+
+impl GaldiHasher for HmacSha256Hasher {
+    fn digest_name(&self) -> &str {
+        ChecksumAlgorithm::HmacSha256.name()
+    }
+
+    fn start(&self) -> Box<dyn GaldiDigest> {
+        Box::new(
+            HashState::new(ChecksumAlgorithm::HmacSha256, Some(&self.key)).expect("a key is given"),
+        )
+    }
+}
+
+/// The hasher for an unkeyed algorithm. Keyed algorithms, which need a key,
+/// are an error: use `get_keyed_hasher` for those. So are sampled algorithms,
+/// which need a seekable file: use `hash_sampled`.
+pub fn get_hasher(algorithm: ChecksumAlgorithm) -> io::Result<Box<dyn GaldiHasher>> {
+    Ok(match algorithm {
         ChecksumAlgorithm::XXH3_64 => Box::new(XXH3_64Hasher),
         ChecksumAlgorithm::Sha256 => Box::new(Sha256Hasher),
         ChecksumAlgorithm::Blake3 => Box::new(Blake3Hasher),
+        ChecksumAlgorithm::XXH128 => Box::new(XXH128Hasher),
+        ChecksumAlgorithm::Sha512 => Box::new(Sha512Hasher),
+        ChecksumAlgorithm::Sha1 => Box::new(Sha1Hasher),
+        ChecksumAlgorithm::Md5 => Box::new(Md5Hasher),
+        ChecksumAlgorithm::Crc32c => Box::new(Crc32cHasher),
+        ChecksumAlgorithm::Blake3Keyed | ChecksumAlgorithm::HmacSha256 => {
+            return Err(needs_key(algorithm));
+        }
+        ChecksumAlgorithm::XXH3_64Sampled => return Err(not_streamable(algorithm)),
+    })
+}
+
+/// The hasher for any streamable algorithm, keyed with `key` where the
+/// algorithm needs one. Sampled algorithms are an error.
+pub fn get_keyed_hasher(
+    algorithm: ChecksumAlgorithm,
+    key: Option<&ChecksumKey>,
+) -> io::Result<Box<dyn GaldiHasher>> {
+    match (algorithm, key) {
+        (ChecksumAlgorithm::Blake3Keyed, Some(key)) => {
+            Ok(Box::new(Blake3KeyedHasher { key: key.clone() }))
+        }
+        (ChecksumAlgorithm::HmacSha256, Some(key)) => {
+            Ok(Box::new(HmacSha256Hasher { key: key.clone() }))
+        }
+        (algorithm, _) if algorithm.is_sampled() => Err(not_streamable(algorithm)),
+        (algorithm, None) if algorithm.is_keyed() => Err(needs_key(algorithm)),
+        (algorithm, _) => get_hasher(algorithm),
     }
 }
 
+/// Computes several checksums from a single read, fanning each buffer out to
+/// one hash state per algorithm.
+pub struct MultiHasher {
+    algorithms: Vec<ChecksumAlgorithm>,
+    key: Option<ChecksumKey>,
+}
+
+impl MultiHasher {
+    /// Duplicate algorithms are only computed once.
+    pub fn new(algorithms: &[ChecksumAlgorithm]) -> Self {
+        let mut unique = Vec::with_capacity(algorithms.len());
+        for algorithm in algorithms {
+            if !unique.contains(algorithm) {
+                unique.push(*algorithm);
+            }
+        }
+        MultiHasher {
+            algorithms: unique,
+            key: None,
+        }
+    }
+
+    /// Key for the keyed algorithms among `algorithms`.
+    pub fn with_key(mut self, key: Option<ChecksumKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Hash everything readable from `reader` with every algorithm.
+    pub fn hash_reader(
+        &self,
+        reader: &mut dyn Read,
+    ) -> io::Result<BTreeMap<ChecksumAlgorithm, String>> {
+        let mut states = self
+            .algorithms
+            .iter()
+            .map(|algorithm| Ok((*algorithm, HashState::new(*algorithm, self.key.as_ref())?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        for_each_chunk(reader, |chunk| {
+            for (_, state) in states.iter_mut() {
+                state.update(chunk);
+            }
+        })?;
+        Ok(states
+            .into_iter()
+            .map(|(algorithm, state)| {
+                (
+                    algorithm,
+                    format!("{}:{}", algorithm.name(), state.into_hex()),
+                )
+            })
+            .collect())
+    }
+
+    pub fn hash_file(&self, path: &Path) -> io::Result<BTreeMap<ChecksumAlgorithm, String>> {
+        let mut file = File::open(path)?;
+        self.hash_reader(&mut file)
+    }
+}
+
+/// Hashers added with `register_hasher`, by digest name.
+static REGISTRY: RwLock<BTreeMap<String, Arc<dyn GaldiHasher>>> = RwLock::new(BTreeMap::new());
+
+/// Make a hasher for an algorithm galdi does not ship available to
+/// `lookup_hasher` under its `digest_name`.
+///
+/// Returns `false`, leaving the registry unchanged, when the name is empty,
+/// contains `:` or whitespace, or is already taken by a built-in algorithm or
+/// an earlier registration. Registered algorithms can hash anything through
+/// the `GaldiHasher` API, but scans and snapshots only use `ChecksumAlgorithm`.
+pub fn register_hasher(hasher: Arc<dyn GaldiHasher>) -> bool {
+    let name = hasher.digest_name().to_string();
+    if name.is_empty()
+        || name.contains(|c: char| c == ':' || c.is_whitespace())
+        || ChecksumAlgorithm::from_str(&name).is_ok()
+    {
+        return false;
+    }
+
+    let mut registry = REGISTRY.write().unwrap_or_else(|err| err.into_inner());
+    if registry.contains_key(&name) {
+        return false;
+    }
+    registry.insert(name, hasher);
+    true
+}
+
+/// The hasher for a digest name: a built-in `ChecksumAlgorithm` name such as
+/// `sha256`, or a name added with `register_hasher`.
+pub fn lookup_hasher(name: &str) -> Option<Arc<dyn GaldiHasher>> {
+    if let Ok(algorithm) = ChecksumAlgorithm::from_str(name) {
+        // Keyed algorithms cannot be looked up without their key, nor sampled
+        // ones used on streams.
+        return get_hasher(algorithm).ok().map(Arc::from);
+    }
+    REGISTRY
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(name)
+        .cloned()
+}
+
+/// Split an `algo:hex` checksum string into its algorithm and hex digest.
+pub fn parse_checksum(checksum: &str) -> Option<(ChecksumAlgorithm, &str)> {
+    let (name, hex) = checksum.split_once(':')?;
+    Some((ChecksumAlgorithm::from_str(name).ok()?, hex))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,7 +674,7 @@ mod tests {
 
     #[test]
     fn test_get_hasher_xxh3() {
-        let hasher = get_hasher(ChecksumAlgorithm::XXH3_64);
+        let hasher = get_hasher(ChecksumAlgorithm::XXH3_64).unwrap();
 
         let mut temp_file = NamedTempFile::new().unwrap();
         temp_file.write_all(b"test").unwrap();
@@ -202,7 +686,7 @@ mod tests {
 
     #[test]
     fn test_get_hasher_sha256() {
-        let hasher = get_hasher(ChecksumAlgorithm::Sha256);
+        let hasher = get_hasher(ChecksumAlgorithm::Sha256).unwrap();
 
         let mut temp_file = NamedTempFile::new().unwrap();
         temp_file.write_all(b"test").unwrap();
@@ -286,9 +770,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hash_reader_matches_hash_file() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b"hello world").unwrap();
+        temp_file.flush().unwrap();
+
+        for algorithm in [
+            ChecksumAlgorithm::XXH3_64,
+            ChecksumAlgorithm::Sha256,
+            ChecksumAlgorithm::Blake3,
+            ChecksumAlgorithm::XXH128,
+            ChecksumAlgorithm::Sha512,
+            ChecksumAlgorithm::Sha1,
+            ChecksumAlgorithm::Md5,
+            ChecksumAlgorithm::Crc32c,
+        ] {
+            let hasher = get_hasher(algorithm).unwrap();
+            let from_file = hasher.hash_file(temp_file.path()).unwrap();
+            let from_reader = hasher.hash_reader(&mut &b"hello world"[..]).unwrap();
+            assert_eq!(from_file, from_reader);
+        }
+    }
+
     #[test]
     fn test_get_hasher_blake3() {
-        let hasher = get_hasher(ChecksumAlgorithm::Blake3);
+        let hasher = get_hasher(ChecksumAlgorithm::Blake3).unwrap();
 
         let mut temp_file = NamedTempFile::new().unwrap();
         temp_file.write_all(b"test").unwrap();
@@ -297,4 +804,269 @@ mod tests {
         let result = hasher.hash_file(temp_file.path()).unwrap();
         assert!(result.starts_with("blake3:"));
     }
+
+    #[test]
+    fn test_known_content_legacy_and_extra_algorithms() {
+        // Reference values from sha512sum, sha1sum, md5sum, xxhsum -H2 and
+        // the CRC-32C check value for "123456789".
+        let cases = [
+            (
+                ChecksumAlgorithm::Sha512,
+                &b"hello world"[..],
+                "sha512:309ecc489c12d6eb4cc40f50c902f2b4d0ed77ee511a7c7a9bcd3ca86d4cd86f989dd35bc5ff499670da34255b45b0cfd830e81f605dcf7dc5542e93ae9cd76f",
+            ),
+            (
+                ChecksumAlgorithm::Sha1,
+                &b"hello world"[..],
+                "sha1:2aae6c35c94fcfb415dbe95f408b9ce91ee846ed",
+            ),
+            (
+                ChecksumAlgorithm::Md5,
+                &b"hello world"[..],
+                "md5:5eb63bbbe01eeed093cb22bb8f5acdc3",
+            ),
+            (
+                ChecksumAlgorithm::XXH128,
+                &b""[..],
+                "xxh128:99aa06d3014798d86001c324468d497f",
+            ),
+            (
+                ChecksumAlgorithm::Crc32c,
+                &b"123456789"[..],
+                "crc32c:e3069283",
+            ),
+        ];
+
+        for (algorithm, input, expected) in cases {
+            let result = get_hasher(algorithm)
+                .unwrap()
+                .hash_reader(&mut &input[..])
+                .unwrap();
+            assert_eq!(result, expected, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_multi_hasher_matches_single_hashers() {
+        let algorithms = [
+            ChecksumAlgorithm::XXH3_64,
+            ChecksumAlgorithm::Sha256,
+            ChecksumAlgorithm::Crc32c,
+            ChecksumAlgorithm::Sha256,
+        ];
+        let checksums = MultiHasher::new(&algorithms)
+            .hash_reader(&mut &b"hello world"[..])
+            .unwrap();
+
+        assert_eq!(checksums.len(), 3);
+        for (algorithm, checksum) in &checksums {
+            let expected = get_hasher(*algorithm)
+                .unwrap()
+                .hash_reader(&mut &b"hello world"[..])
+                .unwrap();
+            assert_eq!(checksum, &expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        assert_eq!(
+            parse_checksum("sha256:abcd"),
+            Some((ChecksumAlgorithm::Sha256, "abcd"))
+        );
+        assert_eq!(
+            parse_checksum("xxh3_64:2d06800538d394c2"),
+            Some((ChecksumAlgorithm::XXH3_64, "2d06800538d394c2"))
+        );
+        assert_eq!(parse_checksum("whirlpool:abcd"), None);
+        assert_eq!(parse_checksum("abcd"), None);
+    }
+
+    #[test]
+    fn test_streaming_matches_hash_reader() {
+        let data = vec![7u8; 20_000];
+        for algorithm in [
+            ChecksumAlgorithm::XXH3_64,
+            ChecksumAlgorithm::Blake3,
+            ChecksumAlgorithm::Sha512,
+            ChecksumAlgorithm::Crc32c,
+        ] {
+            let hasher = get_hasher(algorithm).unwrap();
+            let mut digest = hasher.start();
+            for chunk in data.chunks(3_000) {
+                digest.update(chunk);
+            }
+            let streamed = hasher.finish(digest);
+
+            assert_eq!(streamed, hasher.hash_reader(&mut &data[..]).unwrap());
+            assert_eq!(streamed, hasher.hash_bytes(&data));
+            assert_eq!(hasher.digest_name(), algorithm.name());
+        }
+    }
+
+    /// Sum of all bytes, as a toy downstream algorithm.
+    struct ByteSumHasher;
+    struct ByteSum(u64);
+
+    impl GaldiDigest for ByteSum {
+        fn update(&mut self, data: &[u8]) {
+            self.0 += data.iter().map(|b| *b as u64).sum::<u64>();
+        }
+
+        fn finalize(self: Box<Self>) -> String {
+            format!("{:016x}", self.0)
+        }
+    }
+
+    impl GaldiHasher for ByteSumHasher {
+        fn digest_name(&self) -> &str {
+            "bytesum"
+        }
+
+        fn start(&self) -> Box<dyn GaldiDigest> {
+            Box::new(ByteSum(0))
+        }
+    }
+
+    #[test]
+    fn test_registered_hasher_is_found_by_name() {
+        assert!(lookup_hasher("bytesum").is_none());
+        assert!(register_hasher(Arc::new(ByteSumHasher)));
+        assert!(!register_hasher(Arc::new(ByteSumHasher)));
+
+        let hasher = lookup_hasher("bytesum").unwrap();
+        assert_eq!(hasher.hash_bytes(&[1, 2, 3]), "bytesum:0000000000000006");
+        assert_eq!(
+            lookup_hasher("sha256").unwrap().hash_bytes(b""),
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_register_hasher_rejects_builtin_names() {
+        struct Impostor;
+        impl GaldiHasher for Impostor {
+            fn digest_name(&self) -> &str {
+                "sha256"
+            }
+
+            fn start(&self) -> Box<dyn GaldiDigest> {
+                Box::new(ByteSum(0))
+            }
+        }
+
+        assert!(!register_hasher(Arc::new(Impostor)));
+        assert!(
+            lookup_hasher("sha256")
+                .unwrap()
+                .hash_bytes(b"")
+                .starts_with("sha256:e3b0")
+        );
+    }
+
+    #[test]
+    fn test_keyed_checksums_depend_on_key() {
+        let key = ChecksumKey::new("correct horse").unwrap();
+        let other = ChecksumKey::new("battery staple").unwrap();
+
+        for algorithm in [
+            ChecksumAlgorithm::Blake3Keyed,
+            ChecksumAlgorithm::HmacSha256,
+        ] {
+            let hasher = get_keyed_hasher(algorithm, Some(&key)).unwrap();
+            let checksum = hasher.hash_bytes(b"payload");
+            assert!(checksum.starts_with(&format!("{}:", algorithm.name())));
+            assert_eq!(checksum.len(), algorithm.name().len() + 1 + 64);
+
+            let again = get_keyed_hasher(algorithm, Some(&key)).unwrap();
+            assert_eq!(again.hash_bytes(b"payload"), checksum);
+            let rekeyed = get_keyed_hasher(algorithm, Some(&other)).unwrap();
+            assert_ne!(rekeyed.hash_bytes(b"payload"), checksum);
+
+            let multi = MultiHasher::new(&[algorithm])
+                .with_key(Some(key.clone()))
+                .hash_reader(&mut &b"payload"[..])
+                .unwrap();
+            assert_eq!(multi[&algorithm], checksum);
+
+            assert!(get_keyed_hasher(algorithm, None).is_err());
+            let error = get_hasher(algorithm).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert!(lookup_hasher(algorithm.name()).is_none());
+            assert!(
+                MultiHasher::new(&[algorithm])
+                    .hash_reader(&mut &b""[..])
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_hmac_sha256_matches_rfc_4231() {
+        // RFC 4231, test case 2
+        let key = ChecksumKey::new("Jefe").unwrap();
+        let hasher = get_keyed_hasher(ChecksumAlgorithm::HmacSha256, Some(&key)).unwrap();
+        assert_eq!(
+            hasher.hash_bytes(b"what do ya want for nothing?"),
+            "hmac_sha256:5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_checksum_key_id_does_not_reveal_key() {
+        let key = ChecksumKey::new("hunter2").unwrap();
+        assert_eq!(key.id().len(), 16);
+        assert_eq!(key.id(), ChecksumKey::new("hunter2").unwrap().id());
+        assert!(!format!("{:?}", key).contains("hunter2"));
+        assert!(ChecksumKey::new("").is_err());
+    }
+
+    #[test]
+    fn test_sampled_checksum_reads_start_middle_and_end() {
+        let sample = DEFAULT_SAMPLE_LEN as usize;
+        let content = vec![7u8; 10 * sample];
+        let checksum = |content: &[u8]| {
+            hash_sampled(&mut io::Cursor::new(content), DEFAULT_SAMPLE_LEN).unwrap()
+        };
+        let original = checksum(&content);
+        assert!(original.starts_with("xxh3_64s:"));
+        assert_eq!(original.len(), "xxh3_64s:".len() + 16);
+
+        // Changes inside any of the samples, or to the size, are seen
+        for offset in [0, (content.len() - sample) / 2, content.len() - 1] {
+            let mut changed = content.clone();
+            changed[offset] = 8;
+            assert_ne!(checksum(&changed), original, "change at {}", offset);
+        }
+        assert_ne!(checksum(&content[1..]), original);
+
+        // A change between the samples is not
+        let mut changed = content.clone();
+        changed[2 * sample] = 8;
+        assert_eq!(checksum(&changed), original);
+
+        // Small files are hashed whole
+        let mut small = vec![7u8; 2 * sample];
+        let before = checksum(&small);
+        small[sample + 1] = 8;
+        assert_ne!(checksum(&small), before);
+
+        // Larger samples see more
+        let larger = |content: &[u8]| {
+            hash_sampled(&mut io::Cursor::new(content), 3 * DEFAULT_SAMPLE_LEN).unwrap()
+        };
+        assert_ne!(larger(&changed), larger(&content));
+    }
+
+    #[test]
+    fn test_sampled_checksums_refuse_streams() {
+        assert!(get_keyed_hasher(ChecksumAlgorithm::XXH3_64Sampled, None).is_err());
+        let error = get_hasher(ChecksumAlgorithm::XXH3_64Sampled).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(lookup_hasher("xxh3_64s").is_none());
+        let error = MultiHasher::new(&[ChecksumAlgorithm::XXH3_64Sampled])
+            .hash_reader(&mut &b"data"[..])
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
 }
@@ -0,0 +1,325 @@
+//! Opt-in cache of file checksums in extended attributes.
+//!
+//! Each algorithm's checksum is stored as `user.galdi.<algorithm>`, together
+//! with the size and mtime the file had when it was hashed. Later scans trust
+//! a cached value only while both still match exactly; entries that are
+//! stale, unparsable or recorded for another algorithm are ignored, and the
+//! file is hashed again. Sampled checksums also record the sample length
+//! they were taken with, and are only reused by scans with the same one.
+//!
+//! The ctime is not compared: writing the attribute moves it, so no entry
+//! could ever record the ctime its file ends up with. A rewrite that keeps
+//! the size and restores the mtime therefore goes unnoticed, as does anyone
+//! able to write the attributes themselves; `verify_snapshot` never uses the
+//! cache.
+
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::content_type::ContentType;
+use crate::snapshot::ChecksumAlgorithm;
+
+/// What is stored in `user.galdi.<algorithm>`.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    checksum: String,
+    content_type: ContentType,
+    size: u64,
+    mtime_ns: i64,
+    /// Bytes per sample, for sampled algorithms only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sample_len: Option<u64>,
+}
+
+/// The attributes of a file that a cache entry is only valid for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStamp {
+    size: u64,
+    mtime_ns: i64,
+}
+
+impl FileStamp {
+    /// `None` where extended attributes are not supported.
+    #[cfg(unix)]
+    pub(crate) fn of(metadata: &Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        Some(FileStamp {
+            size: metadata.size(),
+            mtime_ns: metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn of(_metadata: &Metadata) -> Option<Self> {
+        None
+    }
+}
+
+/// Cached checksums for every one of `algorithms` and the content type, or
+/// `None` unless all of them are present and still valid for `stamp`, with
+/// sampled ones taken `sample_len` bytes at a time.
+pub(crate) fn lookup(
+    path: &Path,
+    stamp: FileStamp,
+    algorithms: &[ChecksumAlgorithm],
+    sample_len: u64,
+) -> Option<(BTreeMap<ChecksumAlgorithm, String>, ContentType)> {
+    let mut checksums = BTreeMap::new();
+    let mut content_type = None;
+    for &algorithm in algorithms {
+        let value = read_attribute(path, &attribute_name(algorithm))?;
+        let entry: CacheEntry = serde_json::from_slice(&value).ok()?;
+        if !entry.is_valid(algorithm, stamp, sample_len)
+            || content_type.is_some_and(|t| t != entry.content_type)
+        {
+            return None;
+        }
+        content_type = Some(entry.content_type);
+        checksums.insert(algorithm, entry.checksum);
+    }
+    Some((checksums, content_type?))
+}
+
+/// Record `checksums` for a file that had `stamp` while it was hashed, with
+/// sampled ones taken `sample_len` bytes at a time. Failures (read-only files, filesystems without user attributes) are ignored.
+pub(crate) fn store(
+    path: &Path,
+    stamp: FileStamp,
+    checksums: &BTreeMap<ChecksumAlgorithm, String>,
+    content_type: ContentType,
+    sample_len: u64,
+) {
+    for (&algorithm, checksum) in checksums {
+        let entry = CacheEntry {
+            checksum: checksum.clone(),
+            content_type,
+            size: stamp.size,
+            mtime_ns: stamp.mtime_ns,
+            sample_len: algorithm.is_sampled().then_some(sample_len),
+        };
+        let value = serde_json::to_vec(&entry).expect("cache entries always serialize");
+        if !write_attribute(path, &attribute_name(algorithm), &value) {
+            return;
+        }
+    }
+}
+
+impl CacheEntry {
+    fn is_valid(&self, algorithm: ChecksumAlgorithm, stamp: FileStamp, sample_len: u64) -> bool {
+        let well_formed = self
+            .checksum
+            .strip_prefix(algorithm.name())
+            .and_then(|rest| rest.strip_prefix(':'))
+            .is_some_and(|hex| !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit()));
+        well_formed
+            && stamp.size == self.size
+            && stamp.mtime_ns == self.mtime_ns
+            && self.sample_len == algorithm.is_sampled().then_some(sample_len)
+    }
+}
+
+fn attribute_name(algorithm: ChecksumAlgorithm) -> String {
+    format!("user.galdi.{}", algorithm.name())
+}
+
+#[cfg(unix)]
+fn read_attribute(path: &Path, name: &str) -> Option<Vec<u8>> {
+    xattr::get_deref(path, name).ok().flatten()
+}
+
+#[cfg(not(unix))]
+fn read_attribute(_path: &Path, _name: &str) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(unix)]
+fn write_attribute(path: &Path, name: &str, value: &[u8]) -> bool {
+    xattr::set_deref(path, name, value).is_ok()
+}
+
+#[cfg(not(unix))]
+fn write_attribute(_path: &Path, _name: &str, _value: &[u8]) -> bool {
+    false
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::checksum::DEFAULT_SAMPLE_LEN;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn stamp(file: &NamedTempFile) -> FileStamp {
+        FileStamp::of(&file.as_file().metadata().unwrap()).unwrap()
+    }
+
+    /// Store a fake checksum, or `None` where the filesystem has no user
+    /// attributes, in which case the calling test is skipped with a warning.
+    fn cached_file() -> Option<NamedTempFile> {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"media").unwrap();
+        let checksums = BTreeMap::from([(
+            ChecksumAlgorithm::Sha256,
+            "sha256:0123456789abcdef".to_string(),
+        )]);
+        store(
+            file.path(),
+            stamp(&file),
+            &checksums,
+            ContentType::Binary,
+            DEFAULT_SAMPLE_LEN,
+        );
+        let cached = read_attribute(file.path(), "user.galdi.sha256").map(|_| file);
+        if cached.is_none() {
+            eprintln!(
+                "SKIPPED: {} has no user extended attributes",
+                std::env::temp_dir().display()
+            );
+        }
+        cached
+    }
+
+    #[test]
+    fn test_cached_checksum_is_trusted_while_file_is_unchanged() {
+        let Some(file) = cached_file() else { return };
+        let (checksums, content_type) = lookup(
+            file.path(),
+            stamp(&file),
+            &[ChecksumAlgorithm::Sha256],
+            DEFAULT_SAMPLE_LEN,
+        )
+        .unwrap();
+        assert_eq!(
+            checksums[&ChecksumAlgorithm::Sha256],
+            "sha256:0123456789abcdef"
+        );
+        assert_eq!(content_type, ContentType::Binary);
+
+        // Other algorithms were never cached
+        assert!(
+            lookup(
+                file.path(),
+                stamp(&file),
+                &[ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Blake3],
+                DEFAULT_SAMPLE_LEN
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_stale_entry_is_ignored() {
+        let Some(mut file) = cached_file() else {
+            return;
+        };
+        file.write_all(b" and more").unwrap();
+        assert!(
+            lookup(
+                file.path(),
+                stamp(&file),
+                &[ChecksumAlgorithm::Sha256],
+                DEFAULT_SAMPLE_LEN
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_rewrite_with_a_new_mtime_is_noticed() {
+        let Some(file) = cached_file() else { return };
+        let mtime = file.as_file().metadata().unwrap().modified().unwrap();
+        file.as_file()
+            .set_modified(mtime + std::time::Duration::from_secs(1))
+            .unwrap();
+        assert!(
+            lookup(
+                file.path(),
+                stamp(&file),
+                &[ChecksumAlgorithm::Sha256],
+                DEFAULT_SAMPLE_LEN
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_corrupt_entry_is_ignored() {
+        let Some(file) = cached_file() else { return };
+        xattr::set(file.path(), "user.galdi.sha256", b"{\"checksum\":").unwrap();
+        assert!(
+            lookup(
+                file.path(),
+                stamp(&file),
+                &[ChecksumAlgorithm::Sha256],
+                DEFAULT_SAMPLE_LEN
+            )
+            .is_none()
+        );
+
+        // Well-formed JSON for the wrong algorithm
+        let mut entry: CacheEntry = serde_json::from_value(serde_json::json!({
+            "checksum": "md5:0123", "content_type": "binary", "size": 5, "mtime_ns": 0
+        }))
+        .unwrap();
+        entry.mtime_ns = stamp(&file).mtime_ns;
+        xattr::set(
+            file.path(),
+            "user.galdi.sha256",
+            &serde_json::to_vec(&entry).unwrap(),
+        )
+        .unwrap();
+        assert!(
+            lookup(
+                file.path(),
+                stamp(&file),
+                &[ChecksumAlgorithm::Sha256],
+                DEFAULT_SAMPLE_LEN
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_sampled_checksum_is_only_reused_with_its_sample_len() {
+        let Some(file) = cached_file() else { return };
+        let sampled = [ChecksumAlgorithm::XXH3_64Sampled];
+        let checksums = BTreeMap::from([(sampled[0], "xxh3_64s:0123456789abcdef".to_string())]);
+        store(
+            file.path(),
+            stamp(&file),
+            &checksums,
+            ContentType::Binary,
+            16,
+        );
+
+        let (cached, _) = lookup(file.path(), stamp(&file), &sampled, 16).unwrap();
+        assert_eq!(cached, checksums);
+        assert!(lookup(file.path(), stamp(&file), &sampled, DEFAULT_SAMPLE_LEN).is_none());
+
+        // The sample length does not matter to streamed checksums
+        assert!(lookup(file.path(), stamp(&file), &[ChecksumAlgorithm::Sha256], 16).is_some());
+    }
+
+    #[test]
+    fn test_sampled_entry_without_sample_len_is_ignored() {
+        let Some(file) = cached_file() else { return };
+        let entry = CacheEntry {
+            checksum: "xxh3_64s:0123456789abcdef".to_string(),
+            content_type: ContentType::Binary,
+            size: stamp(&file).size,
+            mtime_ns: stamp(&file).mtime_ns,
+            sample_len: None,
+        };
+        xattr::set(
+            file.path(),
+            "user.galdi.xxh3_64s",
+            &serde_json::to_vec(&entry).unwrap(),
+        )
+        .unwrap();
+        let sampled = [ChecksumAlgorithm::XXH3_64Sampled];
+        assert!(lookup(file.path(), stamp(&file), &sampled, DEFAULT_SAMPLE_LEN).is_none());
+    }
+}
@@ -0,0 +1,414 @@
+//! Content type detection from magic bytes.
+//!
+//! Detection runs on the first bytes of a file as they pass through the
+//! hashing pass (see `ContentSniffer`), so it never costs a second read.
+
+use std::io::{self, Read};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::elf::{ELF_CAPTURE_LIMIT, ElfInfo};
+
+/// Number of leading bytes inspected for magic numbers and the text/binary check.
+pub const SNIFF_LEN: usize = 8192;
+
+/// Coarse classification of file content, for spotting e.g. a `.txt` that
+/// turned into an executable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    /// Zero-length file.
+    Empty,
+    /// ELF executable, shared library or object file.
+    Elf,
+    /// Windows PE/COFF (or plain DOS `MZ`) executable.
+    Pe,
+    /// Mach-O executable or library, including universal binaries.
+    MachO,
+    /// Any file starting with a `#!` shebang line.
+    Script,
+    /// Compressed or archive container (zip, gzip, xz, zstd, tar, ar, ...).
+    Archive,
+    /// Common raster image formats.
+    Image,
+    /// Valid UTF-8 without NUL bytes.
+    Text,
+    /// Anything else.
+    Binary,
+}
+
+/// Classify content from its leading bytes.
+///
+/// `head` should hold the first `SNIFF_LEN` bytes of the content (or all of it,
+/// if shorter).
+pub fn detect_content_type(head: &[u8]) -> ContentType {
+    const ARCHIVE_MAGIC: &[&[u8]] = &[
+        b"PK\x03\x04",
+        b"PK\x05\x06",
+        b"\x1f\x8b",
+        b"\xfd7zXZ\x00",
+        b"\x28\xb5\x2f\xfd",
+        b"7z\xbc\xaf\x27\x1c",
+        b"Rar!\x1a\x07",
+        b"!<arch>\n",
+    ];
+    const IMAGE_MAGIC: &[&[u8]] = &[
+        b"\x89PNG\r\n\x1a\n",
+        b"\xff\xd8\xff",
+        b"GIF87a",
+        b"GIF89a",
+        b"II*\x00",
+        b"MM\x00*",
+    ];
+    const MACHO_MAGIC: &[&[u8]] = &[
+        b"\xfe\xed\xfa\xce",
+        b"\xfe\xed\xfa\xcf",
+        b"\xce\xfa\xed\xfe",
+        b"\xcf\xfa\xed\xfe",
+    ];
+
+    if head.is_empty() {
+        return ContentType::Empty;
+    }
+    if head.starts_with(b"\x7fELF") {
+        return ContentType::Elf;
+    }
+    if MACHO_MAGIC.iter().any(|magic| head.starts_with(magic)) || is_macho_universal(head) {
+        return ContentType::MachO;
+    }
+    if is_pe(head) {
+        return ContentType::Pe;
+    }
+    if head.starts_with(b"#!") {
+        return ContentType::Script;
+    }
+    if ARCHIVE_MAGIC.iter().any(|magic| head.starts_with(magic))
+        || is_bzip2(head)
+        || head.get(257..262) == Some(b"ustar")
+    {
+        return ContentType::Archive;
+    }
+    if IMAGE_MAGIC.iter().any(|magic| head.starts_with(magic))
+        || (head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP"))
+        || is_bmp(head)
+    {
+        return ContentType::Image;
+    }
+    if is_text(head) {
+        ContentType::Text
+    } else {
+        ContentType::Binary
+    }
+}
+
+/// `0xcafebabe` is shared by Mach-O universal binaries and Java class files;
+/// the former store a small architecture count where the latter store their
+/// class file version (45 and up).
+fn is_macho_universal(head: &[u8]) -> bool {
+    match head {
+        [0xca, 0xfe, 0xba, 0xbe, a, b, c, d, ..] => u32::from_be_bytes([*a, *b, *c, *d]) < 45,
+        _ => false,
+    }
+}
+
+/// `MZ` starts many a text file too. A PE file's DOS header points at its
+/// `PE\0\0` signature; a plain DOS executable is only taken for one when the
+/// head is not text.
+fn is_pe(head: &[u8]) -> bool {
+    if !head.starts_with(b"MZ") {
+        return false;
+    }
+    let signature = head
+        .get(0x3c..0x40)
+        .map(|offset| u32::from_le_bytes(offset.try_into().expect("four bytes")) as usize)
+        .and_then(|offset| head.get(offset..offset.checked_add(4)?));
+    signature == Some(b"PE\0\0") || !is_text(head)
+}
+
+/// `BM` followed by the BMP file header's reserved (zero) fields and the size
+/// of one of the known info headers, which no text file has.
+fn is_bmp(head: &[u8]) -> bool {
+    match head {
+        [
+            b'B',
+            b'M',
+            _,
+            _,
+            _,
+            _,
+            0,
+            0,
+            0,
+            0,
+            _,
+            _,
+            _,
+            _,
+            a,
+            b,
+            c,
+            d,
+            ..,
+        ] => matches!(
+            u32::from_le_bytes([*a, *b, *c, *d]),
+            12 | 16 | 40 | 52 | 56 | 64 | 108 | 124
+        ),
+        _ => false,
+    }
+}
+
+/// `BZh`, a block size digit, then the magic of the first block or, for an
+/// empty stream, of the end of the stream.
+fn is_bzip2(head: &[u8]) -> bool {
+    match head {
+        [b'B', b'Z', b'h', b'1'..=b'9', rest @ ..] => {
+            rest.starts_with(b"\x31\x41\x59\x26\x53\x59")
+                || rest.starts_with(b"\x17\x72\x45\x38\x50\x90")
+        }
+        _ => false,
+    }
+}
+
+fn is_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        // A multi-byte character cut off by the end of the sniff window is fine.
+        Err(err) => err.error_len().is_none() && head.len() - err.valid_up_to() < 4,
+    }
+}
+
+/// Reader adapter that keeps a copy of the first `SNIFF_LEN` bytes passing
+/// through it, so content can be classified while it is being hashed.
+///
+/// Unless created with `head_only`, ELF files are captured whole (up to
+/// `ELF_CAPTURE_LIMIT`) so their metadata can be parsed from the same read.
+/// Readers that can seek back should use `ElfInfo::read` instead.
+pub struct ContentSniffer<R> {
+    inner: R,
+    head: Vec<u8>,
+    capture_elf: bool,
+    overflowed: bool,
+}
+
+impl<R: Read> ContentSniffer<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            head: Vec::with_capacity(SNIFF_LEN),
+            capture_elf: true,
+            overflowed: false,
+        }
+    }
+
+    /// Keep only the first `SNIFF_LEN` bytes, ELF or not.
+    pub fn head_only(inner: R) -> Self {
+        Self {
+            capture_elf: false,
+            ..Self::new(inner)
+        }
+    }
+
+    /// Classify everything read so far.
+    pub fn content_type(&self) -> ContentType {
+        detect_content_type(&self.head[..self.head.len().min(SNIFF_LEN)])
+    }
+
+    /// ELF metadata of the content read so far, if it is a complete ELF file
+    /// within the capture limit.
+    pub fn elf_info(&self) -> Option<ElfInfo> {
+        if !self.capture_elf || self.overflowed || self.content_type() != ContentType::Elf {
+            return None;
+        }
+        ElfInfo::parse(&self.head)
+    }
+
+    fn capture_limit(&self) -> usize {
+        if self.capture_elf && self.head.len() >= SNIFF_LEN && self.head.starts_with(b"\x7fELF") {
+            ELF_CAPTURE_LIMIT
+        } else {
+            SNIFF_LEN
+        }
+    }
+}
+
+impl<R: Read> Read for ContentSniffer<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let mut consumed = 0;
+        // The limit can grow once the ELF magic has been seen, hence the loop.
+        while consumed < read && !self.overflowed {
+            let room = self.capture_limit().saturating_sub(self.head.len());
+            if room == 0 {
+                if self.capture_limit() == ELF_CAPTURE_LIMIT {
+                    // Too big to parse; keep only what classification needs.
+                    self.overflowed = true;
+                    self.head.truncate(SNIFF_LEN);
+                    self.head.shrink_to_fit();
+                }
+                break;
+            }
+            let wanted = room.min(read - consumed);
+            self.head
+                .extend_from_slice(&buf[consumed..consumed + wanted]);
+            consumed += wanted;
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_executables() {
+        assert_eq!(
+            detect_content_type(b"\x7fELF\x02\x01\x01\x00"),
+            ContentType::Elf
+        );
+        assert_eq!(detect_content_type(b"MZ\x90\x00\x03\x00"), ContentType::Pe);
+        let mut pe = b"MZ".to_vec();
+        pe.resize(0x80, b' ');
+        pe[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        assert_eq!(detect_content_type(&pe), ContentType::Pe);
+        assert_eq!(
+            detect_content_type(b"\xcf\xfa\xed\xfe\x07\x00\x00\x01"),
+            ContentType::MachO
+        );
+        assert_eq!(
+            detect_content_type(b"\xca\xfe\xba\xbe\x00\x00\x00\x02"),
+            ContentType::MachO
+        );
+        // Java class file (version 52) shares the universal binary magic
+        assert_eq!(
+            detect_content_type(b"\xca\xfe\xba\xbe\x00\x00\x00\x34"),
+            ContentType::Binary
+        );
+    }
+
+    #[test]
+    fn test_detect_scripts_archives_and_images() {
+        assert_eq!(
+            detect_content_type(b"#!/bin/sh\necho hi\n"),
+            ContentType::Script
+        );
+        assert_eq!(
+            detect_content_type(b"PK\x03\x04\x14\x00"),
+            ContentType::Archive
+        );
+        assert_eq!(
+            detect_content_type(b"\x1f\x8b\x08\x00"),
+            ContentType::Archive
+        );
+        assert_eq!(
+            detect_content_type(b"\x89PNG\r\n\x1a\n\x00\x00"),
+            ContentType::Image
+        );
+        assert_eq!(
+            detect_content_type(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+            ContentType::Image
+        );
+        assert_eq!(
+            detect_content_type(b"BMF\x00\x00\x00\x00\x00\x00\x00\x36\x00\x00\x00\x28\x00\x00\x00"),
+            ContentType::Image
+        );
+        assert_eq!(
+            detect_content_type(b"BZh91AY&SY\x8e\x1f\x05\xbb"),
+            ContentType::Archive
+        );
+        assert_eq!(
+            detect_content_type(b"BZh9\x17\x72\x45\x38\x50\x90\x00\x00\x00\x00"),
+            ContentType::Archive
+        );
+
+        let mut tar_head = vec![0u8; 512];
+        tar_head[..5].copy_from_slice(b"hello");
+        tar_head[257..262].copy_from_slice(b"ustar");
+        assert_eq!(detect_content_type(&tar_head), ContentType::Archive);
+    }
+
+    #[test]
+    fn test_weak_magic_in_text_is_text() {
+        for text in [
+            "BMW service history\n",
+            "MZ-80K emulator notes\n",
+            "BZh9 is the bzip2 header\n",
+        ] {
+            assert_eq!(
+                detect_content_type(text.as_bytes()),
+                ContentType::Text,
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_detect_text_and_binary() {
+        assert_eq!(detect_content_type(b""), ContentType::Empty);
+        assert_eq!(
+            detect_content_type("plain text, ünïcødé\n".as_bytes()),
+            ContentType::Text
+        );
+        assert_eq!(
+            detect_content_type(b"text\x00with nul"),
+            ContentType::Binary
+        );
+        assert_eq!(detect_content_type(b"\xff\xfe\xfd"), ContentType::Binary);
+        // Multi-byte character truncated at the end of the window
+        assert_eq!(detect_content_type(b"abc\xc3"), ContentType::Text);
+    }
+
+    #[test]
+    fn test_sniffer_passes_data_through() {
+        let data = vec![b'a'; SNIFF_LEN * 3];
+        let mut sniffer = ContentSniffer::new(&data[..]);
+        let mut copy = Vec::new();
+        sniffer.read_to_end(&mut copy).unwrap();
+
+        assert_eq!(copy, data);
+        assert_eq!(sniffer.head.len(), SNIFF_LEN);
+        assert_eq!(sniffer.content_type(), ContentType::Text);
+    }
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    #[test]
+    fn test_sniffer_captures_elf_metadata() {
+        let file = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+        let size = file.metadata().unwrap().len();
+        let mut sniffer = ContentSniffer::new(file);
+        io::copy(&mut sniffer, &mut io::sink()).unwrap();
+
+        assert_eq!(sniffer.content_type(), ContentType::Elf);
+        // Debug test binaries easily outgrow the capture limit
+        assert_eq!(
+            sniffer.elf_info().is_some(),
+            size <= ELF_CAPTURE_LIMIT as u64
+        );
+        assert!(sniffer.head.len() <= ELF_CAPTURE_LIMIT);
+
+        let file = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+        let mut sniffer = ContentSniffer::head_only(file);
+        io::copy(&mut sniffer, &mut io::sink()).unwrap();
+        assert_eq!(sniffer.content_type(), ContentType::Elf);
+        assert_eq!(sniffer.head.len(), SNIFF_LEN);
+        assert!(sniffer.elf_info().is_none());
+    }
+
+    #[test]
+    fn test_content_type_serializes_lowercase() {
+        assert_eq!(
+            serde_json::to_string(&ContentType::MachO).unwrap(),
+            r#""macho""#
+        );
+        assert_eq!(
+            serde_json::to_string(&ContentType::Elf).unwrap(),
+            r#""elf""#
+        );
+    }
+}
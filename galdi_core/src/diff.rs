@@ -1,30 +1,50 @@
 use std::path::PathBuf;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{PlumbahObject, snapshot::SnapshotEntry};
-#[derive(Debug, Serialize, Deserialize)]
+use crate::{
+    PlumbahObject,
+    snapshot::{EntryType, SnapshotEntry},
+};
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct DiffResult {
     #[serde(rename = "$plumbah")]
     pub plumbah: PlumbahObject,
     pub identical: bool,
+    /// Set when the snapshots are identical as far as sampled checksums can
+    /// tell: some entries are only `probably_unchanged`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub probable: bool,
     pub summary: DiffSummary,
     pub differences: Vec<Difference>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct DiffSummary {
     pub added: usize,
     pub removed: usize,
     pub modified: usize,
     pub unchanged: usize,
+    /// How many of the unchanged entries were only compared on sampled
+    /// checksums, and so are probably rather than certainly unchanged.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub probably_unchanged: usize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+fn is_false(flag: &bool) -> bool {
+    !flag
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Difference {
     pub path: PathBuf,
     pub change_type: ChangeType,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub changes: Vec<AttributeChange>,
     pub source: Option<SnapshotEntry>,
     pub target: Option<SnapshotEntry>,
@@ -32,7 +52,7 @@ pub struct Difference {
     pub error: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ChangeType {
     Added,
@@ -41,7 +61,7 @@ pub enum ChangeType {
     PermissionDenied,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum AttributeChange {
     Content,
@@ -50,4 +70,104 @@ pub enum AttributeChange {
     Type,
     Size,
     Target,
+    #[serde(rename = "content_type")]
+    ContentType,
+    /// ELF GNU build-id changed.
+    #[serde(rename = "build_id")]
+    BuildId,
+    /// ELF `DT_NEEDED` libraries or program interpreter changed.
+    Libraries,
+    /// ELF RPATH or RUNPATH changed.
+    Rpath,
+}
+
+/// Which attributes `SnapshotEntry::changes` compares.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiffOptions {
+    pub ignore_time: bool,
+    pub ignore_mode: bool,
+    /// Compare entry types only.
+    pub structure_only: bool,
+    /// One side was read from a checksum manifest, which records neither
+    /// modes nor sizes: compare those only where both sides record them.
+    pub manifest: bool,
+}
+
+impl SnapshotEntry {
+    /// The attributes that differ between this entry and `other`, an entry
+    /// at the same path in another snapshot.
+    pub fn changes(&self, other: &SnapshotEntry, options: &DiffOptions) -> Vec<AttributeChange> {
+        let (src, tgt) = (self, other);
+        let mut changes = Vec::new();
+
+        if src.entry_type != tgt.entry_type {
+            changes.push(AttributeChange::Type);
+        }
+
+        if !options.structure_only {
+            if src.content_differs(tgt) {
+                changes.push(AttributeChange::Content);
+            }
+
+            if !options.ignore_mode && attribute_differs(&src.mode, &tgt.mode, options) {
+                changes.push(AttributeChange::Mode);
+            }
+
+            if !options.ignore_time && src.mtime != tgt.mtime {
+                changes.push(AttributeChange::Mtime);
+            }
+
+            if !src.is_directory()
+                && !tgt.is_directory()
+                && attribute_differs(&src.size, &tgt.size, options)
+            {
+                changes.push(AttributeChange::Size);
+            }
+
+            if src.target != tgt.target {
+                changes.push(AttributeChange::Target);
+            }
+
+            // Older snapshots carry no content type; only compare when both do.
+            if let (Some(src_type), Some(tgt_type)) = (src.content_type, tgt.content_type)
+                && src_type != tgt_type
+            {
+                changes.push(AttributeChange::ContentType);
+            }
+
+            if let (Some(src_elf), Some(tgt_elf)) = (&src.elf, &tgt.elf) {
+                if src_elf.build_id != tgt_elf.build_id {
+                    changes.push(AttributeChange::BuildId);
+                }
+                if src_elf.needed != tgt_elf.needed || src_elf.interpreter != tgt_elf.interpreter {
+                    changes.push(AttributeChange::Libraries);
+                }
+                if src_elf.rpath != tgt_elf.rpath || src_elf.runpath != tgt_elf.runpath {
+                    changes.push(AttributeChange::Rpath);
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// Directories carry no size, though snapshots taken before that was so
+    /// recorded one.
+    fn is_directory(&self) -> bool {
+        self.entry_type == EntryType::Directory
+    }
+}
+
+/// Whether an optional attribute differs. Against a manifest, an attribute
+/// recorded on one side only is not taken for a change.
+fn attribute_differs<T: PartialEq>(
+    src: &Option<T>,
+    tgt: &Option<T>,
+    options: &DiffOptions,
+) -> bool {
+    if options.manifest {
+        matches!((src, tgt), (Some(src), Some(tgt)) if src != tgt)
+    } else {
+        src != tgt
+    }
 }
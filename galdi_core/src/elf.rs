@@ -0,0 +1,301 @@
+//! ELF metadata extraction for executables and shared libraries.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use goblin::container::{Container, Ctx};
+use goblin::elf::{
+    Elf, ProgramHeader, SectionHeader,
+    dynamic::{
+        DF_1_PIE, DT_FLAGS_1, DT_NEEDED, DT_NULL, DT_RPATH, DT_RUNPATH, DT_STRSZ, DT_STRTAB,
+    },
+    header::{self, ET_DYN},
+    note::NT_GNU_BUILD_ID,
+    program_header::{PT_DYNAMIC, PT_INTERP, PT_LOAD, PT_NOTE},
+    section_header::SHT_NOTE,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// ELF files streamed from tar archives and git objects are captured up to
+/// this size to parse their metadata; larger ones are hashed as usual but get
+/// no `elf` block. Files on disk are parsed by seeking instead.
+pub const ELF_CAPTURE_LIMIT: usize = 8 * 1024 * 1024;
+
+/// Header tables, string tables, notes and the dynamic section are small;
+/// a region claiming to be larger than this is taken for a corrupt file.
+const REGION_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// Linking and build metadata of an ELF file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ElfInfo {
+    /// GNU build-id note as lowercase hex, if the linker emitted one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_id: Option<String>,
+    /// Target architecture, e.g. "x86_64" or "aarch64".
+    pub arch: String,
+    /// Program interpreter (`PT_INTERP`), e.g. "/lib64/ld-linux-x86-64.so.2".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpreter: Option<String>,
+    /// `DT_NEEDED` libraries, in link order.
+    #[serde(default)]
+    pub needed: Vec<String>,
+    #[serde(default)]
+    pub rpath: Vec<String>,
+    #[serde(default)]
+    pub runpath: Vec<String>,
+    /// Whether the symbol table (`.symtab`) has been stripped.
+    pub stripped: bool,
+    /// Whether this is a position independent executable.
+    pub pie: bool,
+}
+
+impl ElfInfo {
+    /// Parse ELF metadata from the complete file contents. Returns `None` for
+    /// anything that does not parse as ELF.
+    pub fn parse(bytes: &[u8]) -> Option<ElfInfo> {
+        ElfInfo::read(&mut io::Cursor::new(bytes))
+    }
+
+    /// Parse ELF metadata from a seekable file, reading only the header, the
+    /// program and section header tables and the few regions they point to:
+    /// the interpreter, the dynamic section and its strings, and the notes.
+    pub fn read<R: Read + Seek>(file: &mut R) -> Option<ElfInfo> {
+        let header =
+            Elf::parse_header(&read_at(file, 0, header::header64::SIZEOF_EHDR as u64)?).ok()?;
+        let ctx = Ctx::new(header.container().ok()?, header.endianness().ok()?);
+
+        let phnum = usize::from(header.e_phnum);
+        let program_headers = if phnum == 0 {
+            vec![]
+        } else {
+            let table = read_at(
+                file,
+                header.e_phoff,
+                (phnum * ProgramHeader::size(ctx)) as u64,
+            )?;
+            ProgramHeader::parse(&table, 0, phnum, ctx).ok()?
+        };
+        let shnum = usize::from(header.e_shnum);
+        let section_headers = if shnum == 0 || header.e_shoff == 0 {
+            vec![]
+        } else {
+            let table = read_at(
+                file,
+                header.e_shoff,
+                (shnum * SectionHeader::size(ctx)) as u64,
+            )?;
+            SectionHeader::parse_from(&table, 0, shnum, ctx).ok()?
+        };
+
+        let section_names = section_headers
+            .get(usize::from(header.e_shstrndx))
+            .and_then(|names| read_at(file, names.sh_offset, names.sh_size));
+        let stripped = !section_headers.iter().any(|section| {
+            section_names
+                .as_deref()
+                .and_then(|names| c_string(names, section.sh_name as u64))
+                .as_deref()
+                == Some(".symtab")
+        });
+
+        let segment = |file: &mut R, p_type| {
+            let segment = program_headers.iter().find(|ph| ph.p_type == p_type)?;
+            read_at(file, segment.p_offset, segment.p_filesz)
+        };
+        let interpreter = segment(file, PT_INTERP).and_then(|bytes| c_string(&bytes, 0));
+
+        let dynamic = segment(file, PT_DYNAMIC)
+            .map(|bytes| dynamic_entries(&bytes, ctx))
+            .unwrap_or_default();
+        let value = |tag| dynamic.iter().find(|(t, _)| *t == tag).map(|(_, v)| *v);
+        let strings = value(DT_STRTAB)
+            .zip(value(DT_STRSZ))
+            .and_then(|(address, len)| {
+                let load = program_headers.iter().find(|ph| {
+                    ph.p_type == PT_LOAD
+                        && ph.p_vaddr <= address
+                        && address < ph.p_vaddr.saturating_add(ph.p_filesz)
+                })?;
+                // A crafted segment may lie past the end of any file; no strings then.
+                let offset = load.p_offset.checked_add(address - load.p_vaddr)?;
+                read_at(file, offset, len)
+            })
+            .unwrap_or_default();
+        let names = |tag| -> Vec<String> {
+            dynamic
+                .iter()
+                .filter(|(t, _)| *t == tag)
+                .filter_map(|(_, offset)| c_string(&strings, *offset))
+                .collect()
+        };
+
+        let build_id = program_headers
+            .iter()
+            .filter(|ph| ph.p_type == PT_NOTE)
+            .map(|ph| (ph.p_offset, ph.p_filesz, ph.p_align))
+            .chain(
+                section_headers
+                    .iter()
+                    .filter(|sh| sh.sh_type == SHT_NOTE)
+                    .map(|sh| (sh.sh_offset, sh.sh_size, sh.sh_addralign)),
+            )
+            .filter_map(|(offset, len, align)| {
+                let notes = read_at(file, offset, len)?;
+                gnu_build_id(&notes, align, ctx)
+            })
+            .next();
+
+        // Shared libraries are ET_DYN too; PIE executables either say so in
+        // DT_FLAGS_1 or give themselves away by requesting an interpreter.
+        let flags_1 = value(DT_FLAGS_1).unwrap_or(0);
+        let pie = header.e_type == ET_DYN && (flags_1 & DF_1_PIE != 0 || interpreter.is_some());
+
+        Some(ElfInfo {
+            build_id,
+            arch: arch_name(header.e_machine, ctx.container == Container::Big),
+            interpreter,
+            needed: names(DT_NEEDED),
+            rpath: names(DT_RPATH),
+            runpath: names(DT_RUNPATH),
+            stripped,
+            pie,
+        })
+    }
+}
+
+/// `len` bytes of `file` from `offset`, or `None` if they are not all there.
+fn read_at<R: Read + Seek>(file: &mut R, offset: u64, len: u64) -> Option<Vec<u8>> {
+    if len > REGION_LIMIT {
+        return None;
+    }
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let mut bytes = vec![0; len as usize];
+    file.read_exact(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// The NUL-terminated string at `offset` in a string table.
+fn c_string(table: &[u8], offset: u64) -> Option<String> {
+    let rest = table.get(usize::try_from(offset).ok()?..)?;
+    let len = rest.iter().position(|&b| b == 0)?;
+    Some(String::from_utf8_lossy(&rest[..len]).into_owned())
+}
+
+/// An unsigned word of the container's size and endianness at `offset`.
+fn word(bytes: &[u8], offset: usize, size: usize, ctx: Ctx) -> Option<u64> {
+    let bytes = bytes.get(offset..offset + size)?;
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate() {
+        let shift = if ctx.is_little_endian() {
+            i
+        } else {
+            size - 1 - i
+        };
+        value |= u64::from(*byte) << (8 * shift);
+    }
+    Some(value)
+}
+
+/// `(d_tag, d_val)` pairs of a dynamic section, up to `DT_NULL`.
+fn dynamic_entries(bytes: &[u8], ctx: Ctx) -> Vec<(u64, u64)> {
+    let size = if ctx.container == Container::Big {
+        8
+    } else {
+        4
+    };
+    (0..bytes.len() / (2 * size))
+        .map_while(|i| {
+            let tag = word(bytes, 2 * size * i, size, ctx)?;
+            let value = word(bytes, 2 * size * i + size, size, ctx)?;
+            (tag != DT_NULL).then_some((tag, value))
+        })
+        .collect()
+}
+
+/// The GNU build-id in a run of notes aligned to `align` bytes.
+fn gnu_build_id(notes: &[u8], align: u64, ctx: Ctx) -> Option<String> {
+    let align = if align == 8 { 8 } else { 4 };
+    let padded = |len: usize| len.div_ceil(align) * align;
+    let mut offset = 0;
+    while offset + 12 <= notes.len() {
+        let name_len = word(notes, offset, 4, ctx)? as usize;
+        let desc_len = word(notes, offset + 4, 4, ctx)? as usize;
+        let note_type = word(notes, offset + 8, 4, ctx)? as u32;
+        let name_start = offset + 12;
+        let desc_start = name_start + padded(name_len);
+        let name = notes.get(name_start..name_start + name_len)?;
+        let desc = notes.get(desc_start..desc_start + desc_len)?;
+        if note_type == NT_GNU_BUILD_ID && name == b"GNU\0" {
+            return Some(desc.iter().map(|b| format!("{:02x}", b)).collect());
+        }
+        offset = desc_start + padded(desc_len);
+    }
+    None
+}
+
+/// Architecture names as used by `uname -m` for the common cases.
+fn arch_name(machine: u16, is_64: bool) -> String {
+    match (machine, is_64) {
+        (header::EM_X86_64, _) => "x86_64".to_string(),
+        (header::EM_386, _) => "x86".to_string(),
+        (header::EM_AARCH64, _) => "aarch64".to_string(),
+        (header::EM_ARM, _) => "arm".to_string(),
+        (header::EM_RISCV, true) => "riscv64".to_string(),
+        (header::EM_RISCV, false) => "riscv32".to_string(),
+        (header::EM_PPC64, _) => "ppc64".to_string(),
+        (header::EM_S390, _) => "s390x".to_string(),
+        (machine, _) => header::machine_to_str(machine).to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rejects_non_elf() {
+        assert_eq!(ElfInfo::parse(b"#!/bin/sh\n"), None);
+        assert_eq!(ElfInfo::parse(b"\x7fELF"), None);
+    }
+
+    #[test]
+    fn test_arch_name() {
+        assert_eq!(arch_name(header::EM_X86_64, true), "x86_64");
+        assert_eq!(arch_name(header::EM_RISCV, true), "riscv64");
+        assert_eq!(arch_name(header::EM_MIPS, false), "mips");
+    }
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    #[test]
+    fn test_read_agrees_with_a_full_parse() {
+        let path = std::env::current_exe().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let elf = Elf::parse(&bytes).unwrap();
+        let info = ElfInfo::read(&mut std::fs::File::open(&path).unwrap()).unwrap();
+
+        assert_eq!(info.interpreter.as_deref(), elf.interpreter);
+        assert_eq!(info.needed, elf.libraries);
+        assert_eq!(info.rpath, elf.rpaths);
+        assert_eq!(info.runpath, elf.runpaths);
+        let build_id = elf
+            .iter_note_headers(&bytes)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .find(|note| note.n_type == NT_GNU_BUILD_ID && note.name == "GNU")
+            .map(|note| note.desc.iter().map(|b| format!("{:02x}", b)).collect());
+        assert_eq!(info.build_id, build_id);
+    }
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    #[test]
+    fn test_parse_own_test_binary() {
+        let bytes = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let info = ElfInfo::parse(&bytes).unwrap();
+
+        assert_eq!(info.arch, std::env::consts::ARCH);
+        assert!(info.needed.iter().any(|lib| lib.starts_with("libc.so")));
+        assert!(info.interpreter.is_some());
+        assert!(!info.stripped);
+    }
+}
@@ -0,0 +1,498 @@
+//! Binary encodings and compression for stored snapshots and diff reports.
+//!
+//! Documents are written as pretty-printed JSON, CBOR or MessagePack, each
+//! optionally compressed with zstd or gzip. Readers are not told which: the
+//! compression and then the encoding are recognized by their first bytes.
+//!
+//! - zstd frames start with `28 B5 2F FD`, gzip members with `1F 8B`
+//! - CBOR is written behind the self-describe tag `D9 D9 F7`; untagged CBOR
+//!   maps (`A0`–`BF`) are recognized as well
+//! - MessagePack documents are maps (`80`–`8F`, `DE`, `DF`)
+//! - JSON documents are objects, so start with `{` after any whitespace
+//!
+//! Every encoding carries the same fields, the `$plumbah` annotation
+//! included, so a document converts between them without loss.
+//!
+//! Telling whether an arbitrary file holds a document at all takes more than
+//! its first byte, which many binary formats share (PNGs start with `89`, a
+//! MessagePack map). `detect_format` therefore also wants the self-describe
+//! tag, or a map whose first key is `$plumbah` or `entries`.
+
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::str::FromStr;
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const CBOR_SELF_DESCRIBE: [u8; 3] = [0xD9, 0xD9, 0xF7];
+
+/// How many leading bytes are looked at to recognize a document.
+const PEEK_LEN: u64 = 64;
+
+/// Top-level keys a document may start with, as far as `detect_format` is
+/// concerned. Galdi writes `$plumbah` first, as do key-sorting writers.
+const DOCUMENT_KEYS: [&[u8]; 2] = [b"$plumbah", b"entries"];
+
+/// Serialization format of a stored document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Json,
+    Cbor,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Json => "json",
+            Encoding::Cbor => "cbor",
+            Encoding::MessagePack => "msgpack",
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Encoding::Json),
+            "cbor" => Ok(Encoding::Cbor),
+            "msgpack" | "messagepack" => Ok(Encoding::MessagePack),
+            _ => Err(format!("Unknown encoding: {}", s)),
+        }
+    }
+}
+
+/// Compression applied on top of an `Encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Zstd => "zstd",
+            Compression::Gzip => "gzip",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "zstd" => Ok(Compression::Zstd),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            _ => Err(format!("Unknown compression: {}", s)),
+        }
+    }
+}
+
+/// A writer that compresses everything written to it. The compressed stream
+/// is only complete once `finish` has been called.
+pub enum CompressedWriter<W: Write> {
+    Plain(W),
+    Zstd(zstd::Encoder<'static, W>),
+    Gzip(GzEncoder<W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        Ok(match compression {
+            Compression::None => CompressedWriter::Plain(writer),
+            Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(writer, 0)?),
+            Compression::Gzip => {
+                CompressedWriter::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+            }
+        })
+    }
+
+    /// Write the end of the compressed stream and return the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            CompressedWriter::Plain(writer) => Ok(writer),
+            CompressedWriter::Zstd(encoder) => encoder.finish(),
+            CompressedWriter::Gzip(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Write `document` to `writer` in `encoding`, compressed with `compression`.
+/// JSON is pretty-printed and ends with a newline.
+pub fn write_encoded<T: Serialize + ?Sized, W: Write>(
+    document: &T,
+    writer: W,
+    encoding: Encoding,
+    compression: Compression,
+) -> io::Result<()> {
+    let mut out = CompressedWriter::new(BufWriter::new(writer), compression)?;
+    match encoding {
+        Encoding::Json => {
+            serde_json::to_writer_pretty(&mut out, document)?;
+            out.write_all(b"\n")?;
+        }
+        Encoding::Cbor => {
+            out.write_all(&CBOR_SELF_DESCRIBE)?;
+            ciborium::into_writer(document, &mut out).map_err(|err| match err {
+                ciborium::ser::Error::Io(err) => err,
+                ciborium::ser::Error::Value(message) => invalid_data(message),
+            })?;
+        }
+        Encoding::MessagePack => {
+            // Named fields, so that `$plumbah` and the rest survive as map keys
+            rmp_serde::encode::write_named(&mut out, document)
+                .map_err(|err| invalid_data(err.to_string()))?;
+        }
+    }
+    out.finish()?.flush()
+}
+
+/// Read a document written by `write_encoded`, in whichever encoding and
+/// compression it was written with.
+pub fn read_encoded<'a, T: DeserializeOwned, R: Read + 'a>(reader: R) -> io::Result<T> {
+    let (prefix, mut reader) = peek(decompressing_reader(reader)?)?;
+    match detect_encoding(&prefix) {
+        Some(Encoding::Json) => Ok(serde_json::from_reader(BufReader::new(reader))?),
+        Some(Encoding::Cbor) => {
+            if prefix.starts_with(&CBOR_SELF_DESCRIBE) {
+                reader.read_exact(&mut [0; CBOR_SELF_DESCRIBE.len()])?;
+            }
+            ciborium::from_reader(BufReader::new(reader)).map_err(|err| match err {
+                ciborium::de::Error::Io(err) => err,
+                err => invalid_data(format!("invalid CBOR document: {}", err)),
+            })
+        }
+        Some(Encoding::MessagePack) => rmp_serde::from_read(BufReader::new(reader))
+            .map_err(|err| invalid_data(format!("invalid MessagePack document: {}", err))),
+        None => Err(invalid_data(
+            "unrecognized document encoding; expected JSON, CBOR or MessagePack".to_string(),
+        )),
+    }
+}
+
+/// `reader` with any zstd or gzip compression taken off, recognized by its
+/// first bytes.
+pub fn decompressing_reader<'a, R: Read + 'a>(reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    let (prefix, reader) = peek(reader)?;
+    decompressed(reader, detect_compression(&prefix))
+}
+
+/// The encoding and compression of the document `reader` starts, or `None`
+/// when it does not look like one. Only the first bytes are read: the CBOR
+/// self-describe tag, or a map whose first key is one of `DOCUMENT_KEYS`.
+pub fn detect_format<R: Read>(reader: R) -> io::Result<Option<(Encoding, Compression)>> {
+    let (prefix, reader) = peek(reader)?;
+    let compression = detect_compression(&prefix);
+    let (prefix, _) = peek(decompressed(reader, compression)?)?;
+    Ok(detect_encoding(&prefix)
+        .filter(|encoding| has_document_signature(&prefix, *encoding))
+        .map(|encoding| (encoding, compression)))
+}
+
+fn detect_compression(prefix: &[u8]) -> Compression {
+    if prefix.starts_with(&ZSTD_MAGIC) {
+        Compression::Zstd
+    } else if prefix.starts_with(&GZIP_MAGIC) {
+        Compression::Gzip
+    } else {
+        Compression::None
+    }
+}
+
+fn detect_encoding(prefix: &[u8]) -> Option<Encoding> {
+    match prefix.first()? {
+        _ if prefix.starts_with(&CBOR_SELF_DESCRIBE) => Some(Encoding::Cbor),
+        0xA0..=0xBF => Some(Encoding::Cbor),
+        0x80..=0x8F | 0xDE | 0xDF => Some(Encoding::MessagePack),
+        _ => match prefix.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') => Some(Encoding::Json),
+            _ => None,
+        },
+    }
+}
+
+/// Whether `prefix` starts a document in `encoding` rather than merely
+/// sharing its first byte.
+fn has_document_signature(prefix: &[u8], encoding: Encoding) -> bool {
+    if prefix.starts_with(&CBOR_SELF_DESCRIBE) {
+        return true;
+    }
+    first_key(prefix, encoding).is_some_and(|key| DOCUMENT_KEYS.contains(&key))
+}
+
+/// The first key of the map `prefix` starts, if it is a string short enough
+/// to lie within the prefix.
+fn first_key(prefix: &[u8], encoding: Encoding) -> Option<&[u8]> {
+    let (start, len) = match encoding {
+        Encoding::Json => {
+            let rest = prefix.trim_ascii_start().strip_prefix(b"{")?;
+            let rest = rest.trim_ascii_start().strip_prefix(b"\"")?;
+            let len = rest.iter().position(|&b| b == b'"')?;
+            return Some(&rest[..len]);
+        }
+        Encoding::Cbor => {
+            let header = match prefix.first()? {
+                0xA0..=0xB7 | 0xBF => 1,
+                0xB8 => 2,
+                0xB9 => 3,
+                0xBA => 5,
+                0xBB => 9,
+                _ => return None,
+            };
+            match *prefix.get(header)? {
+                b @ 0x60..=0x77 => (header + 1, usize::from(b - 0x60)),
+                0x78 => (header + 2, usize::from(*prefix.get(header + 1)?)),
+                _ => return None,
+            }
+        }
+        Encoding::MessagePack => {
+            let header = match prefix.first()? {
+                0x80..=0x8F => 1,
+                0xDE => 3,
+                0xDF => 5,
+                _ => return None,
+            };
+            match *prefix.get(header)? {
+                b @ 0xA0..=0xBF => (header + 1, usize::from(b & 0x1F)),
+                0xD9 => (header + 2, usize::from(*prefix.get(header + 1)?)),
+                _ => return None,
+            }
+        }
+    };
+    prefix.get(start..start + len)
+}
+
+/// Read the first `PEEK_LEN` bytes of `reader` and hand them back together
+/// with a reader that still yields everything.
+fn peek<R: Read>(mut reader: R) -> io::Result<(Vec<u8>, impl Read)> {
+    let mut prefix = Vec::new();
+    reader.by_ref().take(PEEK_LEN).read_to_end(&mut prefix)?;
+    Ok((prefix.clone(), io::Cursor::new(prefix).chain(reader)))
+}
+
+fn decompressed<'a, R: Read + 'a>(
+    reader: R,
+    compression: Compression,
+) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+    })
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    use crate::{ChecksumAlgorithm, ContentType, DiffResult, Snapshot, SnapshotEntry};
+    use serde_json::{Value, json};
+
+    const ENCODINGS: [Encoding; 3] = [Encoding::Json, Encoding::Cbor, Encoding::MessagePack];
+    const COMPRESSIONS: [Compression; 3] =
+        [Compression::None, Compression::Zstd, Compression::Gzip];
+
+    fn plumbah() -> Value {
+        json!({
+            "version": "1.0",
+            "status": "ok",
+            "meta": {
+                "tool": "galdi_snapshot",
+                "tool_version": "0.1.0",
+                "idempotent": true,
+                "mutates": false,
+                "safe": true,
+                "deterministic": false,
+                "plumbah_level": 2,
+                "execution_time_ms": 12,
+                "timestamp": "2026-01-01T00:00:00Z",
+                "profiles": [{ "name": "streaming", "format": "jsonl" }],
+                "host": "build-7"
+            }
+        })
+    }
+
+    fn snapshot() -> Snapshot {
+        let app = SnapshotEntry {
+            checksums: [(
+                ChecksumAlgorithm::Blake3,
+                "blake3:fedcba9876543210".to_string(),
+            )]
+            .into(),
+            content_type: Some(ContentType::Binary),
+            ..SnapshotEntry::test_file("dist/app", "sha256:0123456789abcdef")
+        };
+        Snapshot {
+            plumbah: serde_json::from_value(plumbah()).unwrap(),
+            root: PathBuf::from("dist"),
+            checksum_algorithm: ChecksumAlgorithm::Sha256,
+            extra_checksums: vec![ChecksumAlgorithm::Blake3],
+            ..Snapshot::test_of(vec![SnapshotEntry::test_dir("dist"), app])
+        }
+    }
+
+    fn encode<T: Serialize>(document: &T, encoding: Encoding, compression: Compression) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_encoded(document, &mut bytes, encoding, compression).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_snapshot_round_trips_in_every_format() {
+        let snapshot = snapshot();
+        let expected = serde_json::to_value(&snapshot).unwrap();
+
+        for encoding in ENCODINGS {
+            for compression in COMPRESSIONS {
+                let bytes = encode(&snapshot, encoding, compression);
+                assert_eq!(
+                    detect_format(bytes.as_slice()).unwrap(),
+                    Some((encoding, compression))
+                );
+
+                let restored: Snapshot = read_encoded(bytes.as_slice()).unwrap();
+                assert_eq!(serde_json::to_value(&restored).unwrap(), expected);
+
+                // The annotation survives as such, not just as struct fields
+                let document: Value = read_encoded(bytes.as_slice()).unwrap();
+                assert_eq!(document["$plumbah"]["meta"]["host"], "build-7");
+            }
+        }
+    }
+
+    #[test]
+    fn test_diff_result_round_trips_in_binary_formats() {
+        let diff: DiffResult = serde_json::from_value(json!({
+            "$plumbah": plumbah(),
+            "identical": false,
+            "summary": { "added": 1, "removed": 0, "modified": 0, "unchanged": 3 },
+            "differences": [{
+                "path": "dist/new",
+                "change_type": "added",
+                "source": null,
+                "target": snapshot().entries[1]
+            }]
+        }))
+        .unwrap();
+        let expected = serde_json::to_value(&diff).unwrap();
+
+        for encoding in [Encoding::Cbor, Encoding::MessagePack] {
+            let bytes = encode(&diff, encoding, Compression::Zstd);
+            let restored: DiffResult = read_encoded(bytes.as_slice()).unwrap();
+            assert_eq!(serde_json::to_value(&restored).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_binary_encodings_are_smaller() {
+        let snapshot = snapshot();
+        let json = encode(&snapshot, Encoding::Json, Compression::None).len();
+        assert!(encode(&snapshot, Encoding::Cbor, Compression::None).len() < json);
+        assert!(encode(&snapshot, Encoding::MessagePack, Compression::None).len() < json);
+    }
+
+    #[test]
+    fn test_unrecognized_input_is_rejected() {
+        assert_eq!(detect_format(&b"sha256  dist/app\n"[..]).unwrap(), None);
+        assert_eq!(detect_format(&b""[..]).unwrap(), None);
+        assert_eq!(
+            detect_format(&b"\n  {\"$plumbah\": {}}"[..]).unwrap(),
+            Some((Encoding::Json, Compression::None))
+        );
+        assert_eq!(detect_format(&b"{\"a\": 1}"[..]).unwrap(), None);
+
+        let error = read_encoded::<Value, _>(&b"[1, 2]"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("unrecognized document encoding"));
+    }
+
+    #[test]
+    fn test_binary_files_are_not_documents() {
+        // PNG signature: 0x89 opens a MessagePack map
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+        assert_eq!(detect_format(&png[..]).unwrap(), None);
+        // 0xA5 opens a CBOR map, but no text key follows
+        assert_eq!(detect_format(&b"\xa5\x01\x02"[..]).unwrap(), None);
+        // A MessagePack map whose first key is some other string
+        assert_eq!(detect_format(&b"\x81\xa3abc\x01"[..]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_untagged_maps_are_recognized_by_their_first_key() {
+        let snapshot = snapshot();
+        let msgpack = encode(&snapshot, Encoding::MessagePack, Compression::None);
+        assert_eq!(
+            detect_format(&msgpack[..]).unwrap(),
+            Some((Encoding::MessagePack, Compression::None))
+        );
+        // The same document in CBOR, without the self-describe tag
+        let cbor = encode(&snapshot, Encoding::Cbor, Compression::None);
+        assert_eq!(
+            detect_format(&cbor[CBOR_SELF_DESCRIBE.len()..]).unwrap(),
+            Some((Encoding::Cbor, Compression::None))
+        );
+    }
+
+    #[test]
+    fn test_names_parse() {
+        for encoding in ENCODINGS {
+            assert_eq!(encoding.name().parse::<Encoding>(), Ok(encoding));
+        }
+        for compression in COMPRESSIONS {
+            assert_eq!(compression.name().parse::<Compression>(), Ok(compression));
+        }
+        assert!("yaml".parse::<Encoding>().is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::{PathBuf, StripPrefixError};
+use std::path::{Path, PathBuf, StripPrefixError};
 
 use crate::plumbah::PlumbahError;
 
@@ -15,9 +15,45 @@ pub enum ScanError {
 
     #[error("Symlink loop detected at: {0}")]
     SymlinkLoop(PathBuf),
+
+    #[error("Git error: {0}")]
+    Git(String),
+
+    #[error("Manifest error: {0}")]
+    Manifest(String),
+
+    #[error("Key mismatch: {0}")]
+    KeyMismatch(String),
+
+    #[error("Signature error: {0}")]
+    Signature(String),
+
+    #[error("Schema error: {0}")]
+    Schema(String),
+
+    #[error("Snapshot operation error: {0}")]
+    Operation(String),
+
+    #[error("Filter error: {0}")]
+    Filter(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Store error: {0}")]
+    Store(String),
 }
 
 impl ScanError {
+    /// Attribute an I/O error to `path` where its kind allows it.
+    pub fn from_io(err: std::io::Error, path: &Path) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => Self::PathNotFound(path.to_path_buf()),
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied(path.to_path_buf()),
+            _ => Self::Io(err),
+        }
+    }
+
     pub fn to_plumbah_error(&self) -> PlumbahError {
         match self {
             Self::PathNotFound(path) => PlumbahError {
@@ -48,6 +84,69 @@ impl ScanError {
                 recoverable: false,
                 context: None,
             },
+            Self::Git(message) => PlumbahError {
+                code: "GIT_ERROR".to_string(),
+                message: message.clone(),
+                path: None,
+                recoverable: false,
+                context: None,
+            },
+            Self::Manifest(message) => PlumbahError {
+                code: "MANIFEST_ERROR".to_string(),
+                message: message.clone(),
+                path: None,
+                recoverable: false,
+                context: None,
+            },
+            Self::KeyMismatch(message) => PlumbahError {
+                code: "KEY_MISMATCH".to_string(),
+                message: message.clone(),
+                path: None,
+                recoverable: false,
+                context: None,
+            },
+            Self::Signature(message) => PlumbahError {
+                code: "SIGNATURE_ERROR".to_string(),
+                message: message.clone(),
+                path: None,
+                recoverable: false,
+                context: None,
+            },
+            Self::Schema(message) => PlumbahError {
+                code: "SCHEMA_ERROR".to_string(),
+                message: message.clone(),
+                path: None,
+                recoverable: false,
+                context: None,
+            },
+            Self::Operation(message) => PlumbahError {
+                code: "OPERATION_ERROR".to_string(),
+                message: message.clone(),
+                path: None,
+                recoverable: false,
+                context: None,
+            },
+            Self::Filter(message) => PlumbahError {
+                code: "FILTER_ERROR".to_string(),
+                message: message.clone(),
+                path: None,
+                recoverable: false,
+                context: None,
+            },
+            Self::Database(message) => PlumbahError {
+                code: "DATABASE_ERROR".to_string(),
+                message: message.clone(),
+                path: None,
+                recoverable: false,
+                context: None,
+            },
+            Self::Store(message) => PlumbahError {
+                code: "STORE_ERROR".to_string(),
+                message: message.clone(),
+                path: None,
+                recoverable: false,
+                context: None,
+            },
         }
     }
 }
@@ -62,7 +161,13 @@ impl From<StripPrefixError> for ScanError {
 
 impl From<ignore::Error> for ScanError {
     fn from(err: ignore::Error) -> Self {
-        // Convert ignore::Error to ScanError::Io
+        // Keep the path of unreadable directories; everything else becomes ScanError::Io
+        if let ignore::Error::WithPath { path, err: inner } = &err
+            && let Some(io_error) = inner.io_error()
+            && io_error.kind() == std::io::ErrorKind::PermissionDenied
+        {
+            return ScanError::PermissionDenied(path.clone());
+        }
         ScanError::Io(std::io::Error::other(err.to_string()))
     }
 }
@@ -120,6 +225,30 @@ mod tests {
         assert!(!plumbah_error.recoverable);
     }
 
+    #[test]
+    fn test_git_error_to_plumbah_error() {
+        let error = ScanError::Git("unknown revision v9.9.9".to_string());
+        let plumbah_error = error.to_plumbah_error();
+
+        assert_eq!(plumbah_error.code, "GIT_ERROR");
+        assert!(plumbah_error.message.contains("unknown revision"));
+        assert_eq!(plumbah_error.path, None);
+        assert!(!plumbah_error.recoverable);
+    }
+
+    #[test]
+    fn test_from_io_attributes_path() {
+        let path = Path::new("/etc/shadow");
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(matches!(
+            ScanError::from_io(denied, path),
+            ScanError::PermissionDenied(p) if p == path
+        ));
+
+        let other = std::io::Error::other("disk on fire");
+        assert!(matches!(ScanError::from_io(other, path), ScanError::Io(_)));
+    }
+
     #[test]
     fn test_plumbah_error_code_format() {
         // Verify all error codes are UPPER_CASE with underscores
@@ -128,6 +257,15 @@ mod tests {
             ScanError::PermissionDenied(PathBuf::from("/test")),
             ScanError::Io(std::io::Error::other("test")),
             ScanError::SymlinkLoop(PathBuf::from("/test")),
+            ScanError::Git("test".to_string()),
+            ScanError::Manifest("test".to_string()),
+            ScanError::KeyMismatch("test".to_string()),
+            ScanError::Signature("test".to_string()),
+            ScanError::Schema("test".to_string()),
+            ScanError::Operation("test".to_string()),
+            ScanError::Filter("test".to_string()),
+            ScanError::Database("test".to_string()),
+            ScanError::Store("test".to_string()),
         ];
 
         for error in test_cases {
@@ -167,6 +305,15 @@ mod tests {
             ScanError::PermissionDenied(PathBuf::from("/test")),
             ScanError::Io(std::io::Error::other("test")),
             ScanError::SymlinkLoop(PathBuf::from("/test")),
+            ScanError::Git("test".to_string()),
+            ScanError::Manifest("test".to_string()),
+            ScanError::KeyMismatch("test".to_string()),
+            ScanError::Signature("test".to_string()),
+            ScanError::Schema("test".to_string()),
+            ScanError::Operation("test".to_string()),
+            ScanError::Filter("test".to_string()),
+            ScanError::Database("test".to_string()),
+            ScanError::Store("test".to_string()),
         ];
 
         for error in test_cases {
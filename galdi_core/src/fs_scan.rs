@@ -1,5 +1,7 @@
 use std::{
-    io, panic,
+    collections::BTreeMap,
+    io::{self, Read},
+    panic,
     path::{MAIN_SEPARATOR, PathBuf},
     sync::{
         Arc,
@@ -14,13 +16,25 @@ use ignore::{DirEntry, WalkBuilder, overrides::OverrideBuilder};
 
 use crate::{
     Meta, PlumbahObject, Status,
+    checksum::{ChecksumKey, MultiHasher, hash_sampled},
+    checksum_cache::{self, FileStamp},
+    content_type::{ContentSniffer, ContentType, SNIFF_LEN},
+    elf::ElfInfo,
     error::ScanError,
+    migration::SNAPSHOT_VERSION,
     snapshot::{ChecksumAlgorithm, EntryType, Snapshot, SnapshotEntry},
 };
 
 pub struct ScanOptions {
     pub root: PathBuf,
     pub checksum_algorithm: ChecksumAlgorithm,
+    /// Additional algorithms computed in the same read as `checksum_algorithm`.
+    pub extra_checksums: Vec<ChecksumAlgorithm>,
+    /// Key for keyed algorithms (`blake3_keyed`, `hmac_sha256`).
+    pub checksum_key: Option<ChecksumKey>,
+    /// Trust and maintain checksums cached in `user.galdi.<algorithm>`
+    /// extended attributes. Keyed checksums are never cached.
+    pub checksum_cache: bool,
     pub follow_symlinks: bool,
     pub max_depth: Option<usize>,
     pub exclude_patterns: Vec<String>,
@@ -28,6 +42,38 @@ pub struct ScanOptions {
     pub threads: Option<usize>, // None = auto-detect, Some(n) = explicit
     /// Normalize paths to use '/' as separator (useful on Windows).
     pub normalize_paths: bool,
+    /// Bytes per sample of sampled algorithms (`xxh3_64s`).
+    pub sample_len: u64,
+}
+
+impl ScanOptions {
+    /// `extra_checksums` without duplicates or repeats of the primary algorithm.
+    pub fn extra_checksum_algorithms(&self) -> Vec<ChecksumAlgorithm> {
+        let mut extra = Vec::new();
+        for algorithm in &self.extra_checksums {
+            if *algorithm != self.checksum_algorithm && !extra.contains(algorithm) {
+                extra.push(*algorithm);
+            }
+        }
+        extra
+    }
+
+    /// Whether checksums are cached in extended attributes, which writes to
+    /// the scanned files. Keyed checksums are never cached.
+    pub fn caches_checksums(&self) -> bool {
+        self.checksum_cache && self.checksum_key.is_none()
+    }
+
+    /// `sample_len`, if any algorithm is sampled; recorded as
+    /// `Snapshot::sample_len`.
+    pub fn recorded_sample_len(&self) -> Option<u64> {
+        let sampled = self.checksum_algorithm.is_sampled()
+            || self
+                .extra_checksums
+                .iter()
+                .any(ChecksumAlgorithm::is_sampled);
+        sampled.then_some(self.sample_len)
+    }
 }
 
 pub struct Scanner {
@@ -39,11 +85,23 @@ pub struct ScanIterator {
     receiver: Receiver<Result<SnapshotEntry, ScanError>>,
 }
 
+/// Primary checksum, extra checksums, content type and ELF metadata of a file.
+type FileContent = (
+    String,
+    BTreeMap<ChecksumAlgorithm, String>,
+    ContentType,
+    Option<ElfInfo>,
+);
+
 /// Shared scanner configuration for the iterator
 struct ScannerRef {
     root: PathBuf,
     checksum_algorithm: ChecksumAlgorithm,
+    extra_checksums: Vec<ChecksumAlgorithm>,
+    checksum_key: Option<ChecksumKey>,
+    checksum_cache: bool,
     normalize_paths: bool,
+    sample_len: u64,
 }
 
 impl ScannerRef {
@@ -61,12 +119,17 @@ impl ScannerRef {
             EntryType::Undefined
         };
 
-        let checksum = if entry_type == EntryType::File {
-            Some(self.compute_checksum(entry.path())?)
+        let (checksum, checksums, content_type, elf) = if entry_type == EntryType::File {
+            let (checksum, checksums, content_type, elf) = self
+                .compute_checksum(entry.path(), &metadata)
+                .map_err(|err| ScanError::from_io(err, entry.path()))?;
+            (Some(checksum), checksums, Some(content_type), elf)
         } else {
-            None
+            (None, BTreeMap::new(), None, None)
         };
 
+        let (uid, gid) = owner_ids(&metadata);
+
         Ok(SnapshotEntry {
             path: if self.normalize_paths {
                 to_unix_like_string(relative_path).into()
@@ -74,10 +137,16 @@ impl ScannerRef {
                 relative_path.to_path_buf()
             },
             entry_type,
-            size: Some(metadata.len()),
+            // What a directory's size says depends on the filesystem, not its content
+            size: (entry_type != EntryType::Directory).then_some(metadata.len()),
             mode: Some(format_mode(&metadata)),
+            uid,
+            gid,
             mtime: metadata.modified()?.into(),
             checksum,
+            checksums,
+            content_type,
+            elf,
             target: if entry_type == EntryType::Symlink {
                 Some(std::fs::read_link(entry.path())?)
             } else {
@@ -86,10 +155,73 @@ impl ScannerRef {
         })
     }
 
-    fn compute_checksum(&self, path: &std::path::Path) -> Result<String, io::Error> {
-        let hasher: Box<dyn crate::checksum::GaldiHasher> =
-            crate::checksum::get_hasher(self.checksum_algorithm);
-        hasher.hash_file(path)
+    /// Hash the file with every configured algorithm and classify its content
+    /// (and ELF metadata), all in the same read.
+    ///
+    /// With the checksum cache enabled, still valid cached checksums are used
+    /// instead, and fresh ones are cached unless the file changed while it was
+    /// read. ELF files are always read, for their metadata.
+    ///
+    /// Sampled algorithms read their samples separately. ELF metadata is
+    /// parsed by seeking through the file afterwards, so that no more than
+    /// the head of a file is ever buffered.
+    fn compute_checksum(
+        &self,
+        path: &std::path::Path,
+        metadata: &std::fs::Metadata,
+    ) -> Result<FileContent, io::Error> {
+        let mut algorithms = vec![self.checksum_algorithm];
+        algorithms.extend_from_slice(&self.extra_checksums);
+        let stamp = FileStamp::of(metadata).filter(|_| self.checksum_cache);
+
+        if let Some(stamp) = stamp
+            && let Some((mut checksums, content_type)) =
+                checksum_cache::lookup(path, stamp, &algorithms, self.sample_len)
+        {
+            let checksum = checksums
+                .remove(&self.checksum_algorithm)
+                .expect("primary algorithm is always cached");
+            return Ok((checksum, checksums, content_type, None));
+        }
+
+        // Sampled checksums seek through the file on their own; when there is
+        // nothing else to hash, only the head is read to classify the content.
+        let (sampled, streamed): (Vec<_>, Vec<_>) = algorithms
+            .iter()
+            .partition(|algorithm| algorithm.is_sampled());
+        let mut reader = ContentSniffer::head_only(std::fs::File::open(path)?);
+        let mut checksums = if streamed.is_empty() {
+            io::copy(&mut (&mut reader).take(SNIFF_LEN as u64), &mut io::sink())?;
+            BTreeMap::new()
+        } else {
+            let hasher = MultiHasher::new(&streamed).with_key(self.checksum_key.clone());
+            hasher.hash_reader(&mut reader)?
+        };
+        for algorithm in sampled {
+            checksums.insert(
+                algorithm,
+                hash_sampled(&mut std::fs::File::open(path)?, self.sample_len)?,
+            );
+        }
+        let content_type = reader.content_type();
+        let elf = if content_type == ContentType::Elf {
+            ElfInfo::read(&mut std::fs::File::open(path)?)
+        } else {
+            None
+        };
+        if let Some(stamp) = stamp
+            && content_type != ContentType::Elf
+            && std::fs::metadata(path)
+                .ok()
+                .and_then(|metadata| FileStamp::of(&metadata))
+                == Some(stamp)
+        {
+            checksum_cache::store(path, stamp, &checksums, content_type, self.sample_len);
+        }
+        let checksum = checksums
+            .remove(&self.checksum_algorithm)
+            .expect("primary algorithm is always hashed");
+        Ok((checksum, checksums, content_type, elf))
     }
 }
 
@@ -135,7 +267,11 @@ impl Scanner {
         let scanner_ref = Arc::new(ScannerRef {
             root: self.options.root.clone(),
             checksum_algorithm: self.options.checksum_algorithm,
+            extra_checksums: self.options.extra_checksum_algorithms(),
+            checksum_key: self.options.checksum_key.clone(),
+            checksum_cache: self.options.caches_checksums(),
             normalize_paths: self.options.normalize_paths,
+            sample_len: self.options.sample_len,
         });
 
         // Always use parallel walker with channel for streaming
@@ -218,16 +354,19 @@ impl Scanner {
         };
 
         Ok(Snapshot {
-            version: "1.0".to_string(),
+            version: SNAPSHOT_VERSION.to_string(),
             root: self.options.root.clone(),
             checksum_algorithm: self.options.checksum_algorithm,
+            extra_checksums: self.options.extra_checksum_algorithms(),
+            checksum_key_id: self.options.checksum_key.as_ref().map(ChecksumKey::id),
+            sample_len: self.options.recorded_sample_len(),
             plumbah: PlumbahObject::new(
                 status,
                 Meta::new(
                     "galdi_snapshot",
                     env!("CARGO_PKG_VERSION"),
                     true,
-                    false,
+                    self.options.caches_checksums(),
                     true,
                     false,
                     start.elapsed().as_millis() as u64,
@@ -259,3 +398,16 @@ fn format_mode(metadata: &std::fs::Metadata) -> String {
         format!("{:08x}{}", attrs, if readonly { ",readonly" } else { "" })
     }
 }
+
+fn owner_ids(metadata: &std::fs::Metadata) -> (Option<u32>, Option<u32>) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        (Some(metadata.uid()), Some(metadata.gid()))
+    }
+    #[cfg(windows)]
+    {
+        let _ = metadata;
+        (None, None)
+    }
+}
@@ -0,0 +1,317 @@
+//! Snapshot construction from git commits and trees.
+//!
+//! A git source is written `git:<rev>` or `git:<rev>:<subdir>`, e.g.
+//! `git:HEAD` or `git:v1.2.0:deploy/app`. The tree is streamed out of the
+//! repository with `git archive` and read with the tar scanner, so nothing is
+//! checked out and checksums use galdi's own `ChecksumAlgorithm`.
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::Instant,
+};
+
+use chrono::Utc;
+
+use crate::{
+    Meta, PlumbahObject, Status,
+    error::ScanError,
+    migration::SNAPSHOT_VERSION,
+    snapshot::{ChecksumAlgorithm, Snapshot},
+    tar_scan::read_tar_entries,
+};
+
+/// Prefix marking a snapshot source as a git object rather than a path.
+pub const GIT_SOURCE_PREFIX: &str = "git:";
+
+/// A git commit (or tree) and optional subdirectory to snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSource {
+    /// Anything `git archive` accepts: a branch, tag, commit id, `HEAD~2`, ...
+    pub rev: String,
+    /// Directory inside the tree to use as the snapshot root.
+    pub subdir: Option<PathBuf>,
+}
+
+impl GitSource {
+    /// Parse a `git:<rev>[:<subdir>]` spec. Returns `None` for anything without
+    /// the `git:` prefix, so callers can fall back to treating it as a path.
+    pub fn parse(spec: &str) -> Option<GitSource> {
+        let rest = spec.strip_prefix(GIT_SOURCE_PREFIX)?;
+        // Ref names cannot contain ':', so the first one separates rev and path.
+        let (rev, subdir) = match rest.split_once(':') {
+            Some((rev, subdir)) => (rev, Some(subdir)),
+            None => (rest, None),
+        };
+        let rev = if rev.is_empty() { "HEAD" } else { rev };
+        let subdir = subdir
+            .map(|subdir| subdir.trim_matches('/'))
+            .filter(|subdir| !subdir.is_empty() && *subdir != ".")
+            .map(PathBuf::from);
+
+        Some(GitSource {
+            rev: rev.to_string(),
+            subdir,
+        })
+    }
+
+    fn spec(&self) -> String {
+        match &self.subdir {
+            Some(subdir) => format!("{}{}:{}", GIT_SOURCE_PREFIX, self.rev, subdir.display()),
+            None => format!("{}{}", GIT_SOURCE_PREFIX, self.rev),
+        }
+    }
+}
+
+/// The top of the git work tree holding `dir`, if it is in one.
+pub fn repository_of(dir: &Path) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--show-toplevel"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let toplevel = String::from_utf8(output.stdout).ok()?;
+    output
+        .status
+        .success()
+        .then(|| PathBuf::from(toplevel.trim_end_matches('\n')))
+}
+
+impl Snapshot {
+    /// Build a snapshot of a git commit or tree in the repository at `repo`.
+    ///
+    /// Modes follow a checkout with the usual `022` umask (`644`/`755`), mtimes
+    /// are the commit time and symlinks keep their recorded targets. The
+    /// snapshot `root` is the `git:` spec itself.
+    pub fn from_git(
+        repo: &Path,
+        source: &GitSource,
+        checksum_algorithm: ChecksumAlgorithm,
+    ) -> Result<Snapshot, ScanError> {
+        let start = Instant::now();
+
+        let mut command = Command::new("git");
+        command
+            .arg("-C")
+            .arg(repo)
+            .args(["-c", "tar.umask=0022", "archive", "--format=tar"])
+            .arg(&source.rev);
+        if let Some(subdir) = &source.subdir {
+            command.arg("--").arg(subdir);
+        }
+
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| ScanError::Git(format!("failed to run git: {}", err)))?;
+
+        // Drain stderr while the archive is read, lest git block on a full pipe
+        let mut pipe = child.stderr.take().expect("stderr is piped");
+        let stderr = thread::spawn(move || {
+            let mut stderr = String::new();
+            let _ = pipe.read_to_string(&mut stderr);
+            stderr
+        });
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let entries = read_tar_entries(stdout, checksum_algorithm, source.subdir.as_deref());
+
+        let status = child.wait()?;
+        let stderr = stderr.join().unwrap_or_default();
+        if !status.success() {
+            return Err(ScanError::Git(format!(
+                "git archive {} failed: {}",
+                source.rev,
+                stderr.trim()
+            )));
+        }
+        let entries = entries?;
+
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION.to_string(),
+            root: PathBuf::from(source.spec()),
+            checksum_algorithm,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
+            plumbah: PlumbahObject::new(
+                Status::Ok,
+                Meta::new(
+                    "galdi_snapshot",
+                    env!("CARGO_PKG_VERSION"),
+                    true,
+                    false,
+                    true,
+                    false, // `HEAD` and branch names move
+                    start.elapsed().as_millis() as u64,
+                    Utc::now(),
+                ),
+            ),
+            count: entries.len(),
+            entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntryType, get_hasher};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn git(repo: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args([
+                "-c",
+                "user.name=galdi",
+                "-c",
+                "user.email=galdi@example.com",
+                "-c",
+                "commit.gpgsign=false",
+            ])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .expect("git should be installed");
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    fn sample_repo() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let repo = dir.path();
+        git(repo, &["init", "-q"]);
+        fs::create_dir_all(repo.join("app/bin")).unwrap();
+        fs::write(repo.join("README"), b"readme\n").unwrap();
+        fs::write(repo.join("app/config.toml"), b"port = 80\n").unwrap();
+        fs::write(repo.join("app/bin/run"), b"#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(repo.join("app/bin/run"), fs::Permissions::from_mode(0o755))
+                .unwrap();
+            std::os::unix::fs::symlink("config.toml", repo.join("app/current")).unwrap();
+        }
+        git(repo, &["add", "-A"]);
+        git(repo, &["commit", "-q", "-m", "initial"]);
+        git(repo, &["tag", "v1.0.0"]);
+        fs::write(repo.join("app/config.toml"), b"port = 8080\n").unwrap();
+        git(repo, &["commit", "-q", "-am", "change port"]);
+        dir
+    }
+
+    #[test]
+    fn test_parse_git_source() {
+        assert_eq!(
+            GitSource::parse("git:HEAD"),
+            Some(GitSource {
+                rev: "HEAD".to_string(),
+                subdir: None
+            })
+        );
+        assert_eq!(
+            GitSource::parse("git:v1.2.0:deploy/app/"),
+            Some(GitSource {
+                rev: "v1.2.0".to_string(),
+                subdir: Some(PathBuf::from("deploy/app"))
+            })
+        );
+        assert_eq!(GitSource::parse("git:").unwrap().rev, "HEAD");
+        assert_eq!(GitSource::parse("git:main:.").unwrap().subdir, None);
+        assert_eq!(GitSource::parse("snapshot.json"), None);
+    }
+
+    #[test]
+    fn test_from_git_whole_tree() {
+        let repo = sample_repo();
+        let source = GitSource::parse("git:HEAD").unwrap();
+        let snapshot = Snapshot::from_git(repo.path(), &source, ChecksumAlgorithm::Sha256).unwrap();
+
+        assert_eq!(snapshot.root, PathBuf::from("git:HEAD"));
+        let readme = snapshot
+            .entries
+            .iter()
+            .find(|e| e.path == Path::new("README"))
+            .unwrap();
+        assert_eq!(readme.entry_type, EntryType::File);
+        assert_eq!(readme.mode.as_deref(), Some("644"));
+        let expected = get_hasher(ChecksumAlgorithm::Sha256)
+            .unwrap()
+            .hash_reader(&mut &b"readme\n"[..])
+            .unwrap();
+        assert_eq!(readme.checksum.as_deref(), Some(expected.as_str()));
+    }
+
+    #[test]
+    fn test_from_git_tag_and_subdir() {
+        let repo = sample_repo();
+        let source = GitSource::parse("git:v1.0.0:app").unwrap();
+        let snapshot =
+            Snapshot::from_git(repo.path(), &source, ChecksumAlgorithm::XXH3_64).unwrap();
+
+        let paths: Vec<_> = snapshot.entries.iter().map(|e| e.path.clone()).collect();
+        assert!(paths.contains(&PathBuf::from("")));
+        assert!(paths.contains(&PathBuf::from("config.toml")));
+        assert!(!paths.iter().any(|p| p.starts_with("app")));
+        assert!(!paths.contains(&PathBuf::from("README")));
+
+        let config = snapshot
+            .entries
+            .iter()
+            .find(|e| e.path == Path::new("config.toml"))
+            .unwrap();
+        let expected = get_hasher(ChecksumAlgorithm::XXH3_64)
+            .unwrap()
+            .hash_reader(&mut &b"port = 80\n"[..])
+            .unwrap();
+        assert_eq!(config.checksum.as_deref(), Some(expected.as_str()));
+
+        #[cfg(unix)]
+        {
+            let run = snapshot
+                .entries
+                .iter()
+                .find(|e| e.path == Path::new("bin/run"))
+                .unwrap();
+            assert_eq!(run.mode.as_deref(), Some("755"));
+
+            let current = snapshot
+                .entries
+                .iter()
+                .find(|e| e.path == Path::new("current"))
+                .unwrap();
+            assert_eq!(current.entry_type, EntryType::Symlink);
+            assert_eq!(current.target, Some(PathBuf::from("config.toml")));
+        }
+    }
+
+    #[test]
+    fn test_from_git_unknown_rev_is_git_error() {
+        let repo = sample_repo();
+        let source = GitSource::parse("git:no-such-tag").unwrap();
+        let result = Snapshot::from_git(repo.path(), &source, ChecksumAlgorithm::XXH3_64);
+        assert!(matches!(result, Err(ScanError::Git(_))));
+    }
+
+    #[test]
+    fn test_repository_of() {
+        let repo = sample_repo();
+        let toplevel = repo.path().canonicalize().unwrap();
+        let found = repository_of(&repo.path().join("app/bin")).unwrap();
+        assert_eq!(found.canonicalize().unwrap(), toplevel);
+
+        let outside = TempDir::new().unwrap();
+        assert_eq!(repository_of(outside.path()), None);
+        assert_eq!(repository_of(&repo.path().join("README")), None);
+    }
+}
@@ -0,0 +1,256 @@
+//! Change history of a single path across a series of snapshots.
+//!
+//! Snapshots are observed one at a time, keeping only the entry of the path,
+//! so that long series need not be held in memory together. The timeline is
+//! ordered by the time each snapshot was taken, `$plumbah.meta.timestamp`,
+//! whatever order the snapshots were observed in.
+
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::diff::{AttributeChange, DiffOptions};
+use crate::snapshot::{Snapshot, SnapshotEntry};
+
+/// Collects the entries of one path from snapshots, see `timeline`.
+pub struct PathHistory {
+    path: PathBuf,
+    observations: Vec<Observation>,
+}
+
+struct Observation {
+    timestamp: DateTime<Utc>,
+    source: String,
+    entry: Option<SnapshotEntry>,
+}
+
+/// Every point of a series of snapshots where a path appeared, disappeared
+/// or changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timeline {
+    pub path: PathBuf,
+    /// Snapshots in the series.
+    pub snapshots: usize,
+    /// Snapshots that record the path.
+    pub recorded_in: usize,
+    /// Oldest first.
+    pub events: Vec<HistoryEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEvent {
+    /// When the snapshot that shows the event was taken.
+    pub timestamp: DateTime<Utc>,
+    /// Where that snapshot came from, such as its file.
+    pub source: String,
+    pub event: HistoryEventKind,
+    /// What changed, for `changed` events.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<AttributeChange>,
+    /// The entry from then on; none once it disappeared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<SnapshotEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryEventKind {
+    /// Recorded for the first time, or again after it disappeared.
+    Appeared,
+    /// No longer recorded.
+    Disappeared,
+    /// Recorded with other attributes, as `galdi diff` compares them; the
+    /// mtime alone is not a change.
+    Changed,
+}
+
+impl PathHistory {
+    /// Follow `path`, relative to the snapshot roots, or absolute to be
+    /// taken relative to the root of each snapshot.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        PathHistory {
+            path: path.into(),
+            observations: Vec::new(),
+        }
+    }
+
+    /// Record what `snapshot`, from `source`, holds at the path.
+    pub fn observe(&mut self, source: impl Into<String>, snapshot: &Snapshot) {
+        let path = relative_to(&self.path, &snapshot.root);
+        self.observations.push(Observation {
+            timestamp: snapshot
+                .plumbah
+                .meta
+                .as_ref()
+                .map_or(DateTime::<Utc>::MIN_UTC, |meta| meta.timestamp),
+            source: source.into(),
+            // The last entry wins where a path is recorded twice, as in `SnapshotIndex`
+            entry: snapshot
+                .entries
+                .iter()
+                .rev()
+                .find(|entry| entry.path == path)
+                .cloned(),
+        });
+    }
+
+    /// The events in the snapshots observed, ordered by when they were taken.
+    pub fn timeline(mut self) -> Timeline {
+        // Stable, so that snapshots taken at the same time keep their order
+        self.observations
+            .sort_by_key(|observation| observation.timestamp);
+
+        let options = DiffOptions {
+            ignore_time: true,
+            ..DiffOptions::default()
+        };
+        let mut events = Vec::new();
+        let mut previous: Option<&SnapshotEntry> = None;
+        for observation in &self.observations {
+            let current = observation.entry.as_ref();
+            let event = match (previous, current) {
+                (None, Some(_)) => Some((HistoryEventKind::Appeared, vec![])),
+                (Some(_), None) => Some((HistoryEventKind::Disappeared, vec![])),
+                (Some(before), Some(after)) => Some(before.changes(after, &options))
+                    .filter(|changes| !changes.is_empty())
+                    .map(|changes| (HistoryEventKind::Changed, changes)),
+                (None, None) => None,
+            };
+            if let Some((event, changes)) = event {
+                events.push(HistoryEvent {
+                    timestamp: observation.timestamp,
+                    source: observation.source.clone(),
+                    event,
+                    changes,
+                    entry: current.cloned(),
+                });
+            }
+            previous = current;
+        }
+
+        let recorded_in = self
+            .observations
+            .iter()
+            .filter(|observation| observation.entry.is_some())
+            .count();
+
+        Timeline {
+            path: self.path,
+            snapshots: self.observations.len(),
+            recorded_in,
+            events,
+        }
+    }
+}
+
+impl Timeline {
+    /// The timeline of `path` across `snapshots`, each given with its source.
+    pub fn of<'a>(
+        path: impl Into<PathBuf>,
+        snapshots: impl IntoIterator<Item = (String, &'a Snapshot)>,
+    ) -> Self {
+        let mut history = PathHistory::new(path);
+        for (source, snapshot) in snapshots {
+            history.observe(source, snapshot);
+        }
+        history.timeline()
+    }
+
+    /// The last event that changed the content, if any.
+    pub fn last_content_change(&self) -> Option<&HistoryEvent> {
+        self.events.iter().rev().find(|event| {
+            event.event != HistoryEventKind::Changed
+                || event
+                    .changes
+                    .iter()
+                    .any(|change| matches!(change, AttributeChange::Content))
+        })
+    }
+}
+
+/// `path` relative to `root`: as it is when relative, with `./` taken off.
+fn relative_to(path: &Path, root: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.strip_prefix(root).unwrap_or(path)
+    } else {
+        path
+    };
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn snapshot(day: u32, config: Option<(&str, &str)>) -> Snapshot {
+        let entries = config
+            .into_iter()
+            .map(|(checksum, mode)| SnapshotEntry {
+                mode: Some(mode.to_string()),
+                ..SnapshotEntry::test_file("etc/app.conf", &format!("xxh3_64:{}", checksum))
+            })
+            .collect();
+        let timestamp = Utc.with_ymd_and_hms(2026, 1, day, 0, 0, 0).unwrap();
+        let mut snapshot = Snapshot {
+            root: PathBuf::from("/srv"),
+            ..Snapshot::test_of(entries)
+        };
+        snapshot.plumbah.meta.as_mut().unwrap().timestamp = timestamp;
+        snapshot
+    }
+
+    #[test]
+    fn test_timeline_events() {
+        // Observed out of order: the timeline follows the timestamps
+        let snapshots = [
+            snapshot(3, Some(("02", "644"))),
+            snapshot(1, None),
+            snapshot(2, Some(("01", "644"))),
+            snapshot(4, Some(("02", "600"))),
+            snapshot(5, Some(("02", "600"))),
+            snapshot(6, None),
+            snapshot(7, Some(("03", "600"))),
+        ];
+        let timeline = Timeline::of(
+            "etc/app.conf",
+            snapshots
+                .iter()
+                .enumerate()
+                .map(|(i, snapshot)| (format!("s{}", i), snapshot)),
+        );
+
+        assert_eq!(timeline.snapshots, 7);
+        assert_eq!(timeline.recorded_in, 5);
+        let events: Vec<_> = timeline
+            .events
+            .iter()
+            .map(|event| (event.source.as_str(), event.event, event.changes.clone()))
+            .collect();
+        use HistoryEventKind::*;
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0].0, "s2");
+        assert_eq!(events[0].1, Appeared);
+        assert_eq!(events[1].0, "s0");
+        assert!(matches!(events[1].2.as_slice(), [AttributeChange::Content]));
+        assert!(matches!(events[2].2.as_slice(), [AttributeChange::Mode]));
+        assert_eq!((events[3].0, events[3].1), ("s5", Disappeared));
+        assert_eq!((events[4].0, events[4].1), ("s6", Appeared));
+        assert!(timeline.events[3].entry.is_none());
+
+        assert_eq!(timeline.last_content_change().unwrap().source, "s6");
+    }
+
+    #[test]
+    fn test_absolute_paths_are_taken_relative_to_the_root() {
+        let snapshots = [snapshot(1, Some(("01", "644")))];
+        for path in ["/srv/etc/app.conf", "./etc/app.conf"] {
+            let timeline = Timeline::of(path, snapshots.iter().map(|s| ("s".to_string(), s)));
+            assert_eq!(timeline.recorded_in, 1, "{}", path);
+        }
+    }
+}
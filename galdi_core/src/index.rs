@@ -0,0 +1,259 @@
+//! Indexed, read-only view over a snapshot's entries.
+//!
+//! Entries are kept sorted by path, compared component by component, so a
+//! directory is immediately followed by everything beneath it. Lookups are
+//! binary searches; subtrees are contiguous runs of the sorted entries.
+
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::snapshot::{EntryType, Snapshot, SnapshotEntry};
+
+/// Sorted view over the entries of a snapshot, for lookups by path, subtree
+/// or checksum.
+///
+/// Where a snapshot holds the same path more than once, the last entry wins.
+pub struct SnapshotIndex<'a> {
+    snapshot: &'a Snapshot,
+    entries: Vec<&'a SnapshotEntry>,
+    /// Entries by checksum, built on the first `find_by_checksum`.
+    by_checksum: OnceCell<HashMap<&'a str, Vec<&'a SnapshotEntry>>>,
+}
+
+/// Aggregate counts over a subtree, see `SnapshotIndex::summary`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubtreeSummary {
+    pub files: usize,
+    pub directories: usize,
+    pub symlinks: usize,
+    /// Special files: devices, FIFOs, sockets...
+    pub other: usize,
+    /// Sum of the recorded sizes of the files.
+    pub total_size: u64,
+}
+
+impl Snapshot {
+    /// Index the entries of this snapshot, see `SnapshotIndex`.
+    pub fn index(&self) -> SnapshotIndex<'_> {
+        SnapshotIndex::new(self)
+    }
+}
+
+impl<'a> SnapshotIndex<'a> {
+    pub fn new(snapshot: &'a Snapshot) -> Self {
+        let mut entries: Vec<(usize, &SnapshotEntry)> =
+            snapshot.entries.iter().enumerate().collect();
+        // Latest entry first among equal paths, so that dedup keeps it
+        entries.sort_unstable_by(|(i, a), (j, b)| a.path.cmp(&b.path).then(j.cmp(i)));
+        entries.dedup_by(|(_, a), (_, b)| a.path == b.path);
+
+        SnapshotIndex {
+            snapshot,
+            entries: entries.into_iter().map(|(_, entry)| entry).collect(),
+            by_checksum: OnceCell::new(),
+        }
+    }
+
+    /// The indexed snapshot.
+    pub fn snapshot(&self) -> &'a Snapshot {
+        self.snapshot
+    }
+
+    /// Number of distinct paths.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// All entries, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = &'a SnapshotEntry> + '_ {
+        self.entries.iter().copied()
+    }
+
+    /// The entry at `path` (relative to the snapshot root), in O(log n).
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&'a SnapshotEntry> {
+        let path = path.as_ref();
+        self.entries
+            .binary_search_by(|entry| entry.path.as_path().cmp(path))
+            .ok()
+            .map(|position| self.entries[position])
+    }
+
+    /// `dir` itself, if recorded, followed by everything beneath it, in path
+    /// order. The root is `""`.
+    pub fn walk(&self, dir: impl AsRef<Path>) -> impl Iterator<Item = &'a SnapshotEntry> + '_ {
+        self.subtree(dir.as_ref()).iter().copied()
+    }
+
+    /// The entries directly inside `dir`, in path order. Each child is found
+    /// in O(log n), skipping over the subtrees of the ones before it.
+    pub fn children(&self, dir: impl AsRef<Path>) -> Children<'a, '_> {
+        let dir = dir.as_ref();
+        let subtree = self.subtree(dir);
+        // Skip `dir` itself
+        let start = usize::from(subtree.first().is_some_and(|entry| entry.path == dir));
+        Children {
+            remaining: &subtree[start..],
+        }
+    }
+
+    /// Entries whose content has this checksum, given as `algorithm:digest`
+    /// like in snapshots. Extra checksums are searched as well. The checksum
+    /// index is built on the first call.
+    pub fn find_by_checksum(&self, checksum: &str) -> &[&'a SnapshotEntry] {
+        let by_checksum = self.by_checksum.get_or_init(|| {
+            let mut by_checksum: HashMap<&str, Vec<&SnapshotEntry>> = HashMap::new();
+            for entry in &self.entries {
+                let checksums = entry.checksum.iter().chain(entry.checksums.values());
+                for checksum in checksums {
+                    by_checksum
+                        .entry(checksum.as_str())
+                        .or_default()
+                        .push(entry);
+                }
+            }
+            by_checksum
+        });
+        by_checksum.get(checksum).map_or(&[], Vec::as_slice)
+    }
+
+    /// Counts and total file size of `dir` and everything beneath it.
+    pub fn summary(&self, dir: impl AsRef<Path>) -> SubtreeSummary {
+        let mut summary = SubtreeSummary::default();
+        for entry in self.walk(dir) {
+            match entry.entry_type {
+                EntryType::File => {
+                    summary.files += 1;
+                    summary.total_size += entry.size.unwrap_or(0);
+                }
+                EntryType::Directory => summary.directories += 1,
+                EntryType::Symlink => summary.symlinks += 1,
+                EntryType::Undefined => summary.other += 1,
+            }
+        }
+        summary
+    }
+
+    /// Total recorded size of the files in `dir` and beneath it.
+    pub fn total_size(&self, dir: impl AsRef<Path>) -> u64 {
+        self.summary(dir).total_size
+    }
+
+    /// The contiguous run of entries at or beneath `dir`.
+    fn subtree(&self, dir: &Path) -> &[&'a SnapshotEntry] {
+        let start = self
+            .entries
+            .partition_point(|entry| entry.path.as_path() < dir);
+        let len = self.entries[start..].partition_point(|entry| entry.path.starts_with(dir));
+        &self.entries[start..start + len]
+    }
+}
+
+/// Iterator over the children of a directory, see `SnapshotIndex::children`.
+pub struct Children<'a, 'i> {
+    remaining: &'i [&'a SnapshotEntry],
+}
+
+impl<'a> Iterator for Children<'a, '_> {
+    type Item = &'a SnapshotEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&child, rest) = self.remaining.split_first()?;
+        let descendants = rest.partition_point(|entry| entry.path.starts_with(&child.path));
+        self.remaining = &rest[descendants..];
+        Some(child)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let file = |path, size, checksum| SnapshotEntry {
+            size: Some(size),
+            ..SnapshotEntry::test_file(path, checksum)
+        };
+        let entries = vec![
+            file("a.txt", 1, "xxh3_64:01"),
+            file("a/b/deep.txt", 100, "xxh3_64:01"),
+            SnapshotEntry::test_dir(""),
+            SnapshotEntry::test_dir("a"),
+            SnapshotEntry::test_dir("a/b"),
+            file("a/c.txt", 10, "xxh3_64:02"),
+            SnapshotEntry {
+                entry_type: EntryType::Symlink,
+                target: Some(PathBuf::from("c.txt")),
+                ..SnapshotEntry::test_dir("a/link")
+            },
+            file("a.txt", 2, "xxh3_64:03"),
+        ];
+        Snapshot::test_of(entries)
+    }
+
+    fn paths<'a>(entries: impl Iterator<Item = &'a SnapshotEntry>) -> Vec<&'a str> {
+        entries.map(|entry| entry.path.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_get_and_duplicates() {
+        let snapshot = snapshot();
+        let index = snapshot.index();
+
+        assert_eq!(index.len(), 7);
+        assert_eq!(index.get("a/c.txt").unwrap().size, Some(10));
+        // The later of two entries for the same path wins
+        assert_eq!(index.get("a.txt").unwrap().size, Some(2));
+        assert!(index.get("a/missing").is_none());
+        assert!(index.get("").is_some());
+    }
+
+    #[test]
+    fn test_walk_and_children() {
+        let snapshot = snapshot();
+        let index = snapshot.index();
+
+        assert_eq!(
+            paths(index.walk("a")),
+            vec!["a", "a/b", "a/b/deep.txt", "a/c.txt", "a/link"]
+        );
+        assert_eq!(paths(index.children("")), vec!["a", "a.txt"]);
+        assert_eq!(paths(index.children("a")), vec!["a/b", "a/c.txt", "a/link"]);
+        assert_eq!(paths(index.children("a/c.txt")), Vec::<&str>::new());
+        assert_eq!(index.walk("nothing").count(), 0);
+    }
+
+    #[test]
+    fn test_find_by_checksum_and_summary() {
+        let snapshot = snapshot();
+        let index = snapshot.index();
+
+        assert_eq!(
+            paths(index.find_by_checksum("xxh3_64:01").iter().copied()),
+            vec!["a/b/deep.txt"]
+        );
+        assert_eq!(
+            paths(index.find_by_checksum("xxh3_64:03").iter().copied()),
+            vec!["a.txt"]
+        );
+        assert!(index.find_by_checksum("xxh3_64:ff").is_empty());
+
+        assert_eq!(
+            index.summary("a"),
+            SubtreeSummary {
+                files: 2,
+                directories: 2,
+                symlinks: 1,
+                other: 0,
+                total_size: 110,
+            }
+        );
+        assert_eq!(index.total_size(""), 112);
+    }
+}
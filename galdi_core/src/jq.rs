@@ -0,0 +1,142 @@
+//! In-process jq filters over galdi's JSON output, so that trimming a
+//! snapshot or a diff report does not need `jq` installed.
+//!
+//! Filters are compiled with jaq, with jq's standard library of definitions.
+
+use jaq_core::load::{self, Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, Native, RcIter};
+use jaq_json::Val;
+use serde_json::Value;
+
+use crate::error::ScanError;
+
+/// A compiled jq filter.
+pub struct JqFilter {
+    filter: jaq_core::Filter<Native<Val>>,
+}
+
+impl JqFilter {
+    /// Compile `expression`, reporting syntax errors and undefined names as
+    /// `ScanError::Filter`.
+    pub fn new(expression: &str) -> Result<Self, ScanError> {
+        let program = File {
+            code: expression,
+            path: (),
+        };
+        let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+        let arena = Arena::default();
+        let modules = loader.load(&arena, program).map_err(|errors| {
+            let messages = errors.into_iter().flat_map(|(_, error)| match error {
+                load::Error::Io(errors) => errors.into_iter().map(|(_, e)| e).collect(),
+                load::Error::Lex(errors) => errors
+                    .into_iter()
+                    .map(|(expected, found)| unexpected(expression, expected.as_str(), found))
+                    .collect(),
+                load::Error::Parse(errors) => errors
+                    .into_iter()
+                    .map(|(expected, found)| unexpected(expression, expected.as_str(), found))
+                    .collect::<Vec<_>>(),
+            });
+            filter_error(expression, messages)
+        })?;
+
+        let filter = Compiler::default()
+            .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+            .compile(modules)
+            .map_err(|errors| {
+                let messages = errors.into_iter().flat_map(|(_, errors)| {
+                    errors
+                        .into_iter()
+                        .map(|(name, kind)| format!("undefined {} `{}`", kind.as_str(), name))
+                });
+                filter_error(expression, messages)
+            })?;
+        Ok(JqFilter { filter })
+    }
+
+    /// Run the filter over `input` and collect its outputs. The first error
+    /// raised while running it fails the whole run.
+    pub fn apply(&self, input: Value) -> Result<Vec<Value>, ScanError> {
+        let inputs = RcIter::new(core::iter::empty());
+        self.filter
+            .run((Ctx::new([], &inputs), Val::from(input)))
+            .map(|output| {
+                output.map(Value::from).map_err(|err| {
+                    // `error("message")` reads better without the quotes
+                    ScanError::Filter(match err.into_val() {
+                        Val::Str(message) => message.to_string(),
+                        value => value.to_string(),
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+/// Describe what the parser expected where `rest` of `expression` starts.
+fn unexpected(expression: &str, expected: &str, rest: &str) -> String {
+    let position = expression.len() - rest.len();
+    if rest.is_empty() {
+        format!("expected {} at the end", expected)
+    } else {
+        format!("expected {} at position {}", expected, position)
+    }
+}
+
+fn filter_error(expression: &str, messages: impl Iterator<Item = String>) -> ScanError {
+    let messages: Vec<String> = messages.collect();
+    ScanError::Filter(format!(
+        "invalid filter `{}`: {}",
+        expression,
+        messages.join("; ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_apply_yields_every_output() {
+        let filter = JqFilter::new(".entries[] | select(.type == \"file\") | .path").unwrap();
+        let document = json!({
+            "entries": [
+                { "path": "a", "type": "directory" },
+                { "path": "a/b.txt", "type": "file", "size": 3 },
+                { "path": "c.txt", "type": "file", "size": 5 },
+            ]
+        });
+        assert_eq!(
+            filter.apply(document.clone()).unwrap(),
+            vec![json!("a/b.txt"), json!("c.txt")]
+        );
+
+        // Definitions of the standard library are available
+        let total = JqFilter::new("[.entries[].size // 0] | add").unwrap();
+        assert_eq!(total.apply(document).unwrap(), vec![json!(8)]);
+    }
+
+    #[test]
+    fn test_invalid_filters_are_filter_errors() {
+        for expression in [".entries[", "nonexistent_function(1)", "| ."] {
+            let err = JqFilter::new(expression).err().unwrap();
+            assert!(matches!(err, ScanError::Filter(_)), "{:?}", err);
+            assert!(err.to_string().contains(expression), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors_fail_the_run() {
+        let filter = JqFilter::new(".size + \"x\"").unwrap();
+        let err = filter.apply(json!({ "size": 1 })).unwrap_err();
+        assert!(matches!(err, ScanError::Filter(_)), "{:?}", err);
+
+        let filter = JqFilter::new("error(\"stop\")").unwrap();
+        assert_eq!(
+            filter.apply(json!(null)).unwrap_err().to_string(),
+            "Filter error: stop"
+        );
+    }
+}
@@ -1,12 +1,51 @@
+mod algebra;
 mod checksum;
+mod checksum_cache;
+mod content_type;
 mod diff;
+mod elf;
+mod encoding;
 mod error;
 mod fs_scan;
+mod git_scan;
+mod history;
+mod index;
+mod jq;
+mod manifest;
+mod migration;
+mod output;
 mod plumbah;
+mod provenance;
+mod schema;
+mod signature;
 mod snapshot;
+mod sqlite;
+mod store;
+mod stream;
+mod tar_scan;
+#[cfg(any(test, feature = "test-util"))]
+mod test_util;
+mod verify;
 pub use crate::checksum::*;
+pub use crate::content_type::*;
 pub use crate::diff::*;
+pub use crate::elf::*;
+pub use crate::encoding::*;
 pub use crate::error::*;
 pub use crate::fs_scan::*;
+pub use crate::git_scan::*;
+pub use crate::history::*;
+pub use crate::index::*;
+pub use crate::jq::*;
+pub use crate::manifest::*;
+pub use crate::migration::*;
+pub use crate::output::*;
 pub use crate::plumbah::*;
+pub use crate::provenance::*;
+pub use crate::schema::*;
+pub use crate::signature::*;
 pub use crate::snapshot::*;
+pub use crate::sqlite::*;
+pub use crate::store::*;
+pub use crate::stream::*;
+pub use crate::verify::*;
@@ -0,0 +1,485 @@
+//! Conversion between snapshots and checksum manifests.
+//!
+//! Reads and writes the `HASH  path` lines of `sha256sum`, `sha512sum`,
+//! `sha1sum`, `md5sum` and `b3sum`, as well as the BSD-style
+//! `SHA256 (path) = HASH` lines of `sha256sum --tag` and BSD `sha256`. A
+//! manifest records nothing but file checksums: snapshots read from one have
+//! no sizes, modes or times, and their directories are implied by the paths.
+
+use std::{
+    collections::BTreeMap,
+    io::BufRead,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    Meta, PlumbahObject, Status,
+    error::ScanError,
+    migration::SNAPSHOT_VERSION,
+    snapshot::{ChecksumAlgorithm, EntryType, Snapshot, SnapshotEntry},
+    tar_scan::normalize_member_path,
+};
+
+/// Line layout of a checksum manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestStyle {
+    /// `HASH  path`, as written by `sha256sum` and friends.
+    Gnu,
+    /// `SHA256 (path) = HASH`, as written by `sha256sum --tag` and BSD tools.
+    Bsd,
+}
+
+/// Algorithms that have a manifest tool, with their BSD tag and digest length.
+const MANIFEST_ALGORITHMS: [(ChecksumAlgorithm, &str, usize); 5] = [
+    (ChecksumAlgorithm::Md5, "MD5", 32),
+    (ChecksumAlgorithm::Sha1, "SHA1", 40),
+    (ChecksumAlgorithm::Sha256, "SHA256", 64),
+    (ChecksumAlgorithm::Sha512, "SHA512", 128),
+    (ChecksumAlgorithm::Blake3, "BLAKE3", 64),
+];
+
+/// The algorithm a manifest file uses, judging by its name: `SHA256SUMS`,
+/// `B3SUMS`, `release.tar.sha256`, `files.md5sum` and so on. Returns `None` for
+/// names that do not look like a manifest, and for anything but a regular
+/// file, such as a directory that happens to be called `SHA256SUMS`.
+pub fn manifest_algorithm(path: &Path) -> Option<ChecksumAlgorithm> {
+    if !path.is_file() {
+        return None;
+    }
+    algorithm_for_name(path)
+}
+
+fn algorithm_for_name(path: &Path) -> Option<ChecksumAlgorithm> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((_, extension)) => (None, extension),
+        None => (Some(name.as_str()), ""),
+    };
+    let tool = stem
+        .and_then(|stem| stem.strip_suffix("sums"))
+        .unwrap_or_else(|| extension.strip_suffix("sum").unwrap_or(extension));
+
+    match tool {
+        "md5" => Some(ChecksumAlgorithm::Md5),
+        "sha1" => Some(ChecksumAlgorithm::Sha1),
+        "sha256" => Some(ChecksumAlgorithm::Sha256),
+        "sha512" => Some(ChecksumAlgorithm::Sha512),
+        "b3" | "blake3" => Some(ChecksumAlgorithm::Blake3),
+        _ => None,
+    }
+}
+
+impl Snapshot {
+    /// Read a checksum manifest into a snapshot rooted at `root`.
+    ///
+    /// `algorithm` is required to tell `b3sum` output from `sha256sum` output;
+    /// without it the algorithm comes from BSD tags, or else from the digest
+    /// length (64 hex digits are taken to be SHA-256). Blank lines and `#`
+    /// comments are skipped, `./` prefixes are dropped and escaped names (lines
+    /// starting with `\`) are unescaped.
+    pub fn from_manifest<R: BufRead>(
+        reader: R,
+        root: impl Into<PathBuf>,
+        algorithm: Option<ChecksumAlgorithm>,
+    ) -> Result<Snapshot, ScanError> {
+        let start = Instant::now();
+        let mut algorithm = algorithm;
+        let mut files = BTreeMap::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let error =
+                |message: String| ScanError::Manifest(format!("line {}: {}", index + 1, message));
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (escaped, line) = match line.strip_prefix('\\') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let (tagged, digest, name) =
+                parse_line(line).ok_or_else(|| error("not a checksum line".to_string()))?;
+
+            let line_algorithm = match (algorithm, tagged) {
+                (Some(expected), Some(found)) if expected != found => {
+                    return Err(error(format!(
+                        "expected {} checksums, found {}",
+                        expected.name(),
+                        found.name()
+                    )));
+                }
+                (Some(expected), _) => expected,
+                (None, Some(found)) => found,
+                (None, None) => algorithm_for_length(digest.len()).ok_or_else(|| {
+                    error(format!(
+                        "no known algorithm has {}-digit digests",
+                        digest.len()
+                    ))
+                })?,
+            };
+            algorithm = Some(line_algorithm);
+
+            if Some(digest.len()) != digest_length(line_algorithm)
+                || !digest.bytes().all(|b| b.is_ascii_hexdigit())
+            {
+                return Err(error(format!("invalid {} digest", line_algorithm.name())));
+            }
+
+            let name = if escaped {
+                unescape(name).ok_or_else(|| error("invalid escape sequence".to_string()))?
+            } else {
+                name.to_string()
+            };
+            let path = normalize_member_path(Path::new(&name))
+                .ok()
+                .filter(|path| !path.as_os_str().is_empty())
+                .ok_or_else(|| error(format!("invalid path '{}'", name)))?;
+
+            files.insert(
+                path,
+                format!("{}:{}", line_algorithm.name(), digest.to_ascii_lowercase()),
+            );
+        }
+
+        let mut entries = BTreeMap::new();
+        for (path, checksum) in files {
+            for directory in path.ancestors().skip(1) {
+                entries
+                    .entry(directory.to_path_buf())
+                    .or_insert_with(|| manifest_entry(directory, EntryType::Directory, None));
+            }
+            entries.insert(
+                path.clone(),
+                manifest_entry(&path, EntryType::File, Some(checksum)),
+            );
+        }
+        let entries: Vec<_> = entries.into_values().collect();
+
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION.to_string(),
+            root: root.into(),
+            checksum_algorithm: algorithm.unwrap_or(ChecksumAlgorithm::Sha256),
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
+            plumbah: PlumbahObject::new(
+                Status::Ok,
+                Meta::new(
+                    "galdi_snapshot",
+                    env!("CARGO_PKG_VERSION"),
+                    true,
+                    false,
+                    true,
+                    true, // the manifest fully determines the output
+                    start.elapsed().as_millis() as u64,
+                    Utc::now(),
+                ),
+            ),
+            count: entries.len(),
+            entries,
+        })
+    }
+
+    /// Render the files of this snapshot as a checksum manifest.
+    ///
+    /// Uses the `algorithm` checksum of every file, whether it is the primary
+    /// checksum or an extra one; fails if any file lacks it. Directories and
+    /// symlinks are not listed.
+    pub fn to_manifest(
+        &self,
+        algorithm: ChecksumAlgorithm,
+        style: ManifestStyle,
+    ) -> Result<String, ScanError> {
+        let tag = bsd_tag(algorithm).ok_or_else(|| {
+            ScanError::Manifest(format!(
+                "{} checksums cannot be written as a manifest",
+                algorithm.name()
+            ))
+        })?;
+
+        let mut manifest = String::new();
+        for entry in &self.entries {
+            if entry.entry_type != EntryType::File {
+                continue;
+            }
+            let digest = entry
+                .all_checksums()
+                .get(&algorithm)
+                .and_then(|checksum| checksum.split_once(':'))
+                .map(|(_, digest)| digest)
+                .ok_or_else(|| {
+                    ScanError::Manifest(format!(
+                        "{} has no {} checksum; snapshot with --checksum {} or --extra-checksum {}",
+                        entry.path.display(),
+                        algorithm.name(),
+                        algorithm.name(),
+                        algorithm.name()
+                    ))
+                })?;
+
+            let name = entry.path.to_string_lossy();
+            let (prefix, name) = escape(&name);
+            let line = match style {
+                ManifestStyle::Gnu => format!("{}{}  {}\n", prefix, digest, name),
+                ManifestStyle::Bsd => format!("{}{} ({}) = {}\n", prefix, tag, name, digest),
+            };
+            manifest.push_str(&line);
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// Split a manifest line into its BSD tag (if any), digest and file name.
+fn parse_line(line: &str) -> Option<(Option<ChecksumAlgorithm>, &str, &str)> {
+    if let Some((tag, rest)) = line.split_once(" (")
+        && let Some(algorithm) = algorithm_for_tag(tag)
+    {
+        let (name, digest) = rest.rsplit_once(") = ")?;
+        return Some((Some(algorithm), digest, name));
+    }
+
+    // `HASH  name` in text mode, `HASH *name` in binary mode.
+    let (digest, rest) = line.split_once(' ')?;
+    let name = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+    Some((None, digest, name))
+}
+
+fn bsd_tag(algorithm: ChecksumAlgorithm) -> Option<&'static str> {
+    MANIFEST_ALGORITHMS
+        .iter()
+        .find(|(candidate, _, _)| *candidate == algorithm)
+        .map(|(_, tag, _)| *tag)
+}
+
+fn digest_length(algorithm: ChecksumAlgorithm) -> Option<usize> {
+    MANIFEST_ALGORITHMS
+        .iter()
+        .find(|(candidate, _, _)| *candidate == algorithm)
+        .map(|(_, _, length)| *length)
+}
+
+fn algorithm_for_tag(tag: &str) -> Option<ChecksumAlgorithm> {
+    MANIFEST_ALGORITHMS
+        .iter()
+        .find(|(_, candidate, _)| *candidate == tag)
+        .map(|(algorithm, _, _)| *algorithm)
+}
+
+/// The first algorithm with `length`-digit digests; SHA-256 wins over BLAKE3.
+fn algorithm_for_length(length: usize) -> Option<ChecksumAlgorithm> {
+    MANIFEST_ALGORITHMS
+        .iter()
+        .find(|(_, _, candidate)| *candidate == length)
+        .map(|(algorithm, _, _)| *algorithm)
+}
+
+fn manifest_entry(path: &Path, entry_type: EntryType, checksum: Option<String>) -> SnapshotEntry {
+    SnapshotEntry {
+        path: path.to_path_buf(),
+        entry_type,
+        size: None,
+        mode: None,
+        uid: None,
+        gid: None,
+        mtime: DateTime::<Utc>::UNIX_EPOCH,
+        checksum,
+        checksums: Default::default(),
+        content_type: None,
+        elf: None,
+        target: None,
+    }
+}
+
+/// Escape a file name the way coreutils does: names containing `\`, newline
+/// or carriage return get a leading `\` on the line and those characters escaped.
+fn escape(name: &str) -> (&'static str, String) {
+    if !name.contains(['\\', '\n', '\r']) {
+        return ("", name.to_string());
+    }
+    let escaped = name
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    ("\\", escaped)
+}
+
+fn unescape(name: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => unescaped.push('\\'),
+            'n' => unescaped.push('\n'),
+            'r' => unescaped.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    fn load(manifest: &str, algorithm: Option<ChecksumAlgorithm>) -> Result<Snapshot, ScanError> {
+        Snapshot::from_manifest(manifest.as_bytes(), "downloads", algorithm)
+    }
+
+    #[test]
+    fn test_manifest_algorithm_from_file_name() {
+        let cases = [
+            ("SHA256SUMS", Some(ChecksumAlgorithm::Sha256)),
+            ("dist/SHA512SUMS", Some(ChecksumAlgorithm::Sha512)),
+            ("B3SUMS", Some(ChecksumAlgorithm::Blake3)),
+            ("release.tar.gz.sha256", Some(ChecksumAlgorithm::Sha256)),
+            ("files.md5sum", Some(ChecksumAlgorithm::Md5)),
+            ("image.iso.sha1", Some(ChecksumAlgorithm::Sha1)),
+            ("snapshot.json", None),
+            ("SUMS", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(algorithm_for_name(Path::new(name)), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_manifest_algorithm_needs_a_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manifest = dir.path().join("SHA256SUMS");
+        assert_eq!(manifest_algorithm(&manifest), None);
+        std::fs::write(&manifest, "").unwrap();
+        assert_eq!(
+            manifest_algorithm(&manifest),
+            Some(ChecksumAlgorithm::Sha256)
+        );
+
+        let directory = dir.path().join("release.sha256");
+        std::fs::create_dir(&directory).unwrap();
+        assert_eq!(manifest_algorithm(&directory), None);
+    }
+
+    #[test]
+    fn test_from_manifest_gnu_and_bsd_lines() {
+        let manifest = format!(
+            "# release files\n{0}  ./a.txt\n{0} *bin/tool\r\n\nSHA256 (docs/read me) = {1}\n",
+            EMPTY_SHA256,
+            EMPTY_SHA256.to_uppercase()
+        );
+        let snapshot = load(&manifest, None).unwrap();
+
+        assert_eq!(snapshot.checksum_algorithm, ChecksumAlgorithm::Sha256);
+        let paths: Vec<_> = snapshot
+            .entries
+            .iter()
+            .map(|entry| (entry.path.to_string_lossy().into_owned(), entry.entry_type))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("".to_string(), EntryType::Directory),
+                ("a.txt".to_string(), EntryType::File),
+                ("bin".to_string(), EntryType::Directory),
+                ("bin/tool".to_string(), EntryType::File),
+                ("docs".to_string(), EntryType::Directory),
+                ("docs/read me".to_string(), EntryType::File),
+            ]
+        );
+        let expected = format!("sha256:{}", EMPTY_SHA256);
+        assert_eq!(
+            snapshot.entries[5].checksum.as_deref(),
+            Some(expected.as_str())
+        );
+        assert_eq!(snapshot.count, 6);
+    }
+
+    #[test]
+    fn test_from_manifest_explicit_algorithm() {
+        let manifest = format!("{}  a.bin\n", EMPTY_SHA256);
+        let snapshot = load(&manifest, Some(ChecksumAlgorithm::Blake3)).unwrap();
+        assert_eq!(snapshot.checksum_algorithm, ChecksumAlgorithm::Blake3);
+        assert!(
+            snapshot.entries[1]
+                .checksum
+                .as_deref()
+                .unwrap()
+                .starts_with("blake3:")
+        );
+
+        let tagged = format!("SHA256 (a.bin) = {}\n", EMPTY_SHA256);
+        let result = load(&tagged, Some(ChecksumAlgorithm::Blake3));
+        assert!(matches!(result, Err(ScanError::Manifest(msg)) if msg.starts_with("line 1:")));
+    }
+
+    #[test]
+    fn test_from_manifest_rejects_bad_lines() {
+        assert!(load("not a manifest\n", None).is_err());
+        assert!(load("abc123  short.txt\n", None).is_err());
+        let wrong_length = format!("{}  a\n{}  b\n", EMPTY_SHA256, &EMPTY_SHA256[..40]);
+        assert!(
+            matches!(load(&wrong_length, None), Err(ScanError::Manifest(msg)) if msg.starts_with("line 2:"))
+        );
+    }
+
+    #[test]
+    fn test_manifest_round_trip_with_escapes() {
+        let manifest = format!(
+            "{0}  plain.txt\n\\{0}  back\\\\slash\\nnewline\n",
+            EMPTY_SHA256
+        );
+        let snapshot = load(&manifest, None).unwrap();
+        assert!(
+            snapshot
+                .entries
+                .iter()
+                .any(|entry| entry.path == Path::new("back\\slash\nnewline"))
+        );
+
+        let written = snapshot
+            .to_manifest(ChecksumAlgorithm::Sha256, ManifestStyle::Gnu)
+            .unwrap();
+        assert_eq!(
+            written,
+            format!(
+                "\\{0}  back\\\\slash\\nnewline\n{0}  plain.txt\n",
+                EMPTY_SHA256
+            )
+        );
+
+        let bsd = snapshot
+            .to_manifest(ChecksumAlgorithm::Sha256, ManifestStyle::Bsd)
+            .unwrap();
+        assert!(bsd.ends_with(&format!("SHA256 (plain.txt) = {}\n", EMPTY_SHA256)));
+        let checksums = |snapshot: &Snapshot| -> Vec<_> {
+            snapshot
+                .entries
+                .iter()
+                .map(|entry| (entry.path.clone(), entry.checksum.clone()))
+                .collect()
+        };
+        assert_eq!(checksums(&load(&bsd, None).unwrap()), checksums(&snapshot));
+    }
+
+    #[test]
+    fn test_to_manifest_requires_checksum() {
+        let manifest = format!("{}  a.txt\n", EMPTY_SHA256);
+        let snapshot = load(&manifest, None).unwrap();
+
+        let missing = snapshot.to_manifest(ChecksumAlgorithm::Md5, ManifestStyle::Gnu);
+        assert!(matches!(missing, Err(ScanError::Manifest(msg)) if msg.contains("a.txt")));
+
+        let unsupported = snapshot.to_manifest(ChecksumAlgorithm::XXH3_64, ManifestStyle::Gnu);
+        assert!(matches!(unsupported, Err(ScanError::Manifest(_))));
+    }
+}
@@ -0,0 +1,228 @@
+//! Versions of the snapshot format, and upgrades of older snapshots.
+//!
+//! Snapshot versions are `MAJOR.MINOR`. Within a major version, minor
+//! versions only ever add optional fields. Hence:
+//!
+//! - snapshots of an older minor version are upgraded in memory when they are
+//!   loaded, one step of `MIGRATIONS` at a time, before they are deserialized;
+//! - snapshots of a newer minor version are read as far as this build
+//!   understands them (fields it does not know are ignored), but never
+//!   rewritten;
+//! - snapshots of any other major version are refused.
+//!
+//! | Version | Changes                                                              |
+//! |---------|----------------------------------------------------------------------|
+//! | 1.0     | `root`, `checksum_algorithm`, `count`, `entries` with `path`, `type`, `size`, `mode`, `mtime`, `checksum`, `target` |
+//! | 1.1     | optional `extra_checksums`, `checksum_key_id` and `sample_len`; optional `uid`, `gid`, `checksums`, `content_type` and `elf` per entry |
+
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::error::ScanError;
+use crate::snapshot::Snapshot;
+
+/// The snapshot format version this build writes.
+pub const SNAPSHOT_VERSION: &str = "1.1";
+
+/// A parsed `MAJOR.MINOR` snapshot format version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SchemaVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl SchemaVersion {
+    pub fn parse(version: &str) -> Result<Self, ScanError> {
+        version
+            .split_once('.')
+            .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)))
+            .map(|(major, minor)| SchemaVersion { major, minor })
+            .ok_or_else(|| ScanError::Schema(format!("malformed snapshot version {:?}", version)))
+    }
+
+    /// The version this build writes, `SNAPSHOT_VERSION`.
+    pub fn current() -> Self {
+        Self::parse(SNAPSHOT_VERSION).expect("SNAPSHOT_VERSION is well-formed")
+    }
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// How a snapshot's version relates to the one this build writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    Current,
+    /// Older, and upgraded when loaded.
+    Upgradable,
+    /// Newer within the same major version, and read as far as understood.
+    Newer,
+}
+
+/// Check `version` against the compatibility rules; other major versions
+/// are an error.
+pub fn compatibility(version: &str) -> Result<Compatibility, ScanError> {
+    let version = SchemaVersion::parse(version)?;
+    let current = SchemaVersion::current();
+    if version.major != current.major {
+        return Err(ScanError::Schema(format!(
+            "snapshot version {} is not supported; this build reads {}.x",
+            version, current.major
+        )));
+    }
+    Ok(match version.cmp(&current) {
+        std::cmp::Ordering::Equal => Compatibility::Current,
+        std::cmp::Ordering::Less => Compatibility::Upgradable,
+        std::cmp::Ordering::Greater => Compatibility::Newer,
+    })
+}
+
+/// One step along the upgrade path, applied to the snapshot document while
+/// it is still untyped, so that it may rename or reshape fields.
+struct Migration {
+    from: SchemaVersion,
+    to: SchemaVersion,
+    apply: fn(&mut Map<String, Value>),
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: SchemaVersion { major: 1, minor: 0 },
+    to: SchemaVersion { major: 1, minor: 1 },
+    // 1.1 only added optional fields, which 1.0 snapshots simply lack
+    apply: |_| {},
+}];
+
+/// Bring the snapshot `document` up to `SNAPSHOT_VERSION`, recording the
+/// version it had as `migrated_from` in its `$plumbah.meta`. Returns that
+/// version, or `None` when there was nothing to upgrade.
+pub fn upgrade_document(document: &mut Value) -> Result<Option<String>, ScanError> {
+    let document = document
+        .as_object_mut()
+        .ok_or_else(|| ScanError::Schema("a snapshot must be an object".to_string()))?;
+    let from = document
+        .get("version")
+        .and_then(Value::as_str)
+        .ok_or_else(|| ScanError::Schema("snapshot has no version".to_string()))?
+        .to_string();
+    if compatibility(&from)? != Compatibility::Upgradable {
+        return Ok(None);
+    }
+
+    let current = SchemaVersion::current();
+    let mut version = SchemaVersion::parse(&from)?;
+    while version < current {
+        let step = MIGRATIONS
+            .iter()
+            .find(|step| step.from == version)
+            .ok_or_else(|| {
+                ScanError::Schema(format!("no upgrade path from snapshot version {}", version))
+            })?;
+        (step.apply)(document);
+        version = step.to;
+    }
+
+    document.insert("version".to_string(), Value::from(SNAPSHOT_VERSION));
+    if let Some(meta) = document
+        .get_mut("$plumbah")
+        .and_then(|plumbah| plumbah.get_mut("meta"))
+        .and_then(Value::as_object_mut)
+    {
+        meta.insert("migrated_from".to_string(), Value::from(from.as_str()));
+    }
+    Ok(Some(from))
+}
+
+/// Deserialize the snapshot `document` once it is upgraded to
+/// `SNAPSHOT_VERSION`, refusing versions this build cannot read.
+pub fn upgraded_snapshot(mut document: Value) -> Result<Snapshot, ScanError> {
+    upgrade_document(&mut document)?;
+    serde_json::from_value(document).map_err(|err| ScanError::Schema(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::SnapshotEntry;
+
+    fn snapshot(version: &str) -> Value {
+        let entry = SnapshotEntry::test_file("dist/app", "xxh3_64:0123456789abcdef");
+        serde_json::to_value(Snapshot {
+            version: version.to_string(),
+            ..Snapshot::test_of(vec![entry])
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_versions_parse_and_order() {
+        let version = SchemaVersion::parse("1.10").unwrap();
+        assert_eq!(
+            version,
+            SchemaVersion {
+                major: 1,
+                minor: 10
+            }
+        );
+        assert!(SchemaVersion::parse("1.9").unwrap() < version);
+        assert_eq!(version.to_string(), "1.10");
+
+        for malformed in ["", "1", "1.x", "v1.0", "1.0.0"] {
+            assert!(matches!(
+                SchemaVersion::parse(malformed),
+                Err(ScanError::Schema(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_older_snapshot_is_upgraded() {
+        let mut document = snapshot("1.0");
+        assert_eq!(
+            upgrade_document(&mut document).unwrap().as_deref(),
+            Some("1.0")
+        );
+        assert_eq!(document["version"], SNAPSHOT_VERSION);
+
+        let upgraded = upgraded_snapshot(snapshot("1.0")).unwrap();
+        assert_eq!(upgraded.version, SNAPSHOT_VERSION);
+        assert_eq!(
+            upgraded.plumbah.meta.as_ref().unwrap().extra["migrated_from"],
+            "1.0"
+        );
+        assert_eq!(
+            upgraded.entries[0].checksum.as_deref(),
+            Some("xxh3_64:0123456789abcdef")
+        );
+    }
+
+    #[test]
+    fn test_current_and_newer_minor_are_left_alone() {
+        let mut current = snapshot(SNAPSHOT_VERSION);
+        assert_eq!(upgrade_document(&mut current).unwrap(), None);
+        assert!(current["$plumbah"]["meta"].get("migrated_from").is_none());
+
+        let mut newer = snapshot("1.99");
+        assert_eq!(compatibility("1.99").unwrap(), Compatibility::Newer);
+        assert_eq!(upgrade_document(&mut newer).unwrap(), None);
+        assert_eq!(newer["version"], "1.99");
+    }
+
+    #[test]
+    fn test_other_major_version_is_refused() {
+        let error = upgrade_document(&mut snapshot("2.0")).unwrap_err();
+        assert!(error.to_string().contains("not supported"));
+        assert!(upgraded_snapshot(snapshot("0.9")).is_err());
+
+        // Documents without a version are not snapshots at all
+        let mut unversioned = snapshot("1.0");
+        unversioned.as_object_mut().unwrap().remove("version");
+        assert!(matches!(
+            upgrade_document(&mut unversioned),
+            Err(ScanError::Schema(_))
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+//! Where the tools send what they produce: back to the caller for printing,
+//! or to the file named by `--output`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::encoding::{Compression, Encoding, write_encoded};
+
+/// Return `output` for printing, or write it to `path` and return nothing.
+pub fn print_or_write(output: String, path: Option<&Path>) -> io::Result<String> {
+    let Some(path) = path else {
+        return Ok(output);
+    };
+    let mut file = File::create(path)?;
+    if !output.is_empty() {
+        writeln!(file, "{}", output)?;
+    }
+    Ok(String::new())
+}
+
+/// Where output that is not returned for printing goes: `path`, or stdout.
+pub fn output_writer(path: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
+/// Render `document` as pretty-printed JSON for printing, or write it out
+/// in `encoding` with `compression` and return nothing to print: to `path`
+/// when given, and to stdout in the binary and compressed formats.
+pub fn render_or_write<T: Serialize + ?Sized>(
+    document: &T,
+    path: Option<&Path>,
+    encoding: Encoding,
+    compression: Compression,
+) -> io::Result<String> {
+    if path.is_none() && encoding == Encoding::Json && compression == Compression::None {
+        return Ok(serde_json::to_string_pretty(document)?);
+    }
+    write_encoded(document, output_writer(path)?, encoding, compression)?;
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_print_or_write() {
+        assert_eq!(print_or_write("1".to_string(), None).unwrap(), "1");
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.json");
+        assert_eq!(print_or_write("1".to_string(), Some(&path)).unwrap(), "");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1\n");
+
+        // Nothing to write still leaves an empty file behind
+        print_or_write(String::new(), Some(&path)).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn test_render_or_write() {
+        let document = serde_json::json!({ "count": 1 });
+        let rendered = render_or_write(&document, None, Encoding::Json, Compression::None);
+        assert_eq!(rendered.unwrap(), "{\n  \"count\": 1\n}");
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.cbor.zst");
+        let written = render_or_write(&document, Some(&path), Encoding::Cbor, Compression::Zstd);
+        assert_eq!(written.unwrap(), "");
+        let restored: serde_json::Value =
+            crate::encoding::read_encoded(File::open(&path).unwrap()).unwrap();
+        assert_eq!(restored, document);
+    }
+}
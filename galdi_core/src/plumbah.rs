@@ -2,13 +2,14 @@ use chrono::{DateTime, Utc};
 /// Plumbah annotation data structures for JSON output.
 /// This module defines the Plumbah annotation structure that is added to JSON output
 /// via the `$plumbah` property, including metadata, status, and error information.
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, path::PathBuf};
 
 /// The current Plumbah annotation version.
 pub const PLUMBAH_VERSION: &str = "1.0";
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct PlumbahObject {
     pub version: String, // "1.0"
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -42,7 +43,7 @@ impl PlumbahObject {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Status {
     Ok,
@@ -51,7 +52,7 @@ pub enum Status {
 }
 
 /// Streaming summary for tail line
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct StreamSummary {
     pub total: usize,
     pub processed: usize,
@@ -59,14 +60,14 @@ pub struct StreamSummary {
 }
 
 /// Profile metadata for streaming profile support
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ProfileMetadata {
     pub name: String,
     #[serde(flatten)]
     pub data: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Meta {
     /// Whether the invocation is idempotent, and as such can be safely retried without side effects.
     pub idempotent: bool,
@@ -136,7 +137,7 @@ impl Meta {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PlumbahError {
     pub code: String,
     pub message: String,
@@ -148,6 +149,7 @@ pub struct PlumbahError {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::single_component_path_imports)]
 mod tests {
     use super::*;
     use serde_json;
@@ -0,0 +1,280 @@
+//! Where a snapshot was taken, by whom, and with which options.
+//!
+//! Recorded as `$plumbah.meta.scan_provenance`, so that a snapshot handed
+//! over long after the fact still tells which host and command produced it,
+//! and whether it was scanned with the same settings as another one. (Under
+//! `provenance`, snapshots computed by `snapshot-ops` record their inputs.)
+
+use std::collections::BTreeMap;
+use std::env;
+
+use serde::{Deserialize, Serialize};
+
+use crate::checksum::ChecksumKey;
+use crate::fs_scan::ScanOptions;
+use crate::plumbah::Meta;
+use crate::snapshot::ChecksumAlgorithm;
+
+/// Key of the provenance in `Meta::extra`.
+const PROVENANCE_KEY: &str = "scan_provenance";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub hostname: Option<String>,
+    pub username: Option<String>,
+    /// Arguments of the process that took the snapshot, program name first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command_line: Vec<String>,
+    /// Operating system, as in `std::env::consts::OS`.
+    pub os: String,
+    pub arch: String,
+    /// Kernel release, e.g. `6.8.0-45-generic`, where the platform reports one.
+    pub kernel_release: Option<String>,
+    /// Options of a directory scan; absent for tar streams and git objects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan: Option<ScanSettings>,
+    /// The jq `--filter` the output was run through.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    /// Free-form `--label key=value` pairs.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// The `ScanOptions` that decide which entries a scan records and how.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanSettings {
+    pub checksum_algorithm: ChecksumAlgorithm,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_checksums: Vec<ChecksumAlgorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum_key_id: Option<String>,
+    pub checksum_cache: bool,
+    pub follow_symlinks: bool,
+    pub max_depth: Option<usize>,
+    pub exclude_patterns: Vec<String>,
+    pub normalize_paths: bool,
+}
+
+impl From<&ScanOptions> for ScanSettings {
+    fn from(options: &ScanOptions) -> Self {
+        ScanSettings {
+            checksum_algorithm: options.checksum_algorithm,
+            extra_checksums: options.extra_checksum_algorithms(),
+            checksum_key_id: options.checksum_key.as_ref().map(ChecksumKey::id),
+            checksum_cache: options.caches_checksums(),
+            follow_symlinks: options.follow_symlinks,
+            max_depth: options.max_depth,
+            exclude_patterns: options.exclude_patterns.clone(),
+            normalize_paths: options.normalize_paths,
+        }
+    }
+}
+
+impl Provenance {
+    /// Describe the current host, user and process.
+    pub fn capture() -> Self {
+        let (hostname, kernel_release) = host();
+        Provenance {
+            hostname,
+            username: username(),
+            command_line: env::args_os()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            kernel_release,
+            scan: None,
+            filter: None,
+            labels: BTreeMap::new(),
+        }
+    }
+
+    /// Leave out what identifies the host and its user: the hostname, the
+    /// username and the command line, which may name private paths.
+    pub fn redacted(mut self) -> Self {
+        self.hostname = None;
+        self.username = None;
+        self.command_line.clear();
+        self
+    }
+
+    pub fn with_scan(mut self, options: &ScanOptions) -> Self {
+        self.scan = Some(ScanSettings::from(options));
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Option<String>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_labels(mut self, labels: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.labels.extend(labels);
+        self
+    }
+
+    /// The provenance recorded in `meta`, if any.
+    pub fn of(meta: &Meta) -> Option<Provenance> {
+        meta.extra
+            .get(PROVENANCE_KEY)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    /// Record this provenance in `meta`, replacing any recorded before.
+    pub fn record(&self, meta: &mut Meta) {
+        meta.extra.insert(
+            PROVENANCE_KEY.to_string(),
+            serde_json::to_value(self).expect("provenance serializes to JSON"),
+        );
+    }
+}
+
+/// Parse a `key=value` label. The value may be empty and contain `=`.
+pub fn parse_label(label: &str) -> Result<(String, String), String> {
+    match label.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected `key=value`, got `{}`", label)),
+    }
+}
+
+/// Hostname and kernel release.
+#[cfg(unix)]
+fn host() -> (Option<String>, Option<String>) {
+    let uname = rustix::system::uname();
+    let text =
+        |s: &std::ffi::CStr| Some(s.to_string_lossy().into_owned()).filter(|s| !s.is_empty());
+    (text(uname.nodename()), text(uname.release()))
+}
+
+#[cfg(not(unix))]
+fn host() -> (Option<String>, Option<String>) {
+    (env::var("COMPUTERNAME").ok(), None)
+}
+
+fn username() -> Option<String> {
+    ["USER", "LOGNAME", "USERNAME"]
+        .into_iter()
+        .find_map(|name| env::var(name).ok().filter(|user| !user.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::path::PathBuf;
+
+    use crate::checksum::DEFAULT_SAMPLE_LEN;
+
+    fn options() -> ScanOptions {
+        ScanOptions {
+            root: PathBuf::from("/data"),
+            checksum_algorithm: ChecksumAlgorithm::Sha256,
+            extra_checksums: vec![ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Blake3],
+            checksum_key: None,
+            checksum_cache: true,
+            follow_symlinks: false,
+            max_depth: Some(3),
+            exclude_patterns: vec!["*.log".to_string()],
+            timeout_ms: None,
+            threads: Some(4),
+            normalize_paths: false,
+            sample_len: DEFAULT_SAMPLE_LEN,
+        }
+    }
+
+    fn meta() -> Meta {
+        Meta::new(
+            "test_tool",
+            "1.0.0",
+            true,
+            false,
+            true,
+            false,
+            0,
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn test_capture_describes_this_process() {
+        let provenance = Provenance::capture();
+        assert_eq!(provenance.os, env::consts::OS);
+        assert_eq!(provenance.arch, env::consts::ARCH);
+        assert!(!provenance.command_line.is_empty());
+        assert!(provenance.scan.is_none());
+        assert!(provenance.labels.is_empty());
+    }
+
+    #[test]
+    fn test_scan_settings_follow_the_options() {
+        let provenance = Provenance::capture().with_scan(&options());
+        let scan = provenance.scan.unwrap();
+        assert_eq!(scan.checksum_algorithm, ChecksumAlgorithm::Sha256);
+        assert_eq!(scan.extra_checksums, vec![ChecksumAlgorithm::Blake3]);
+        assert_eq!(scan.max_depth, Some(3));
+        assert_eq!(scan.exclude_patterns, vec!["*.log".to_string()]);
+        assert!(scan.checksum_cache);
+    }
+
+    #[test]
+    fn test_record_and_read_back() {
+        let provenance = Provenance::capture()
+            .with_scan(&options())
+            .with_labels([("env".to_string(), "prod".to_string())]);
+        let mut meta = meta();
+        provenance.record(&mut meta);
+
+        let json = serde_json::to_value(&meta).unwrap();
+        assert_eq!(json["scan_provenance"]["labels"]["env"], "prod");
+        assert_eq!(json["scan_provenance"]["scan"]["max_depth"], 3);
+
+        let meta: Meta = serde_json::from_value(json).unwrap();
+        assert_eq!(Provenance::of(&meta), Some(provenance));
+    }
+
+    #[test]
+    fn test_computed_provenance_is_kept_apart() {
+        let mut meta = meta();
+        meta.extra.insert(
+            "provenance".to_string(),
+            serde_json::json!({ "operation": "merge", "inputs": [] }),
+        );
+        Provenance::capture().record(&mut meta);
+        assert_eq!(meta.extra["provenance"]["operation"], "merge");
+        assert!(Provenance::of(&meta).is_some());
+    }
+
+    #[test]
+    fn test_redacted_leaves_out_host_user_and_command_line() {
+        let provenance = Provenance::capture()
+            .with_scan(&options())
+            .with_filter(Some(".entries | length".to_string()))
+            .redacted();
+        let json = serde_json::to_value(&provenance).unwrap();
+        assert!(json["hostname"].is_null());
+        assert!(json["username"].is_null());
+        assert!(json.get("command_line").is_none());
+        assert_eq!(json["os"], env::consts::OS);
+        assert_eq!(json["filter"], ".entries | length");
+        assert_eq!(json["scan"]["max_depth"], 3);
+    }
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(
+            parse_label("env=prod"),
+            Ok(("env".to_string(), "prod".to_string()))
+        );
+        assert_eq!(
+            parse_label("query=a=b"),
+            Ok(("query".to_string(), "a=b".to_string()))
+        );
+        assert_eq!(
+            parse_label("empty="),
+            Ok(("empty".to_string(), String::new()))
+        );
+        assert!(parse_label("novalue").is_err());
+        assert!(parse_label("=value").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! JSON Schemas of the documents galdi writes, derived from the types that
+//! (de)serialize them so they cannot drift from the actual output.
+//!
+//! The schemas are also kept as files in `galdi_core/schemas/`, for
+//! consumers that generate parsers from them.
+
+use std::fmt;
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde_json::Value;
+
+use crate::diff::DiffResult;
+use crate::plumbah::PlumbahObject;
+use crate::snapshot::{Snapshot, SnapshotEntry};
+use crate::stream::{StreamHead, StreamLine, StreamTail};
+
+/// A kind of document with a published schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DocumentKind {
+    Snapshot,
+    SnapshotEntry,
+    Diff,
+    /// The `$plumbah` annotation itself.
+    Plumbah,
+    /// First line of a JSONL snapshot stream.
+    StreamHead,
+    /// Entry or error line of a JSONL snapshot stream.
+    StreamLine,
+    /// Last line of a JSONL snapshot stream.
+    StreamTail,
+}
+
+impl DocumentKind {
+    pub const ALL: [DocumentKind; 7] = [
+        DocumentKind::Snapshot,
+        DocumentKind::SnapshotEntry,
+        DocumentKind::Diff,
+        DocumentKind::Plumbah,
+        DocumentKind::StreamHead,
+        DocumentKind::StreamLine,
+        DocumentKind::StreamTail,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DocumentKind::Snapshot => "snapshot",
+            DocumentKind::SnapshotEntry => "snapshot-entry",
+            DocumentKind::Diff => "diff",
+            DocumentKind::Plumbah => "plumbah",
+            DocumentKind::StreamHead => "jsonl-head",
+            DocumentKind::StreamLine => "jsonl-line",
+            DocumentKind::StreamTail => "jsonl-tail",
+        }
+    }
+
+    /// The JSON Schema (draft 2020-12) documents of this kind conform to.
+    pub fn json_schema(&self) -> Value {
+        match self {
+            DocumentKind::Snapshot => schema_of::<Snapshot>(),
+            DocumentKind::SnapshotEntry => schema_of::<SnapshotEntry>(),
+            DocumentKind::Diff => schema_of::<DiffResult>(),
+            DocumentKind::Plumbah => schema_of::<PlumbahObject>(),
+            DocumentKind::StreamHead => schema_of::<StreamHead>(),
+            DocumentKind::StreamLine => schema_of::<StreamLine>(),
+            DocumentKind::StreamTail => schema_of::<StreamTail>(),
+        }
+    }
+
+    /// Tell the kind of a whole (non-JSONL) document from its top-level
+    /// fields, if it is one galdi writes.
+    pub fn detect(document: &Value) -> Option<Self> {
+        let has = |field| document.get(field).is_some();
+        if has("entries") {
+            Some(DocumentKind::Snapshot)
+        } else if has("differences") {
+            Some(DocumentKind::Diff)
+        } else if has("$plumbah") || (has("status") && has("version")) {
+            Some(DocumentKind::Plumbah)
+        } else if has("path") && has("type") {
+            Some(DocumentKind::SnapshotEntry)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for DocumentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DocumentKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DocumentKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("Unknown document kind: {}", s))
+    }
+}
+
+fn schema_of<T: JsonSchema>() -> Value {
+    schemars::schema_for!(T).to_value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shipped(kind: DocumentKind) -> &'static str {
+        match kind {
+            DocumentKind::Snapshot => include_str!("../schemas/snapshot.schema.json"),
+            DocumentKind::SnapshotEntry => include_str!("../schemas/snapshot-entry.schema.json"),
+            DocumentKind::Diff => include_str!("../schemas/diff.schema.json"),
+            DocumentKind::Plumbah => include_str!("../schemas/plumbah.schema.json"),
+            DocumentKind::StreamHead => include_str!("../schemas/jsonl-head.schema.json"),
+            DocumentKind::StreamLine => include_str!("../schemas/jsonl-line.schema.json"),
+            DocumentKind::StreamTail => include_str!("../schemas/jsonl-tail.schema.json"),
+        }
+    }
+
+    #[test]
+    fn test_shipped_schemas_are_current() {
+        for kind in DocumentKind::ALL {
+            let shipped: Value = serde_json::from_str(shipped(kind)).unwrap();
+            assert_eq!(
+                shipped,
+                kind.json_schema(),
+                "galdi_core/schemas/{0}.schema.json is stale; regenerate it with `galdi schema {0}`",
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn test_snapshot_schema_describes_annotation_and_entries() {
+        let schema = DocumentKind::Snapshot.json_schema();
+        assert!(schema["properties"]["$plumbah"].is_object());
+        assert!(schema["properties"]["entries"].is_object());
+        let required = schema["required"].as_array().unwrap();
+        for field in [
+            "$plumbah",
+            "version",
+            "root",
+            "checksum_algorithm",
+            "entries",
+        ] {
+            assert!(
+                required.contains(&Value::from(field)),
+                "{} not required",
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn test_kinds_parse_and_detect() {
+        for kind in DocumentKind::ALL {
+            assert_eq!(kind.name().parse::<DocumentKind>(), Ok(kind));
+        }
+        let detect = |document| DocumentKind::detect(&serde_json::json!(document));
+        assert_eq!(
+            detect(serde_json::json!({ "$plumbah": {}, "entries": [] })),
+            Some(DocumentKind::Snapshot)
+        );
+        assert_eq!(
+            detect(serde_json::json!({ "$plumbah": {}, "differences": [] })),
+            Some(DocumentKind::Diff)
+        );
+        assert_eq!(
+            detect(serde_json::json!({ "version": "1.0", "status": "error" })),
+            Some(DocumentKind::Plumbah)
+        );
+        assert_eq!(detect(serde_json::json!([1, 2])), None);
+    }
+}
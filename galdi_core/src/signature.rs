@@ -0,0 +1,356 @@
+//! Ed25519 signatures over snapshots and diff reports.
+//!
+//! A document is signed over its canonical JSON: compact, with object keys
+//! sorted and any embedded signature left out. The signature either travels
+//! next to the document, conventionally as `<document>.sig`, or inside it as
+//! `$plumbah.meta.signature`.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::{Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::checksum::to_hex;
+use crate::error::ScanError;
+
+/// The signature algorithm galdi writes and accepts.
+pub const SIGNATURE_ALGORITHM: &str = "ed25519";
+
+/// A signature over a document, together with the public key that made it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentSignature {
+    pub algorithm: String,
+    /// Hex-encoded public key of the signer.
+    pub public_key: String,
+    /// Hex-encoded signature over the document's canonical JSON.
+    pub signature: String,
+}
+
+/// Secret Ed25519 key, stored in key files as 64 hex characters.
+pub struct SigningKey(ed25519_dalek::SigningKey);
+
+impl SigningKey {
+    /// Generate a key from the operating system's random source.
+    pub fn generate() -> io::Result<Self> {
+        let mut secret = [0u8; 32];
+        getrandom::fill(&mut secret).map_err(io::Error::other)?;
+        Ok(SigningKey(ed25519_dalek::SigningKey::from_bytes(&secret)))
+    }
+
+    pub fn from_hex(hex: &str) -> io::Result<Self> {
+        Ok(SigningKey(ed25519_dalek::SigningKey::from_bytes(
+            &key_bytes(hex)?,
+        )))
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Self::from_hex(&std::fs::read_to_string(path)?)
+    }
+
+    /// The secret key in its key file form.
+    pub fn to_hex(&self) -> String {
+        to_hex(self.0.as_bytes())
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(self.0.verifying_key())
+    }
+
+    /// Sign `document`. A signature already embedded in it is not covered.
+    pub fn sign(&self, document: &Value) -> DocumentSignature {
+        let signature = self.0.sign(&canonical_json(document));
+        DocumentSignature {
+            algorithm: SIGNATURE_ALGORITHM.to_string(),
+            public_key: self.verifying_key().to_hex(),
+            signature: to_hex(&signature.to_bytes()),
+        }
+    }
+}
+
+// Never print the secret itself.
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("public_key", &self.verifying_key().to_hex())
+            .finish()
+    }
+}
+
+/// Public Ed25519 key, stored in key files as 64 hex characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyingKey(ed25519_dalek::VerifyingKey);
+
+impl VerifyingKey {
+    pub fn from_hex(hex: &str) -> io::Result<Self> {
+        ed25519_dalek::VerifyingKey::from_bytes(&key_bytes(hex)?)
+            .map(VerifyingKey)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Self::from_hex(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(self.0.as_bytes())
+    }
+
+    /// Check that `signature` was made over `document` with this key.
+    pub fn verify(&self, document: &Value, signature: &DocumentSignature) -> Result<(), ScanError> {
+        if signature.algorithm != SIGNATURE_ALGORITHM {
+            return Err(ScanError::Signature(format!(
+                "unsupported signature algorithm {}",
+                signature.algorithm
+            )));
+        }
+        let signer = VerifyingKey::from_hex(&signature.public_key)
+            .map_err(|err| ScanError::Signature(format!("malformed public key: {}", err)))?;
+        if signer != *self {
+            return Err(ScanError::Signature(format!(
+                "signed by key {}, not by the trusted key {}",
+                signer.to_hex(),
+                self.to_hex()
+            )));
+        }
+        let bytes: [u8; 64] = from_hex(&signature.signature)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| ScanError::Signature("malformed signature".to_string()))?;
+        self.0
+            .verify(
+                &canonical_json(document),
+                &ed25519_dalek::Signature::from_bytes(&bytes),
+            )
+            .map_err(|_| ScanError::Signature("signature does not match the document".to_string()))
+    }
+}
+
+/// The bytes a signature covers: `document` as compact JSON with object keys
+/// sorted and `$plumbah.meta.signature` left out.
+pub fn canonical_json(document: &Value) -> Vec<u8> {
+    let mut document = document.clone();
+    if let Some(meta) = document
+        .pointer_mut("/$plumbah/meta")
+        .and_then(Value::as_object_mut)
+    {
+        meta.remove("signature");
+    }
+    let mut out = Vec::new();
+    write_canonical(&document, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(b']');
+        }
+        Value::Object(map) => {
+            let mut fields: Vec<_> = map.iter().collect();
+            fields.sort_by(|a, b| a.0.cmp(b.0));
+            out.push(b'{');
+            for (i, (key, value)) in fields.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                out.extend(serde_json::to_vec(key).expect("strings always serialize"));
+                out.push(b':');
+                write_canonical(value, out);
+            }
+            out.push(b'}');
+        }
+        scalar => out.extend(serde_json::to_vec(scalar).expect("JSON values always serialize")),
+    }
+}
+
+/// The signature embedded in `document`, if it carries one.
+pub fn embedded_signature(document: &Value) -> Result<Option<DocumentSignature>, ScanError> {
+    match document.pointer("/$plumbah/meta/signature") {
+        Some(signature) => serde_json::from_value(signature.clone())
+            .map(Some)
+            .map_err(|err| ScanError::Signature(format!("malformed embedded signature: {}", err))),
+        None => Ok(None),
+    }
+}
+
+/// Embed `signature` in `document` as `$plumbah.meta.signature`.
+pub fn embed_signature(
+    document: &mut Value,
+    signature: &DocumentSignature,
+) -> Result<(), ScanError> {
+    let meta = document
+        .pointer_mut("/$plumbah/meta")
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| {
+            ScanError::Signature(
+                "document has no $plumbah.meta to embed a signature in".to_string(),
+            )
+        })?;
+    meta.insert(
+        "signature".to_string(),
+        serde_json::to_value(signature).expect("signatures always serialize"),
+    );
+    Ok(())
+}
+
+/// Where the detached signature of the document at `path` is looked for.
+pub fn detached_signature_path(path: &Path) -> PathBuf {
+    let mut sig = path.as_os_str().to_owned();
+    sig.push(".sig");
+    PathBuf::from(sig)
+}
+
+/// Read the detached signature of the document at `path`, if there is one.
+pub fn read_detached_signature(path: &Path) -> Result<Option<DocumentSignature>, ScanError> {
+    let sig = detached_signature_path(path);
+    match std::fs::read(&sig) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|err| {
+            ScanError::Signature(format!("malformed signature {}: {}", sig.display(), err))
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(ScanError::from_io(err, &sig)),
+    }
+}
+
+/// Check that `document` was signed with `key`, using the `detached`
+/// signature when given and the embedded one otherwise.
+pub fn verify_document(
+    document: &Value,
+    detached: Option<&DocumentSignature>,
+    key: &VerifyingKey,
+) -> Result<(), ScanError> {
+    let embedded = match detached {
+        Some(_) => None,
+        None => embedded_signature(document)?,
+    };
+    match detached.or(embedded.as_ref()) {
+        Some(signature) => key.verify(document, signature),
+        None => Err(ScanError::Signature("document is not signed".to_string())),
+    }
+}
+
+/// Check that `document`, read from `path`, was signed with `key`. A detached
+/// signature next to the file takes precedence over an embedded one.
+pub fn verify_document_at(
+    document: &Value,
+    path: &Path,
+    key: &VerifyingKey,
+) -> Result<(), ScanError> {
+    let detached = if path == Path::new("-") {
+        None
+    } else {
+        read_detached_signature(path)?
+    };
+    verify_document(document, detached.as_ref(), key)
+}
+
+fn key_bytes(hex: &str) -> io::Result<[u8; 32]> {
+    from_hex(hex.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "key files hold 64 hex characters",
+            )
+        })
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document() -> Value {
+        json!({
+            "$plumbah": {
+                "version": "1.0",
+                "status": "ok",
+                "meta": { "tool": "galdi_snapshot", "timestamp": "2026-01-01T00:00:00Z" }
+            },
+            "root": "dist",
+            "entries": [{ "path": "app", "checksum": "xxh3_64:0123456789abcdef" }]
+        })
+    }
+
+    #[test]
+    fn test_embedded_and_detached_signatures_verify() {
+        let key = SigningKey::generate().unwrap();
+        let mut doc = document();
+        let signature = key.sign(&doc);
+
+        assert!(verify_document(&doc, Some(&signature), &key.verifying_key()).is_ok());
+
+        embed_signature(&mut doc, &signature).unwrap();
+        assert_eq!(embedded_signature(&doc).unwrap(), Some(signature));
+        assert!(verify_document(&doc, None, &key.verifying_key()).is_ok());
+    }
+
+    #[test]
+    fn test_tampered_document_fails() {
+        let key = SigningKey::generate().unwrap();
+        let mut doc = document();
+        let signature = key.sign(&doc);
+        embed_signature(&mut doc, &signature).unwrap();
+
+        doc["entries"][0]["checksum"] = json!("xxh3_64:fedcba9876543210");
+        assert!(matches!(
+            verify_document(&doc, None, &key.verifying_key()),
+            Err(ScanError::Signature(_))
+        ));
+        assert!(verify_document(&document(), None, &key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_other_key_is_not_trusted() {
+        let key = SigningKey::generate().unwrap();
+        let other = SigningKey::generate().unwrap();
+        let doc = document();
+        let signature = key.sign(&doc);
+
+        let error = verify_document(&doc, Some(&signature), &other.verifying_key()).unwrap_err();
+        assert!(error.to_string().contains("not by the trusted key"));
+    }
+
+    #[test]
+    fn test_canonical_json_ignores_key_order_and_whitespace() {
+        let reordered: Value = serde_json::from_str(
+            r#"{ "root": "dist", "entries": [{ "checksum": "xxh3_64:0123456789abcdef", "path": "app" }],
+                 "$plumbah": { "meta": { "timestamp": "2026-01-01T00:00:00Z", "tool": "galdi_snapshot" },
+                               "status": "ok", "version": "1.0" } }"#,
+        )
+        .unwrap();
+        assert_eq!(canonical_json(&reordered), canonical_json(&document()));
+    }
+
+    #[test]
+    fn test_key_files_round_trip() {
+        let key = SigningKey::generate().unwrap();
+        let restored = SigningKey::from_hex(&format!("{}\n", key.to_hex())).unwrap();
+        assert_eq!(restored.verifying_key(), key.verifying_key());
+        assert_eq!(
+            VerifyingKey::from_hex(&key.verifying_key().to_hex()).unwrap(),
+            key.verifying_key()
+        );
+        assert!(SigningKey::from_hex("not hex").is_err());
+        assert!(!format!("{:?}", key).contains(&key.to_hex()));
+    }
+}
@@ -1,6 +1,7 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
 
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Snapshot data structures for galdi.
@@ -8,7 +9,7 @@ use serde::{Deserialize, Serialize};
 /// This module defines the data structures used to represent filesystem snapshots,
 /// including files, directories, and symlinks, along with their metadata.
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Snapshot {
     #[serde(rename = "$plumbah")]
     pub plumbah: PlumbahObject,
@@ -18,11 +19,56 @@ pub struct Snapshot {
 
     pub root: PathBuf,
     pub checksum_algorithm: ChecksumAlgorithm,
+    /// Further algorithms computed alongside `checksum_algorithm`, see `SnapshotEntry::checksums`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_checksums: Vec<ChecksumAlgorithm>,
+    /// Identity of the key used for keyed checksums (never the key itself).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum_key_id: Option<String>,
+    /// Bytes per sample of sampled checksums, present when any are recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_len: Option<u64>,
     pub count: usize,
     pub entries: Vec<SnapshotEntry>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Snapshot {
+    /// Refuse to compare checksums keyed with different keys, keyed
+    /// checksums with unkeyed ones, or checksums sampled with different
+    /// sample lengths: they would all differ without meaning.
+    pub fn check_comparable(&self, other: &Snapshot) -> Result<(), ScanError> {
+        check_key_ids(
+            self.checksum_key_id.as_deref(),
+            other.checksum_key_id.as_deref(),
+        )?;
+        match (self.sample_len, other.sample_len) {
+            (Some(ours), Some(theirs)) if ours != theirs => Err(ScanError::Operation(format!(
+                "cannot compare checksums sampled {} bytes at a time with ones sampled {} bytes at a time",
+                ours, theirs
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Check that two sets of checksums were keyed with the same key (given by
+/// its id), or that neither was keyed.
+pub fn check_key_ids(ours: Option<&str>, theirs: Option<&str>) -> Result<(), ScanError> {
+    match (ours, theirs) {
+        (None, None) => Ok(()),
+        (Some(ours), Some(theirs)) if ours == theirs => Ok(()),
+        (Some(ours), Some(theirs)) => Err(ScanError::KeyMismatch(format!(
+            "checksums were keyed with different keys ({} and {})",
+            ours, theirs
+        ))),
+        (Some(id), None) | (None, Some(id)) => Err(ScanError::KeyMismatch(format!(
+            "cannot compare checksums keyed with key {} to unkeyed checksums",
+            id
+        ))),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SnapshotEntry {
     pub path: PathBuf, // Relative to root
     #[serde(rename = "type")]
@@ -31,12 +77,96 @@ pub struct SnapshotEntry {
     /// for unix e.g ."0644" - posix file mode as octal string
     /// for windows e.g. "00000020" - win32 file attributes bitfield as hex string
     pub mode: Option<String>,
+    /// Numeric owner ids, where the source records them (unix scans, tar headers).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
     pub mtime: DateTime<Utc>,
     pub checksum: Option<String>, // "xxhash64:abc123"
-    pub target: Option<PathBuf>,  // For symlinks
+    /// Checksums for the snapshot's `extra_checksums` algorithms, from the same read.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub checksums: BTreeMap<ChecksumAlgorithm, String>,
+    /// Detected from the leading bytes of regular files while they are hashed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<ContentType>,
+    /// Build and linking metadata, for ELF executables and shared libraries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elf: Option<ElfInfo>,
+    pub target: Option<PathBuf>, // For symlinks
+}
+
+impl SnapshotEntry {
+    /// All checksums of this entry keyed by algorithm: `checksum` (under the
+    /// algorithm named by its prefix) together with `checksums`.
+    pub fn all_checksums(&self) -> BTreeMap<ChecksumAlgorithm, &str> {
+        let mut all: BTreeMap<_, _> = self
+            .checksums
+            .iter()
+            .map(|(algorithm, checksum)| (*algorithm, checksum.as_str()))
+            .collect();
+        if let Some(checksum) = &self.checksum
+            && let Some((algorithm, _)) = parse_checksum(checksum)
+        {
+            all.insert(algorithm, checksum.as_str());
+        }
+        all
+    }
+
+    /// Whether the content of two entries differs, compared on every checksum
+    /// algorithm both carry. Entries with no algorithm in common fall back to
+    /// comparing their primary checksums.
+    pub fn content_differs(&self, other: &SnapshotEntry) -> bool {
+        self.content_match(other) == ContentMatch::Different
+    }
+
+    /// How the content of two entries compares, like `content_differs`, but
+    /// telling matches that rest on sampled checksums only apart.
+    pub fn content_match(&self, other: &SnapshotEntry) -> ContentMatch {
+        let ours = self.all_checksums();
+        let theirs = other.all_checksums();
+        let common: Vec<_> = ours
+            .iter()
+            .filter_map(|(algorithm, ours)| {
+                theirs
+                    .get(algorithm)
+                    .map(|theirs| (algorithm, ours, theirs))
+            })
+            .collect();
+
+        let (differs, sampled_only) = if common.is_empty() {
+            let sampled = self
+                .checksum
+                .as_deref()
+                .and_then(parse_checksum)
+                .is_some_and(|(algorithm, _)| algorithm.is_sampled());
+            (self.checksum != other.checksum, sampled)
+        } else {
+            (
+                common.iter().any(|(_, ours, theirs)| ours != theirs),
+                common
+                    .iter()
+                    .all(|(algorithm, _, _)| algorithm.is_sampled()),
+            )
+        };
+        match (differs, sampled_only) {
+            (true, _) => ContentMatch::Different,
+            (false, true) => ContentMatch::ProbablySame,
+            (false, false) => ContentMatch::Same,
+        }
+    }
+}
+
+/// Outcome of comparing the content of two entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentMatch {
+    Same,
+    /// Only sampled checksums were compared, and they match.
+    ProbablySame,
+    Different,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Copy)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Copy, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum EntryType {
     /// For special files, not yet mapped specifically, like device nodes, FIFOs, sockets, etc.
@@ -58,18 +188,79 @@ pub enum EntryType {
  * 1. XXH3_64 - Fast non-cryptographic 64-bit hash (16 hex chars)
  * 2. Blake3 - Fast cryptographic 256-bit hash (64 hex chars)
  * 3. Sha256 - Cryptographic 256-bit hash (64 hex chars)
+ *
+ * The remaining algorithms exist for interoperability rather than preference:
+ * XXH128 for long-term content ids, Sha512/Sha1/Md5 for vendor manifests and
+ * Crc32c for cloud object-store checksums.
  */
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
 #[serde(rename_all = "lowercase")]
 pub enum ChecksumAlgorithm {
     XXH3_64,
     Sha256,
     Blake3,
+    /// XXH3 128-bit (32 hex chars)
+    XXH128,
+    /// SHA-512 (128 hex chars)
+    Sha512,
+    /// SHA-1 (40 hex chars) - legacy, not collision resistant
+    Sha1,
+    /// MD5 (32 hex chars) - legacy, not collision resistant
+    Md5,
+    /// CRC-32C (Castagnoli, 8 hex chars) - as used by cloud object stores
+    Crc32c,
+    /// BLAKE3 in keyed mode (64 hex chars) - needs a `ChecksumKey`
+    #[serde(rename = "blake3_keyed")]
+    Blake3Keyed,
+    /// HMAC-SHA256 (64 hex chars) - needs a `ChecksumKey`
+    #[serde(rename = "hmac_sha256")]
+    HmacSha256,
+    /// XXH3 64-bit over the size and the first, middle and last `sample_len`
+    /// bytes (16 hex chars) - a quick pre-screen, not a content hash. The
+    /// snapshot records its `sample_len`; `DEFAULT_SAMPLE_LEN` unless a scan
+    /// asks for another.
+    #[serde(rename = "xxh3_64s")]
+    XXH3_64Sampled,
 }
 
 use std::str::FromStr;
 
-use crate::PlumbahObject;
+use crate::{ContentType, ElfInfo, PlumbahObject, ScanError, parse_checksum};
+
+impl ChecksumAlgorithm {
+    /// The serialized name, also used as the prefix of checksum strings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::XXH3_64 => "xxh3_64",
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Blake3 => "blake3",
+            ChecksumAlgorithm::XXH128 => "xxh128",
+            ChecksumAlgorithm::Sha512 => "sha512",
+            ChecksumAlgorithm::Sha1 => "sha1",
+            ChecksumAlgorithm::Md5 => "md5",
+            ChecksumAlgorithm::Crc32c => "crc32c",
+            ChecksumAlgorithm::Blake3Keyed => "blake3_keyed",
+            ChecksumAlgorithm::HmacSha256 => "hmac_sha256",
+            ChecksumAlgorithm::XXH3_64Sampled => "xxh3_64s",
+        }
+    }
+
+    /// Whether checksums of this algorithm depend on a secret key.
+    pub fn is_keyed(&self) -> bool {
+        matches!(
+            self,
+            ChecksumAlgorithm::Blake3Keyed | ChecksumAlgorithm::HmacSha256
+        )
+    }
+
+    /// Whether checksums of this algorithm only cover samples of the content,
+    /// so that equal checksums mean the content is probably, not certainly, equal.
+    pub fn is_sampled(&self) -> bool {
+        *self == ChecksumAlgorithm::XXH3_64Sampled
+    }
+}
 
 impl FromStr for ChecksumAlgorithm {
     type Err = String;
@@ -79,6 +270,14 @@ impl FromStr for ChecksumAlgorithm {
             "xxh3_64" => Ok(ChecksumAlgorithm::XXH3_64),
             "sha256" => Ok(ChecksumAlgorithm::Sha256),
             "blake3" => Ok(ChecksumAlgorithm::Blake3),
+            "xxh128" => Ok(ChecksumAlgorithm::XXH128),
+            "sha512" => Ok(ChecksumAlgorithm::Sha512),
+            "sha1" => Ok(ChecksumAlgorithm::Sha1),
+            "md5" => Ok(ChecksumAlgorithm::Md5),
+            "crc32c" => Ok(ChecksumAlgorithm::Crc32c),
+            "blake3_keyed" => Ok(ChecksumAlgorithm::Blake3Keyed),
+            "hmac_sha256" => Ok(ChecksumAlgorithm::HmacSha256),
+            "xxh3_64s" => Ok(ChecksumAlgorithm::XXH3_64Sampled),
             _ => Err(format!("Invalid checksum algorithm: {}", s)),
         }
     }
@@ -88,7 +287,6 @@ impl FromStr for ChecksumAlgorithm {
 mod tests {
     use super::*;
     use crate::{Meta, Status};
-    use serde_json;
 
     #[test]
     fn test_entry_type_serializes_lowercase() {
@@ -161,9 +359,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_checksum_algorithm_from_str_matches_serde_name() {
+        for algorithm in [
+            ChecksumAlgorithm::XXH3_64,
+            ChecksumAlgorithm::Sha256,
+            ChecksumAlgorithm::Blake3,
+            ChecksumAlgorithm::XXH128,
+            ChecksumAlgorithm::Sha512,
+            ChecksumAlgorithm::Sha1,
+            ChecksumAlgorithm::Md5,
+            ChecksumAlgorithm::Crc32c,
+        ] {
+            let name = serde_json::to_value(algorithm).unwrap();
+            let name = name.as_str().unwrap();
+            assert_eq!(ChecksumAlgorithm::from_str(name).unwrap(), algorithm);
+        }
+    }
+
     #[test]
     fn test_checksum_algorithm_invalid_returns_err() {
-        let result = ChecksumAlgorithm::from_str("md5");
+        let result = ChecksumAlgorithm::from_str("md4");
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Invalid checksum algorithm"));
     }
@@ -186,6 +402,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 0,
             entries: vec![],
         };
@@ -206,8 +425,13 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(1024),
             mode: Some("644".to_string()),
+            uid: None,
+            gid: None,
             mtime: Utc::now(),
             checksum: Some("xxh3_64:abc123".to_string()),
+            checksums: Default::default(),
+            content_type: None,
+            elf: None,
             target: None,
         };
 
@@ -238,8 +462,13 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(512),
             mode: Some("644".to_string()),
+            uid: None,
+            gid: None,
             mtime: Utc::now(),
             checksum: Some("xxh3_64:abc123".to_string()),
+            checksums: Default::default(),
+            content_type: None,
+            elf: None,
             target: None,
         };
 
@@ -248,6 +477,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 1,
             entries: vec![file_entry.clone()],
         };
@@ -275,8 +507,13 @@ mod tests {
             entry_type: EntryType::Directory,
             size: Some(0),
             mode: Some("755".to_string()),
+            uid: None,
+            gid: None,
             mtime: Utc::now(),
             checksum: None,
+            checksums: Default::default(),
+            content_type: None,
+            elf: None,
             target: None,
         };
 
@@ -285,6 +522,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 1,
             entries: vec![dir_entry.clone()],
         };
@@ -311,8 +551,13 @@ mod tests {
             entry_type: EntryType::Symlink,
             size: Some(0),
             mode: Some("777".to_string()),
+            uid: None,
+            gid: None,
             mtime: Utc::now(),
             checksum: None,
+            checksums: Default::default(),
+            content_type: None,
+            elf: None,
             target: Some(PathBuf::from("target.txt")),
         };
 
@@ -321,6 +566,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 1,
             entries: vec![symlink_entry.clone()],
         };
@@ -350,8 +598,13 @@ mod tests {
             entry_type: EntryType::File,
             size: Some(100),
             mode: Some("644".to_string()),
+            uid: None,
+            gid: None,
             mtime: Utc::now(),
             checksum: Some("xxh3_64:123".to_string()),
+            checksums: Default::default(),
+            content_type: None,
+            elf: None,
             target: None,
         };
 
@@ -360,6 +613,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/absolute/root"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 1,
             entries: vec![entry],
         };
@@ -386,6 +642,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 0,
             entries: vec![],
         };
@@ -429,6 +688,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 0,
             entries: vec![],
         };
@@ -459,6 +721,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 0,
             entries: vec![],
         };
@@ -489,6 +754,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 0,
             entries: vec![],
         };
@@ -524,8 +792,13 @@ mod tests {
                 entry_type: EntryType::File,
                 size: Some(100),
                 mode: Some("644".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: Some("xxh3_64:abc".to_string()),
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             },
             SnapshotEntry {
@@ -533,8 +806,13 @@ mod tests {
                 entry_type: EntryType::Directory,
                 size: Some(0),
                 mode: Some("755".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: None,
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             },
         ];
@@ -544,6 +822,9 @@ mod tests {
             version: "1.0".to_string(),
             root: PathBuf::from("/test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 2,
             entries: entries.clone(),
         };
@@ -0,0 +1,447 @@
+//! Export of snapshots and diff reports into a SQLite database, so that they
+//! can be queried with SQL.
+//!
+//! Each exported document becomes a row of `documents`, with its `$plumbah`
+//! metadata and errors. Snapshots add a row to `snapshots` and their entries
+//! to `entries`; diff reports add a row to `diffs` and their differences to
+//! `differences`, whose source and target sides are rows of `entries` as
+//! well. Documents are appended, so a database accumulates snapshots and
+//! reports over time, each under its own `documents.id`.
+
+use std::path::Path;
+
+use rusqlite::{Connection, Transaction, params};
+use serde::Serialize;
+
+use crate::diff::DiffResult;
+use crate::error::ScanError;
+use crate::plumbah::PlumbahObject;
+use crate::snapshot::{Snapshot, SnapshotEntry};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS documents (
+    id INTEGER PRIMARY KEY,
+    kind TEXT NOT NULL,
+    name TEXT,
+    status TEXT NOT NULL,
+    tool TEXT,
+    tool_version TEXT,
+    timestamp TEXT,
+    execution_time_ms INTEGER,
+    meta TEXT,
+    exported_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS errors (
+    document_id INTEGER NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    code TEXT NOT NULL,
+    message TEXT NOT NULL,
+    path TEXT,
+    recoverable INTEGER NOT NULL,
+    context TEXT
+);
+CREATE TABLE IF NOT EXISTS snapshots (
+    document_id INTEGER PRIMARY KEY REFERENCES documents(id) ON DELETE CASCADE,
+    version TEXT NOT NULL,
+    root TEXT NOT NULL,
+    checksum_algorithm TEXT NOT NULL,
+    extra_checksums TEXT,
+    checksum_key_id TEXT,
+    count INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS entries (
+    id INTEGER PRIMARY KEY,
+    document_id INTEGER NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    type TEXT NOT NULL,
+    size INTEGER,
+    mode TEXT,
+    uid INTEGER,
+    gid INTEGER,
+    mtime TEXT NOT NULL,
+    checksum TEXT,
+    content_type TEXT,
+    elf TEXT,
+    target TEXT
+);
+CREATE TABLE IF NOT EXISTS entry_checksums (
+    entry_id INTEGER NOT NULL REFERENCES entries(id) ON DELETE CASCADE,
+    algorithm TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    PRIMARY KEY (entry_id, algorithm)
+);
+CREATE TABLE IF NOT EXISTS diffs (
+    document_id INTEGER PRIMARY KEY REFERENCES documents(id) ON DELETE CASCADE,
+    identical INTEGER NOT NULL,
+    added INTEGER NOT NULL,
+    removed INTEGER NOT NULL,
+    modified INTEGER NOT NULL,
+    unchanged INTEGER NOT NULL,
+    probably_unchanged INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS differences (
+    id INTEGER PRIMARY KEY,
+    document_id INTEGER NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    change_type TEXT NOT NULL,
+    source_entry_id INTEGER REFERENCES entries(id),
+    target_entry_id INTEGER REFERENCES entries(id),
+    error TEXT
+);
+CREATE TABLE IF NOT EXISTS difference_changes (
+    difference_id INTEGER NOT NULL REFERENCES differences(id) ON DELETE CASCADE,
+    attribute TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS errors_document ON errors(document_id);
+CREATE INDEX IF NOT EXISTS entries_document_path ON entries(document_id, path);
+CREATE INDEX IF NOT EXISTS entries_path ON entries(path);
+CREATE INDEX IF NOT EXISTS entries_checksum ON entries(checksum);
+CREATE INDEX IF NOT EXISTS entry_checksums_checksum ON entry_checksums(checksum);
+CREATE INDEX IF NOT EXISTS differences_document_path ON differences(document_id, path);
+CREATE INDEX IF NOT EXISTS differences_path ON differences(path);
+CREATE INDEX IF NOT EXISTS difference_changes_difference ON difference_changes(difference_id);
+";
+
+impl From<rusqlite::Error> for ScanError {
+    fn from(err: rusqlite::Error) -> Self {
+        ScanError::Database(err.to_string())
+    }
+}
+
+/// A SQLite database that snapshots and diff reports are exported to.
+pub struct SqliteExport {
+    connection: Connection,
+}
+
+impl SqliteExport {
+    /// Open the database at `path`, creating it and its tables as needed.
+    pub fn open(path: &Path) -> Result<Self, ScanError> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteExport { connection })
+    }
+
+    /// Append a snapshot and its entries, returning its `documents.id`.
+    /// `name` tells documents apart in queries, e.g. a release or a host.
+    pub fn add_snapshot(
+        &mut self,
+        snapshot: &Snapshot,
+        name: Option<&str>,
+    ) -> Result<i64, ScanError> {
+        let transaction = self.connection.transaction()?;
+        let id = insert_document(&transaction, "snapshot", name, &snapshot.plumbah)?;
+        transaction.execute(
+            "INSERT INTO snapshots (document_id, version, root, checksum_algorithm,
+                extra_checksums, checksum_key_id, count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                id,
+                snapshot.version,
+                snapshot.root.to_string_lossy(),
+                snapshot.checksum_algorithm.name(),
+                (!snapshot.extra_checksums.is_empty()).then(|| {
+                    let names: Vec<_> = snapshot.extra_checksums.iter().map(|a| a.name()).collect();
+                    names.join(",")
+                }),
+                snapshot.checksum_key_id,
+                snapshot.count as i64,
+            ],
+        )?;
+        for entry in &snapshot.entries {
+            insert_entry(&transaction, id, entry)?;
+        }
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    /// Append a diff report and its differences, returning its `documents.id`.
+    pub fn add_diff(&mut self, diff: &DiffResult, name: Option<&str>) -> Result<i64, ScanError> {
+        let transaction = self.connection.transaction()?;
+        let id = insert_document(&transaction, "diff", name, &diff.plumbah)?;
+        let summary = &diff.summary;
+        transaction.execute(
+            "INSERT INTO diffs (document_id, identical, added, removed, modified, unchanged,
+                probably_unchanged)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                id,
+                diff.identical,
+                summary.added as i64,
+                summary.removed as i64,
+                summary.modified as i64,
+                summary.unchanged as i64,
+                summary.probably_unchanged as i64,
+            ],
+        )?;
+        for difference in &diff.differences {
+            let source = difference
+                .source
+                .as_ref()
+                .map(|entry| insert_entry(&transaction, id, entry))
+                .transpose()?;
+            let target = difference
+                .target
+                .as_ref()
+                .map(|entry| insert_entry(&transaction, id, entry))
+                .transpose()?;
+            transaction
+                .prepare_cached(
+                    "INSERT INTO differences (document_id, path, change_type, source_entry_id,
+                    target_entry_id, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )?
+                .execute(params![
+                    id,
+                    difference.path.to_string_lossy(),
+                    label(&difference.change_type),
+                    source,
+                    target,
+                    difference.error,
+                ])?;
+            let difference_id = transaction.last_insert_rowid();
+            for change in &difference.changes {
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO difference_changes (difference_id, attribute) VALUES (?1, ?2)",
+                    )?
+                    .execute(params![difference_id, label(change)])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(id)
+    }
+}
+
+/// Insert the row of `documents` and the errors of a document.
+fn insert_document(
+    transaction: &Transaction,
+    kind: &str,
+    name: Option<&str>,
+    plumbah: &PlumbahObject,
+) -> Result<i64, ScanError> {
+    let meta = plumbah.meta.as_ref();
+    transaction.execute(
+        "INSERT INTO documents (kind, name, status, tool, tool_version, timestamp,
+            execution_time_ms, meta, exported_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            kind,
+            name,
+            label(&plumbah.status),
+            meta.map(|meta| &meta.tool),
+            meta.map(|meta| &meta.tool_version),
+            meta.map(|meta| meta.timestamp.to_rfc3339()),
+            meta.map(|meta| meta.execution_time_ms as i64),
+            meta.map(json).transpose()?,
+            chrono::Utc::now().to_rfc3339(),
+        ],
+    )?;
+    let id = transaction.last_insert_rowid();
+
+    for error in plumbah.errors.iter().flatten() {
+        transaction.execute(
+            "INSERT INTO errors (document_id, code, message, path, recoverable, context)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                error.code,
+                error.message,
+                error.path.as_ref().map(|path| path.to_string_lossy()),
+                error.recoverable,
+                error.context.as_ref().map(json).transpose()?,
+            ],
+        )?;
+    }
+    Ok(id)
+}
+
+/// Insert an entry and its extra checksums, returning its `entries.id`.
+fn insert_entry(
+    transaction: &Transaction,
+    document_id: i64,
+    entry: &SnapshotEntry,
+) -> Result<i64, ScanError> {
+    transaction
+        .prepare_cached(
+            "INSERT INTO entries (document_id, path, type, size, mode, uid, gid, mtime, checksum,
+            content_type, elf, target)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        )?
+        .execute(params![
+            document_id,
+            entry.path.to_string_lossy(),
+            label(&entry.entry_type),
+            entry.size.map(|size| size as i64),
+            entry.mode,
+            entry.uid,
+            entry.gid,
+            entry.mtime.to_rfc3339(),
+            entry.checksum,
+            entry.content_type.as_ref().map(label),
+            entry.elf.as_ref().map(json).transpose()?,
+            entry.target.as_ref().map(|target| target.to_string_lossy()),
+        ])?;
+    let id = transaction.last_insert_rowid();
+    for (algorithm, checksum) in &entry.checksums {
+        transaction
+            .prepare_cached(
+                "INSERT INTO entry_checksums (entry_id, algorithm, checksum) VALUES (?1, ?2, ?3)",
+            )?
+            .execute(params![id, algorithm.name(), checksum])?;
+    }
+    Ok(id)
+}
+
+/// The name an enum serializes to, such as `file` or `modified`.
+fn label<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        other => other.map(|value| value.to_string()).unwrap_or_default(),
+    }
+}
+
+/// Nested structures are stored as JSON text, for SQLite's JSON functions.
+fn json<T: Serialize>(value: &T) -> Result<String, ScanError> {
+    serde_json::to_string(value).map_err(|err| ScanError::Database(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::diff::{AttributeChange, ChangeType, DiffSummary, Difference};
+    use crate::plumbah::{Meta, Status};
+    use crate::snapshot::ChecksumAlgorithm;
+
+    fn plumbah(tool: &str) -> PlumbahObject {
+        PlumbahObject::new(
+            Status::Ok,
+            Meta::new(tool, "0.0.0", true, false, true, true, 0, Utc::now()),
+        )
+    }
+
+    fn entry(path: &str, size: u64, checksum: &str) -> SnapshotEntry {
+        SnapshotEntry {
+            size: Some(size),
+            checksums: [(ChecksumAlgorithm::Sha256, format!("sha256:{}", size))].into(),
+            ..SnapshotEntry::test_file(path, checksum)
+        }
+    }
+
+    fn snapshot(entries: Vec<SnapshotEntry>) -> Snapshot {
+        Snapshot {
+            root: PathBuf::from("/srv"),
+            extra_checksums: vec![ChecksumAlgorithm::Sha256],
+            ..Snapshot::test_of(entries)
+        }
+    }
+
+    #[test]
+    fn test_snapshots_are_appended() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("galdi.db");
+
+        let first = snapshot(vec![entry("a.txt", 1, "xxh3_64:01")]);
+        let second = snapshot(vec![
+            entry("a.txt", 1, "xxh3_64:01"),
+            entry("b.bin", 100, "xxh3_64:02"),
+        ]);
+        let id1 = SqliteExport::open(&path)
+            .unwrap()
+            .add_snapshot(&first, Some("v1"))
+            .unwrap();
+        // Reopening keeps what was exported before
+        let id2 = SqliteExport::open(&path)
+            .unwrap()
+            .add_snapshot(&second, Some("v2"))
+            .unwrap();
+        assert_ne!(id1, id2);
+
+        let connection = Connection::open(&path).unwrap();
+        let count = |sql: &str| -> i64 { connection.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM documents"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM entries"), 3);
+        assert_eq!(count("SELECT COUNT(*) FROM entry_checksums"), 3);
+        let largest: (String, String) = connection
+            .query_row(
+                "SELECT d.name, e.path FROM entries e JOIN documents d ON d.id = e.document_id
+                 WHERE e.checksum = 'xxh3_64:02'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(largest, ("v2".to_string(), "b.bin".to_string()));
+        let algorithm: String = connection
+            .query_row(
+                "SELECT checksum_algorithm FROM snapshots LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(algorithm, "xxh3_64");
+    }
+
+    #[test]
+    fn test_diff_differences_reference_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("galdi.db");
+        let diff = DiffResult {
+            plumbah: plumbah("galdi_diff"),
+            identical: false,
+            probable: false,
+            summary: DiffSummary {
+                added: 1,
+                modified: 1,
+                ..Default::default()
+            },
+            differences: vec![
+                Difference {
+                    path: PathBuf::from("new.bin"),
+                    change_type: ChangeType::Added,
+                    changes: vec![],
+                    source: None,
+                    target: Some(entry("new.bin", 500, "xxh3_64:03")),
+                    error: None,
+                },
+                Difference {
+                    path: PathBuf::from("a.txt"),
+                    change_type: ChangeType::Modified,
+                    changes: vec![AttributeChange::Content, AttributeChange::Size],
+                    source: Some(entry("a.txt", 1, "xxh3_64:01")),
+                    target: Some(entry("a.txt", 2, "xxh3_64:04")),
+                    error: None,
+                },
+            ],
+        };
+        SqliteExport::open(&path)
+            .unwrap()
+            .add_diff(&diff, None)
+            .unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let added: (String, i64) = connection
+            .query_row(
+                "SELECT d.path, t.size FROM differences d JOIN entries t ON t.id = d.target_entry_id
+                 WHERE d.change_type = 'added'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(added, ("new.bin".to_string(), 500));
+        let changes: String = connection
+            .query_row(
+                "SELECT group_concat(attribute, ',') FROM difference_changes",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(changes, "content,size");
+        let kind: String = connection
+            .query_row("SELECT kind FROM documents", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kind, "diff");
+    }
+}
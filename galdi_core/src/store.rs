@@ -0,0 +1,793 @@
+//! A local, content-addressed repository of snapshots.
+//!
+//! Entries are split into chunks at boundaries chosen by the hash of their
+//! paths, so that an entry that changes, appears or disappears only changes
+//! the chunk it falls in. Chunks are stored once under `objects/`, named by
+//! the BLAKE3 hash of their content, and shared by every snapshot that holds
+//! them. A snapshot itself is a record under `snapshots/` with its top-level
+//! fields and the list of its chunks, named by the hash of the whole
+//! snapshot. Tags are files under `tags/` holding the id they point to.
+//!
+//! Snapshots are referred to by tag, by a unique prefix of their id, or as
+//! `latest`, the last one added. References may start with `@`, as they do
+//! where they stand in for a file, like `galdi diff @baseline @latest`.
+//!
+//! Chunks are checked against their ids whenever they are read. Adding a
+//! snapshot holds the store's `lock` file shared and collecting garbage holds
+//! it exclusively, so that no chunk an `add` in progress is about to refer to
+//! is deleted under it.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::encoding::{Compression, Encoding, read_encoded, write_encoded};
+use crate::error::ScanError;
+use crate::migration::upgraded_snapshot;
+use crate::signature::canonical_json;
+use crate::snapshot::Snapshot;
+
+/// Environment variable naming the store when `--store` is not given.
+pub const STORE_ENV: &str = "GALDI_STORE";
+
+/// Store used when neither `--store` nor `GALDI_STORE` name one.
+pub const DEFAULT_STORE: &str = ".galdi";
+
+/// Chunks end after an entry whose path hashes to a first byte of zero, so
+/// they hold 256 entries on average...
+const CHUNK_BOUNDARY: u8 = 0;
+/// ... and never more than this.
+const MAX_CHUNK_ENTRIES: usize = 4096;
+
+/// Shortest id prefix accepted as a reference.
+const MIN_PREFIX_LEN: usize = 4;
+
+/// File under the store root that `add` locks shared and garbage collection
+/// exclusively.
+const LOCK_FILE: &str = "lock";
+
+/// A snapshot store rooted at a directory, created on the first `add`.
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+/// What the store records about a snapshot, as listed by `SnapshotStore::list`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSnapshot {
+    pub id: String,
+    pub added_at: DateTime<Utc>,
+    /// When the snapshot was taken, from its `$plumbah.meta.timestamp`.
+    pub timestamp: Option<DateTime<Utc>>,
+    pub root: PathBuf,
+    pub count: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Outcome of `SnapshotStore::add`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AddedSnapshot {
+    #[serde(flatten)]
+    pub snapshot: StoredSnapshot,
+    /// Chunks of entries the snapshot is made of...
+    pub chunks: usize,
+    /// ... and how many of them the store did not hold yet.
+    pub new_chunks: usize,
+    /// Whether the very same snapshot was stored already.
+    pub already_stored: bool,
+}
+
+/// Which snapshots `SnapshotStore::prune` keeps, besides tagged ones and the
+/// latest, which are always kept.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Keep the last N snapshots added.
+    pub keep_last: Option<usize>,
+    /// Keep snapshots added within this long before now.
+    pub keep_within: Option<Duration>,
+}
+
+/// Outcome of `SnapshotStore::prune`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PruneReport {
+    pub removed: Vec<StoredSnapshot>,
+    pub kept: usize,
+    /// Chunks no snapshot referred to anymore.
+    pub objects_removed: usize,
+}
+
+/// The file of a chunk under `objects/`.
+#[derive(Serialize, Deserialize)]
+struct Chunk {
+    entries: Vec<Value>,
+}
+
+/// The file of a stored snapshot under `snapshots/`.
+#[derive(Serialize, Deserialize)]
+struct Record {
+    id: String,
+    added_at: DateTime<Utc>,
+    /// The snapshot without its entries.
+    header: Value,
+    /// Ids of the chunks holding the entries, in order.
+    chunks: Vec<String>,
+}
+
+impl SnapshotStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        SnapshotStore { root: root.into() }
+    }
+
+    /// The store named by `path`, `GALDI_STORE` or else `.galdi`.
+    pub fn locate(path: Option<&Path>) -> Self {
+        match (path, std::env::var_os(STORE_ENV)) {
+            (Some(path), _) => SnapshotStore::new(path),
+            (None, Some(path)) => SnapshotStore::new(path),
+            (None, None) => SnapshotStore::new(DEFAULT_STORE),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether `argument` is a store reference (`@name`) rather than a path.
+    pub fn is_reference(argument: &Path) -> bool {
+        argument.to_str().is_some_and(|s| s.starts_with('@'))
+    }
+
+    /// Store a snapshot, sharing the chunks of entries it has in common
+    /// with the snapshots stored before it.
+    pub fn add(&self, snapshot: &Snapshot) -> Result<AddedSnapshot, ScanError> {
+        let document = serde_json::to_value(snapshot).map_err(store_error)?;
+        self.add_document(document)
+    }
+
+    /// Store a snapshot document as it is, unknown fields and signature included.
+    pub fn add_document(&self, mut document: Value) -> Result<AddedSnapshot, ScanError> {
+        let _lock = self.lock(false)?;
+        let id = blake3::hash(&canonical_json(&document))
+            .to_hex()
+            .to_string();
+        let entries = match document.as_object_mut().and_then(|o| o.remove("entries")) {
+            Some(Value::Array(entries)) => entries,
+            _ => return Err(ScanError::Store("not a snapshot: no entries".to_string())),
+        };
+
+        let chunks = chunk(entries);
+        let chunk_count = chunks.len();
+        if let Ok(record) = self.record(&id) {
+            return Ok(AddedSnapshot {
+                snapshot: self.describe(&record)?,
+                chunks: chunk_count,
+                new_chunks: 0,
+                already_stored: true,
+            });
+        }
+
+        let mut ids = Vec::with_capacity(chunk_count);
+        let mut new_chunks = 0;
+        for entries in chunks {
+            let chunk = Chunk { entries };
+            let chunk_id = chunk.id();
+            let path = self.object_path(&chunk_id)?;
+            if !path.exists() {
+                write_atomically(&path, |file| {
+                    write_encoded(&chunk, file, Encoding::Json, Compression::Zstd)
+                })?;
+                new_chunks += 1;
+            }
+            ids.push(chunk_id);
+        }
+
+        let record = Record {
+            id: id.clone(),
+            added_at: Utc::now(),
+            header: document,
+            chunks: ids,
+        };
+        write_atomically(&self.record_path(&id)?, |file| {
+            write_encoded(&record, file, Encoding::Json, Compression::None)
+        })?;
+
+        Ok(AddedSnapshot {
+            snapshot: self.describe(&record)?,
+            chunks: chunk_count,
+            new_chunks,
+            already_stored: false,
+        })
+    }
+
+    /// All stored snapshots, oldest first.
+    pub fn list(&self) -> Result<Vec<StoredSnapshot>, ScanError> {
+        self.records()?
+            .iter()
+            .map(|record| self.describe(record))
+            .collect()
+    }
+
+    /// The full id of the snapshot `reference` refers to.
+    pub fn resolve(&self, reference: &str) -> Result<String, ScanError> {
+        let name = reference.strip_prefix('@').unwrap_or(reference);
+        if name == "latest" {
+            return self
+                .records()?
+                .pop()
+                .map(|record| record.id)
+                .ok_or_else(|| ScanError::Store(format!("{}: the store is empty", reference)));
+        }
+        if is_tag_name(name)
+            && let Ok(id) = fs::read_to_string(self.tag_path(name))
+        {
+            return Ok(id.trim().to_string());
+        }
+
+        let matches: Vec<String> =
+            if name.len() >= MIN_PREFIX_LEN && name.chars().all(|c| c.is_ascii_hexdigit()) {
+                self.ids()?
+                    .into_iter()
+                    .filter(|id| id.starts_with(name))
+                    .collect()
+            } else {
+                vec![]
+            };
+        match matches.as_slice() {
+            [id] => Ok(id.clone()),
+            [] => Err(ScanError::Store(format!(
+                "{}: no such tag or snapshot in {}",
+                reference,
+                self.root.display()
+            ))),
+            _ => Err(ScanError::Store(format!(
+                "{}: ambiguous, {} snapshots start with it",
+                reference,
+                matches.len()
+            ))),
+        }
+    }
+
+    /// The snapshot document `reference` refers to, reassembled from its
+    /// chunks exactly as it was added. A chunk whose content does not hash to
+    /// its id is reported as corrupt.
+    pub fn load_document(&self, reference: &str) -> Result<Value, ScanError> {
+        let mut record = self.record(&self.resolve(reference)?)?;
+        let mut entries = Vec::new();
+        for chunk_id in &record.chunks {
+            let path = self.object_path(chunk_id)?;
+            let file = File::open(&path).map_err(|err| ScanError::from_io(err, &path))?;
+            let chunk: Chunk = read_encoded(file)?;
+            let actual = chunk.id();
+            if actual != *chunk_id {
+                return Err(ScanError::Store(format!(
+                    "chunk {} is corrupt: its content hashes to {}",
+                    chunk_id, actual
+                )));
+            }
+            entries.extend(chunk.entries);
+        }
+        if let Some(header) = record.header.as_object_mut() {
+            header.insert("entries".to_string(), Value::Array(entries));
+        }
+        Ok(record.header)
+    }
+
+    /// The snapshot `reference` refers to, upgraded to the current format version.
+    pub fn load(&self, reference: &str) -> Result<Snapshot, ScanError> {
+        upgraded_snapshot(self.load_document(reference)?)
+    }
+
+    /// Point tag `name` at the snapshot `reference` refers to, moving it if
+    /// it pointed elsewhere. Returns the snapshot's id.
+    pub fn tag(&self, reference: &str, name: &str) -> Result<String, ScanError> {
+        if !is_tag_name(name) {
+            return Err(invalid_tag_name(name));
+        }
+        let id = self.resolve(reference)?;
+        write_atomically(&self.tag_path(name), |mut file| {
+            io::Write::write_all(&mut file, format!("{}\n", id).as_bytes())
+        })?;
+        Ok(id)
+    }
+
+    /// Remove tag `name`, leaving the snapshot it pointed to.
+    pub fn untag(&self, name: &str) -> Result<(), ScanError> {
+        let name = name.strip_prefix('@').unwrap_or(name);
+        if !is_tag_name(name) {
+            return Err(invalid_tag_name(name));
+        }
+        let path = self.tag_path(name);
+        fs::remove_file(&path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => ScanError::Store(format!("{}: no such tag", name)),
+            _ => ScanError::Io(err),
+        })
+    }
+
+    /// Remove the snapshot `reference` refers to, its tags, and the chunks
+    /// no other snapshot shares.
+    pub fn remove(&self, reference: &str) -> Result<StoredSnapshot, ScanError> {
+        let record = self.record(&self.resolve(reference)?)?;
+        let removed = self.describe(&record)?;
+        let _lock = self.lock(true)?;
+        self.delete(&removed)?;
+        self.sweep()?;
+        Ok(removed)
+    }
+
+    /// Remove the snapshots `policy` does not keep, and the chunks left
+    /// unused. With `dry_run`, only report what would be removed.
+    pub fn prune(&self, policy: &RetentionPolicy, dry_run: bool) -> Result<PruneReport, ScanError> {
+        let snapshots = self.list()?;
+        let now = Utc::now();
+        let last = snapshots
+            .len()
+            .saturating_sub(policy.keep_last.unwrap_or(0).max(1));
+
+        let mut report = PruneReport::default();
+        for (position, snapshot) in snapshots.into_iter().enumerate() {
+            let keep = !snapshot.tags.is_empty()
+                || position >= last
+                || policy
+                    .keep_within
+                    .is_some_and(|within| now - snapshot.added_at <= within);
+            if keep {
+                report.kept += 1;
+            } else {
+                report.removed.push(snapshot);
+            }
+        }
+
+        if !dry_run {
+            let _lock = self.lock(true)?;
+            for snapshot in &report.removed {
+                self.delete(snapshot)?;
+            }
+            report.objects_removed = self.sweep()?;
+        }
+        Ok(report)
+    }
+
+    /// Delete the chunks no snapshot refers to, returning how many there were.
+    /// Waits for snapshots being added to be done.
+    pub fn collect_garbage(&self) -> Result<usize, ScanError> {
+        if !self.root.exists() {
+            return Ok(0);
+        }
+        let _lock = self.lock(true)?;
+        self.sweep()
+    }
+
+    /// `collect_garbage`, for callers already holding the lock exclusively.
+    fn sweep(&self) -> Result<usize, ScanError> {
+        let used: HashSet<String> = self
+            .records()?
+            .into_iter()
+            .flat_map(|record| record.chunks)
+            .collect();
+
+        let mut removed = 0;
+        for dir in read_dir(&self.root.join("objects"))? {
+            for object in read_dir(&dir)? {
+                let prefix = dir.file_name().unwrap_or_default().to_string_lossy();
+                let name = object.file_name().unwrap_or_default().to_string_lossy();
+                let id = format!("{}{}", prefix, name.trim_end_matches(".json.zst"));
+                if !used.contains(&id) {
+                    fs::remove_file(&object)?;
+                    removed += 1;
+                }
+            }
+            // Only succeeds once the directory is empty
+            let _ = fs::remove_dir(&dir);
+        }
+        Ok(removed)
+    }
+
+    /// Delete the record of `snapshot` and its tags, leaving its chunks.
+    fn delete(&self, snapshot: &StoredSnapshot) -> Result<(), ScanError> {
+        for tag in &snapshot.tags {
+            fs::remove_file(self.tag_path(tag))?;
+        }
+        fs::remove_file(self.record_path(&snapshot.id)?)?;
+        Ok(())
+    }
+
+    /// Lock the store, creating it if need be: shared to add snapshots,
+    /// exclusively to delete chunks. Released when the file is dropped.
+    fn lock(&self, exclusive: bool) -> Result<File, ScanError> {
+        fs::create_dir_all(&self.root)?;
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.root.join(LOCK_FILE))?;
+        if exclusive {
+            file.lock()?;
+        } else {
+            file.lock_shared()?;
+        }
+        Ok(file)
+    }
+
+    fn describe(&self, record: &Record) -> Result<StoredSnapshot, ScanError> {
+        let header = &record.header;
+        let mut tags = Vec::new();
+        for path in read_dir(&self.root.join("tags"))? {
+            if fs::read_to_string(&path)?.trim() == record.id {
+                tags.push(
+                    path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into(),
+                );
+            }
+        }
+        tags.sort();
+
+        Ok(StoredSnapshot {
+            id: record.id.clone(),
+            added_at: record.added_at,
+            timestamp: header
+                .pointer("/$plumbah/meta/timestamp")
+                .and_then(|timestamp| serde_json::from_value(timestamp.clone()).ok()),
+            root: header
+                .get("root")
+                .and_then(Value::as_str)
+                .map(PathBuf::from)
+                .unwrap_or_default(),
+            count: header.get("count").and_then(Value::as_u64).unwrap_or(0) as usize,
+            tags,
+        })
+    }
+
+    fn record(&self, id: &str) -> Result<Record, ScanError> {
+        let path = self.record_path(id)?;
+        let file = File::open(&path).map_err(|err| ScanError::from_io(err, &path))?;
+        Ok(read_encoded(file)?)
+    }
+
+    /// All records, oldest first.
+    fn records(&self) -> Result<Vec<Record>, ScanError> {
+        let mut records = self
+            .ids()?
+            .iter()
+            .map(|id| self.record(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        records.sort_by(|a, b| a.added_at.cmp(&b.added_at).then(a.id.cmp(&b.id)));
+        Ok(records)
+    }
+
+    fn ids(&self) -> Result<Vec<String>, ScanError> {
+        Ok(read_dir(&self.root.join("snapshots"))?
+            .into_iter()
+            .filter_map(|path| {
+                let name = path.file_name()?.to_str()?;
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .collect())
+    }
+
+    fn record_path(&self, id: &str) -> Result<PathBuf, ScanError> {
+        check_id(id)?;
+        Ok(self.root.join("snapshots").join(format!("{}.json", id)))
+    }
+
+    fn object_path(&self, id: &str) -> Result<PathBuf, ScanError> {
+        check_id(id)?;
+        let (prefix, rest) = id.split_at(2);
+        Ok(self
+            .root
+            .join("objects")
+            .join(prefix)
+            .join(format!("{}.json.zst", rest)))
+    }
+
+    fn tag_path(&self, name: &str) -> PathBuf {
+        self.root.join("tags").join(name)
+    }
+}
+
+impl Chunk {
+    /// The BLAKE3 hash of the chunk's canonical JSON, naming its file.
+    fn id(&self) -> String {
+        blake3::hash(&canonical_json(&serde_json::json!(self)))
+            .to_hex()
+            .to_string()
+    }
+}
+
+/// Refuse ids that are not BLAKE3 hashes in lowercase hex, as a tag file or
+/// record that was tampered with might hold, before they become paths.
+fn check_id(id: &str) -> Result<(), ScanError> {
+    if id.len() == 64 && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        Ok(())
+    } else {
+        Err(ScanError::Store(format!("invalid object id `{}`", id)))
+    }
+}
+
+fn is_tag_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "latest"
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn invalid_tag_name(name: &str) -> ScanError {
+    ScanError::Store(format!(
+        "invalid tag name `{}`: use letters, digits, `-`, `_` and `.`, other than `latest`",
+        name
+    ))
+}
+
+/// Split entries into chunks ending after entries whose path hashes to
+/// `CHUNK_BOUNDARY`. Boundaries depend on paths alone, so that chunks away
+/// from a change are the same from one snapshot to the next.
+fn chunk(entries: Vec<Value>) -> Vec<Vec<Value>> {
+    let mut chunks = Vec::new();
+    let mut current = Vec::new();
+    for entry in entries {
+        let path = entry
+            .get("path")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let boundary = blake3::hash(path.as_bytes()).as_bytes()[0] == CHUNK_BOUNDARY;
+        current.push(entry);
+        if boundary || current.len() == MAX_CHUNK_ENTRIES {
+            chunks.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Write a file through a temporary sibling, so that readers never see it
+/// half written.
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&File) -> io::Result<()>,
+) -> Result<(), ScanError> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let temporary = dir.join(format!(
+        ".{}.{}.tmp",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        std::process::id()
+    ));
+    write(&File::create(&temporary)?)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// The paths in `dir`, none if it does not exist.
+fn read_dir(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .filter(|path| {
+                !path.as_ref().is_ok_and(|path| {
+                    path.file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+                })
+            })
+            .collect(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err),
+    }
+}
+
+fn store_error(err: serde_json::Error) -> ScanError {
+    ScanError::Store(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::snapshot::SnapshotEntry;
+
+    fn snapshot(files: &[(&str, &str)]) -> Snapshot {
+        let entries = files
+            .iter()
+            .map(|(path, checksum)| {
+                SnapshotEntry::test_file(path, &format!("xxh3_64:{}", checksum))
+            })
+            .collect();
+        Snapshot {
+            root: PathBuf::from("/srv"),
+            ..Snapshot::test_of(entries)
+        }
+    }
+
+    fn many(count: usize, changed: Option<usize>) -> Snapshot {
+        let files: Vec<(String, String)> = (0..count)
+            .map(|i| {
+                let checksum = if Some(i) == changed { "ff" } else { "00" };
+                (format!("dir/file{:05}", i), checksum.to_string())
+            })
+            .collect();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(path, checksum)| (path.as_str(), checksum.as_str()))
+            .collect();
+        snapshot(&files)
+    }
+
+    #[test]
+    fn test_round_trip_and_dedup() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path());
+
+        let before = many(5000, None);
+        let after = many(5000, Some(2500));
+        let first = store.add(&before).unwrap();
+        let second = store.add(&after).unwrap();
+        assert!(first.chunks > 1);
+        assert_eq!(first.new_chunks, first.chunks);
+        // Only the chunk holding the changed entry is new
+        assert_eq!(second.new_chunks, 1);
+        assert!(store.add(&after).unwrap().already_stored);
+
+        let loaded = store.load(&first.snapshot.id).unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&before).unwrap()
+        );
+        assert_eq!(store.resolve("@latest").unwrap(), second.snapshot.id);
+        assert_eq!(
+            store.resolve(&second.snapshot.id[..8]).unwrap(),
+            second.snapshot.id
+        );
+        assert!(matches!(
+            store.resolve("@nothing"),
+            Err(ScanError::Store(_))
+        ));
+    }
+
+    #[test]
+    fn test_tags_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path());
+
+        let baseline = store.add(&snapshot(&[("a", "01")])).unwrap().snapshot.id;
+        let latest = store.add(&snapshot(&[("a", "02")])).unwrap().snapshot.id;
+        store.tag("@latest", "release").unwrap();
+        store.tag(&baseline, "baseline").unwrap();
+        assert_eq!(store.resolve("@baseline").unwrap(), baseline);
+        assert!(store.tag(&baseline, "latest").is_err());
+        assert!(store.tag(&baseline, "../escape").is_err());
+
+        // Untagging only ever removes tags
+        let record = format!("../snapshots/{}.json", baseline);
+        assert!(matches!(store.untag(&record), Err(ScanError::Store(_))));
+        assert!(store.untag("@latest").is_err());
+        assert_eq!(store.resolve(&baseline).unwrap(), baseline);
+        store.tag(&baseline, "scratch").unwrap();
+        store.untag("@scratch").unwrap();
+        assert!(store.resolve("@scratch").is_err());
+
+        // Tags move
+        store.tag(&baseline, "release").unwrap();
+        let listed = store.list().unwrap();
+        assert_eq!(listed[0].tags, vec!["baseline", "release"]);
+        assert!(listed[1].tags.is_empty());
+
+        store.remove("@baseline").unwrap();
+        assert!(store.resolve("@release").is_err());
+        assert_eq!(store.resolve("@latest").unwrap(), latest);
+        assert_eq!(store.list().unwrap().len(), 1);
+        // The chunk only the removed snapshot held is gone
+        assert_eq!(store.collect_garbage().unwrap(), 0);
+        assert_eq!(read_dir(&dir.path().join("objects")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_prune_keeps_tagged_and_latest() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path());
+
+        let ids: Vec<String> = (0..5)
+            .map(|i| {
+                let checksum = format!("{:02}", i);
+                store
+                    .add(&snapshot(&[("a", checksum.as_str())]))
+                    .unwrap()
+                    .snapshot
+                    .id
+            })
+            .collect();
+        store.tag(&ids[0], "baseline").unwrap();
+
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            keep_within: None,
+        };
+        let dry_run = store.prune(&policy, true).unwrap();
+        assert_eq!(dry_run.removed.len(), 2);
+        assert_eq!(store.list().unwrap().len(), 5);
+
+        let report = store.prune(&policy, false).unwrap();
+        assert_eq!(report.kept, 3);
+        assert_eq!(report.objects_removed, 2);
+        let kept: Vec<String> = store.list().unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(kept, vec![ids[0].clone(), ids[3].clone(), ids[4].clone()]);
+
+        // Everything was added just now
+        let policy = RetentionPolicy {
+            keep_last: None,
+            keep_within: Some(Duration::hours(1)),
+        };
+        assert!(store.prune(&policy, false).unwrap().removed.is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_chunk_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path());
+        let added = store.add(&snapshot(&[("a", "01")])).unwrap();
+        let record = store.record(&added.snapshot.id).unwrap();
+
+        // Swap the chunk for one with other content
+        let forged = Chunk {
+            entries: vec![serde_json::json!({ "path": "a" })],
+        };
+        let path = store.object_path(&record.chunks[0]).unwrap();
+        write_encoded(
+            &forged,
+            File::create(path).unwrap(),
+            Encoding::Json,
+            Compression::Zstd,
+        )
+        .unwrap();
+
+        let err = store.load("@latest").unwrap_err();
+        assert!(matches!(err, ScanError::Store(_)), "{:?}", err);
+        assert!(err.to_string().contains("is corrupt"), "{}", err);
+    }
+
+    #[test]
+    fn test_malformed_ids_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path());
+        store.add(&snapshot(&[("a", "01")])).unwrap();
+
+        for id in [
+            "",
+            "a",
+            "../../etc/passwd",
+            &"A".repeat(64),
+            &"é".repeat(32),
+        ] {
+            assert!(matches!(store.object_path(id), Err(ScanError::Store(_))));
+        }
+        // A tag file pointing outside the store
+        fs::create_dir_all(dir.path().join("tags")).unwrap();
+        fs::write(dir.path().join("tags").join("escape"), "../../escape\n").unwrap();
+        assert!(matches!(store.load("@escape"), Err(ScanError::Store(_))));
+    }
+
+    #[test]
+    fn test_garbage_collection_waits_for_adds() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path());
+        store.add(&snapshot(&[("a", "01")])).unwrap();
+
+        let adding = store.lock(false).unwrap();
+        let collector = std::thread::spawn({
+            let root = dir.path().to_path_buf();
+            move || SnapshotStore::new(root).collect_garbage().unwrap()
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!collector.is_finished());
+        drop(adding);
+        assert_eq!(collector.join().unwrap(), 0);
+    }
+}
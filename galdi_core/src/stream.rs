@@ -0,0 +1,278 @@
+//! Lines of a JSONL snapshot stream (Plumbah Streaming Profile v1.0).
+//!
+//! A stream is a head line, then one line per entry or per error met while
+//! scanning, then a tail line with the final status and a summary.
+//! `Snapshot::from_jsonl` reads such a stream back into a whole snapshot.
+
+use std::io::{BufRead, BufReader, Cursor, Read};
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::{decompressing_reader, read_encoded};
+use crate::error::ScanError;
+use crate::plumbah::{PlumbahError, PlumbahObject, StreamSummary};
+use crate::snapshot::{ChecksumAlgorithm, Snapshot, SnapshotEntry};
+
+/// First line: the snapshot's header, everything but `count` and `entries`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamHead {
+    #[serde(rename = "$plumbah")]
+    pub plumbah: PlumbahObject,
+    pub version: String,
+    pub root: PathBuf,
+    pub checksum_algorithm: ChecksumAlgorithm,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_checksums: Vec<ChecksumAlgorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum_key_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_len: Option<u64>,
+}
+
+/// A middle line: one entry, or an error that took the place of one.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum StreamLine {
+    Error(StreamError),
+    Entry(Box<SnapshotEntry>),
+}
+
+/// An error reported in the middle of a stream.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamError {
+    #[serde(rename = "$plumbah")]
+    pub plumbah: StreamErrors,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamErrors {
+    pub errors: Vec<PlumbahError>,
+}
+
+/// Last line: the final status, with a `summary` of what was streamed.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamTail {
+    #[serde(rename = "$plumbah")]
+    pub plumbah: PlumbahObject,
+}
+
+/// Whether `line` is the head line of a JSONL snapshot stream. Used to tell
+/// a stream from a single JSON document, which starts the same way.
+pub fn is_stream_head(line: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct Head {
+        #[serde(rename = "$plumbah")]
+        plumbah: Marker,
+    }
+    #[derive(Deserialize)]
+    struct Marker {
+        stream: Option<String>,
+    }
+
+    serde_json::from_slice::<Head>(line)
+        .is_ok_and(|head| head.plumbah.stream.as_deref() == Some("head"))
+}
+
+/// Read a stored document in whichever encoding and compression it was
+/// written, or the snapshot rebuilt from a JSONL stream. Both start with `{`:
+/// a stream is told by its head line.
+pub fn read_stored<T: DeserializeOwned, R: Read>(reader: R) -> Result<T, ScanError> {
+    let mut reader = BufReader::new(decompressing_reader(reader)?);
+    let mut first_line = Vec::new();
+    reader.read_until(b'\n', &mut first_line)?;
+    let is_stream = is_stream_head(&first_line);
+
+    let reader = Cursor::new(first_line).chain(reader);
+    if is_stream {
+        let snapshot = Snapshot::from_jsonl(BufReader::new(reader))?;
+        serde_json::to_value(snapshot)
+            .and_then(serde_json::from_value)
+            .map_err(|err| ScanError::Schema(err.to_string()))
+    } else {
+        Ok(read_encoded(reader)?)
+    }
+}
+
+impl Snapshot {
+    /// Rebuild a snapshot from a JSONL stream.
+    ///
+    /// Error lines become the snapshot's `$plumbah.errors`, and the status and
+    /// execution time come from the tail. A stream that lacks its tail (as
+    /// when the scan was interrupted) or whose tail counts disagree with the
+    /// lines read is refused, rather than taken for a smaller tree.
+    pub fn from_jsonl<R: BufRead>(reader: R) -> Result<Snapshot, ScanError> {
+        let mut lines = reader
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line))
+            .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()));
+        let error = |number: usize, message: String| {
+            ScanError::Schema(format!("JSONL line {}: {}", number, message))
+        };
+
+        let (number, line) = lines
+            .next()
+            .ok_or_else(|| ScanError::Schema("empty JSONL stream".to_string()))?;
+        let line = line?;
+        if !is_stream_head(line.as_bytes()) {
+            return Err(error(number, "expected the stream head".to_string()));
+        }
+        let head: StreamHead =
+            serde_json::from_str(&line).map_err(|err| error(number, err.to_string()))?;
+
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+        let mut tail = None;
+        for (number, line) in lines {
+            let line = line?;
+            if tail.is_some() {
+                return Err(error(number, "unexpected line after the tail".to_string()));
+            }
+            match serde_json::from_str::<StreamLine>(&line) {
+                Ok(StreamLine::Entry(entry)) => entries.push(*entry),
+                Ok(StreamLine::Error(line)) => errors.extend(line.plumbah.errors),
+                Err(err) => match serde_json::from_str::<StreamTail>(&line) {
+                    Ok(found) if found.plumbah.stream.as_deref() == Some("tail") => {
+                        tail = Some((number, found))
+                    }
+                    _ => return Err(error(number, err.to_string())),
+                },
+            }
+        }
+
+        let (number, tail) = tail.ok_or_else(|| {
+            ScanError::Schema("JSONL stream ends without its tail line".to_string())
+        })?;
+        let summary = tail.plumbah.summary.as_ref();
+        if let Some(StreamSummary {
+            total,
+            errors: error_count,
+            ..
+        }) = summary
+            && (*total != entries.len() || *error_count != errors.len())
+        {
+            return Err(error(
+                number,
+                format!(
+                    "tail counts {} entries and {} errors, the stream has {} and {}",
+                    total,
+                    error_count,
+                    entries.len(),
+                    errors.len()
+                ),
+            ));
+        }
+
+        let mut plumbah = head.plumbah;
+        plumbah.stream = None;
+        plumbah.status = tail.plumbah.status;
+        if let Some(meta) = plumbah.meta.as_mut()
+            && let Some(execution_time_ms) = tail.plumbah.execution_time_ms
+        {
+            meta.execution_time_ms = execution_time_ms;
+        }
+        if !errors.is_empty() {
+            plumbah.errors = Some(errors);
+        }
+
+        Ok(Snapshot {
+            plumbah,
+            version: head.version,
+            root: head.root,
+            checksum_algorithm: head.checksum_algorithm,
+            extra_checksums: head.extra_checksums,
+            checksum_key_id: head.checksum_key_id,
+            sample_len: head.sample_len,
+            count: entries.len(),
+            entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEAD: &str = r#"{"$plumbah":{"version":"1.0","stream":"head","status":"ok","meta":{"idempotent":true,"mutates":false,"safe":true,"deterministic":false,"plumbah_level":2,"execution_time_ms":0,"tool":"galdi_snapshot","tool_version":"0.1.0","timestamp":"2026-01-01T00:00:00Z"}},"version":"1.1","root":"/data","checksum_algorithm":"sha256"}"#;
+    const ENTRY: &str = r#"{"path":"a.txt","type":"file","size":3,"mtime":"2026-01-01T00:00:00Z","checksum":"sha256:00"}"#;
+    const ERROR: &str = r#"{"$plumbah":{"errors":[{"code":"PERMISSION_DENIED","message":"Permission denied","path":"/data/secret","recoverable":true}]}}"#;
+
+    fn tail(total: usize, errors: usize) -> String {
+        format!(
+            r#"{{"$plumbah":{{"version":"1.0","stream":"tail","status":"{}","summary":{{"total":{},"processed":{},"errors":{}}},"execution_time_ms":42}}}}"#,
+            if errors > 0 { "partial" } else { "ok" },
+            total,
+            total,
+            errors
+        )
+    }
+
+    fn read(lines: &[&str]) -> Result<Snapshot, ScanError> {
+        Snapshot::from_jsonl(lines.join("\n").as_bytes())
+    }
+
+    #[test]
+    fn test_from_jsonl_rebuilds_snapshot() {
+        let tail = tail(1, 1);
+        let snapshot = read(&[HEAD, ENTRY, ERROR, "", &tail]).unwrap();
+
+        assert_eq!(snapshot.root, PathBuf::from("/data"));
+        assert_eq!(snapshot.checksum_algorithm, ChecksumAlgorithm::Sha256);
+        assert_eq!(snapshot.count, 1);
+        assert_eq!(snapshot.entries[0].path, PathBuf::from("a.txt"));
+        assert_eq!(snapshot.plumbah.stream, None);
+        assert!(matches!(
+            snapshot.plumbah.status,
+            crate::plumbah::Status::Partial
+        ));
+        assert_eq!(
+            snapshot.plumbah.errors.unwrap()[0].code,
+            "PERMISSION_DENIED"
+        );
+        assert_eq!(snapshot.plumbah.meta.unwrap().execution_time_ms, 42);
+    }
+
+    #[test]
+    fn test_from_jsonl_refuses_incomplete_streams() {
+        let (empty, one, two) = (tail(0, 0), tail(1, 0), tail(2, 0));
+        let cases = [
+            (vec![HEAD, ENTRY], "without its tail"),
+            (vec![ENTRY], "line 1: expected the stream head"),
+            (vec![HEAD, ENTRY, &two], "tail counts 2 entries"),
+            (vec![HEAD, &empty, ENTRY], "line 3: unexpected line"),
+            (vec![HEAD, "{\"path\":1}", &one], "line 2"),
+        ];
+        for (lines, expected) in cases {
+            let err = read(&lines).unwrap_err();
+            assert!(matches!(err, ScanError::Schema(_)), "{:?}", err);
+            assert!(err.to_string().contains(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_read_stored_takes_streams_and_documents() {
+        let tail = tail(1, 0);
+        let stream = [HEAD, ENTRY, &tail].join("\n");
+        let snapshot: Snapshot = read_stored(stream.as_bytes()).unwrap();
+        assert_eq!(snapshot.count, 1);
+
+        let document: serde_json::Value = read_stored(stream.as_bytes()).unwrap();
+        assert_eq!(document["entries"][0]["path"], "a.txt");
+        assert!(document["$plumbah"].get("stream").is_none());
+
+        let json = serde_json::to_vec(&snapshot).unwrap();
+        let restored: Snapshot = read_stored(json.as_slice()).unwrap();
+        assert_eq!(restored.entries[0].path, snapshot.entries[0].path);
+    }
+
+    #[test]
+    fn test_is_stream_head() {
+        assert!(is_stream_head(HEAD.as_bytes()));
+        assert!(!is_stream_head(ENTRY.as_bytes()));
+        assert!(!is_stream_head(tail(0, 0).as_bytes()));
+        assert!(!is_stream_head(b"{\"$plumbah\":{\"stream\":\"head\""));
+    }
+}
@@ -0,0 +1,363 @@
+//! Snapshot construction from tar streams.
+//!
+//! Builds a `Snapshot` straight from tar headers and member data, without
+//! extracting anything to disk. This makes `docker save`, `git archive` or
+//! any other tarball directly comparable to a live directory.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    time::Instant,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    Meta, PlumbahObject, Status,
+    checksum::get_keyed_hasher,
+    content_type::{ContentSniffer, ContentType},
+    elf::ElfInfo,
+    error::ScanError,
+    migration::SNAPSHOT_VERSION,
+    snapshot::{ChecksumAlgorithm, EntryType, Snapshot, SnapshotEntry},
+};
+
+impl Snapshot {
+    /// Build a snapshot from a tar stream.
+    ///
+    /// Entry types, modes, owner ids, mtimes and symlink targets come from the
+    /// tar headers; file checksums are computed from the member data as it is
+    /// streamed. Member paths are normalized so that `./a/b` and `a/b/` both
+    /// become `a/b`, and the archive root (`.` or `./`) becomes `""`, the same
+    /// as the root entry of a filesystem scan. When a path occurs more than once
+    /// the last member wins, as it would on extraction.
+    pub fn from_tar_reader<R: Read>(
+        reader: R,
+        root: impl Into<PathBuf>,
+        checksum_algorithm: ChecksumAlgorithm,
+    ) -> Result<Snapshot, ScanError> {
+        let start = Instant::now();
+        let entries = read_tar_entries(reader, checksum_algorithm, None)?;
+
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION.to_string(),
+            root: root.into(),
+            checksum_algorithm,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
+            plumbah: PlumbahObject::new(
+                Status::Ok,
+                Meta::new(
+                    "galdi_snapshot",
+                    env!("CARGO_PKG_VERSION"),
+                    true,
+                    false,
+                    true,
+                    true, // the stream fully determines the output
+                    start.elapsed().as_millis() as u64,
+                    Utc::now(),
+                ),
+            ),
+            count: entries.len(),
+            entries,
+        })
+    }
+}
+
+/// Read all members of a tar stream into sorted snapshot entries.
+///
+/// With `strip_prefix`, only members below that directory are kept and their
+/// paths are made relative to it.
+pub(crate) fn read_tar_entries<R: Read>(
+    reader: R,
+    checksum_algorithm: ChecksumAlgorithm,
+    strip_prefix: Option<&Path>,
+) -> Result<Vec<SnapshotEntry>, ScanError> {
+    // Keyed checksums are only supported for directory scans.
+    let hasher = get_keyed_hasher(checksum_algorithm, None)?;
+    let mut archive = tar::Archive::new(reader);
+    let mut entries: BTreeMap<PathBuf, SnapshotEntry> = BTreeMap::new();
+    // Hard links carry no data of their own; remember file checksums so the
+    // link can reuse the checksum of the member it points at.
+    let mut checksums: HashMap<PathBuf, FileContent> = HashMap::new();
+
+    for member in archive.entries()? {
+        let mut member = member?;
+        let header = member.header();
+
+        let entry_type = match header.entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous | tar::EntryType::Link => {
+                EntryType::File
+            }
+            tar::EntryType::Directory => EntryType::Directory,
+            tar::EntryType::Symlink => EntryType::Symlink,
+            tar::EntryType::Char | tar::EntryType::Block | tar::EntryType::Fifo => {
+                EntryType::Undefined
+            }
+            // Pax/GNU extension headers and the like are metadata, not members.
+            _ => continue,
+        };
+        let is_hard_link = header.entry_type() == tar::EntryType::Link;
+
+        // Hard link targets are recorded relative to the archive, not the prefix.
+        let member_path = normalize_member_path(&member.path()?)?;
+        let path = match strip_prefix {
+            Some(prefix) => match member_path.strip_prefix(prefix) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => continue,
+            },
+            None => member_path.clone(),
+        };
+        let mode = format!("{:o}", header.mode()? & 0o7777);
+        // Some archivers leave the owner fields blank; treat that as unknown.
+        let uid = header.uid().ok().and_then(|uid| u32::try_from(uid).ok());
+        let gid = header.gid().ok().and_then(|gid| u32::try_from(gid).ok());
+        let mtime = DateTime::<Utc>::from_timestamp(header.mtime()? as i64, 0)
+            .ok_or_else(|| invalid_data(&path, "mtime out of range"))?;
+        let link_name = member.link_name()?.map(|target| target.into_owned());
+
+        let (size, checksum, content_type, elf) = if is_hard_link {
+            let target = link_name
+                .as_deref()
+                .ok_or_else(|| invalid_data(&path, "hard link without a target"))?;
+            let target = normalize_member_path(target)?;
+            checksums
+                .get(&target)
+                .cloned()
+                .ok_or_else(|| invalid_data(&path, "hard link to an unknown member"))?
+        } else if entry_type == EntryType::File {
+            let size = member.header().size()?;
+            let mut reader = ContentSniffer::new(&mut member);
+            let checksum = hasher.hash_reader(&mut reader)?;
+            (
+                Some(size),
+                Some(checksum),
+                Some(reader.content_type()),
+                reader.elf_info(),
+            )
+        } else if entry_type == EntryType::Directory {
+            (None, None, None, None)
+        } else {
+            (Some(member.header().size()?), None, None, None)
+        };
+
+        if entry_type == EntryType::File {
+            checksums.insert(
+                member_path,
+                (size, checksum.clone(), content_type, elf.clone()),
+            );
+        }
+
+        entries.insert(
+            path.clone(),
+            SnapshotEntry {
+                path,
+                entry_type,
+                size,
+                mode: Some(mode),
+                uid,
+                gid,
+                mtime,
+                checksum,
+                checksums: Default::default(),
+                content_type,
+                elf,
+                target: if entry_type == EntryType::Symlink {
+                    link_name
+                } else {
+                    None
+                },
+            },
+        );
+    }
+
+    Ok(entries.into_values().collect())
+}
+
+/// Size, checksum, content type and ELF metadata of a regular file member.
+type FileContent = (
+    Option<u64>,
+    Option<String>,
+    Option<ContentType>,
+    Option<ElfInfo>,
+);
+
+/// Strip `./` prefixes, trailing slashes and other non-normal components.
+/// Paths climbing out with `..` are refused: dropping those would move the
+/// member somewhere else in the tree.
+pub(crate) fn normalize_member_path(path: &Path) -> Result<PathBuf, ScanError> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(Ok(part)),
+            Component::ParentDir => Some(Err(invalid_data(path, "path leads outside the archive"))),
+            _ => None,
+        })
+        .collect()
+}
+
+fn invalid_data(path: &Path, message: &str) -> ScanError {
+    ScanError::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), message),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::get_hasher;
+
+    fn append_file(builder: &mut tar::Builder<Vec<u8>>, path: &str, mode: u32, data: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(data.len() as u64);
+        header.set_mode(mode);
+        header.set_uid(1000);
+        header.set_gid(100);
+        header.set_mtime(1_700_000_000);
+        header.set_cksum();
+        builder.append_data(&mut header, path, data).unwrap();
+    }
+
+    fn append_special(
+        builder: &mut tar::Builder<Vec<u8>>,
+        entry_type: tar::EntryType,
+        path: &str,
+        link: Option<&str>,
+    ) {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(0);
+        header.set_mode(0o755);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(1_700_000_000);
+        match link {
+            Some(link) => builder.append_link(&mut header, path, link).unwrap(),
+            None => {
+                header.set_cksum();
+                builder.append_data(&mut header, path, io::empty()).unwrap()
+            }
+        }
+    }
+
+    fn sample_archive() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        append_special(&mut builder, tar::EntryType::Directory, "./", None);
+        append_special(&mut builder, tar::EntryType::Directory, "./etc/", None);
+        append_file(&mut builder, "./etc/hosts", 0o644, b"127.0.0.1 localhost\n");
+        append_file(&mut builder, "./run.sh", 0o755, b"#!/bin/sh\n");
+        append_special(
+            &mut builder,
+            tar::EntryType::Symlink,
+            "./hosts",
+            Some("etc/hosts"),
+        );
+        append_special(
+            &mut builder,
+            tar::EntryType::Link,
+            "./hosts.bak",
+            Some("./etc/hosts"),
+        );
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_from_tar_reader_maps_headers() {
+        let archive = sample_archive();
+        let snapshot =
+            Snapshot::from_tar_reader(&archive[..], "-", ChecksumAlgorithm::XXH3_64).unwrap();
+
+        let paths: Vec<_> = snapshot.entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("etc"),
+                PathBuf::from("etc/hosts"),
+                PathBuf::from("hosts"),
+                PathBuf::from("hosts.bak"),
+                PathBuf::from("run.sh"),
+            ]
+        );
+        assert_eq!(snapshot.count, 6);
+        assert_eq!(snapshot.root, PathBuf::from("-"));
+
+        let etc = &snapshot.entries[1];
+        assert_eq!(etc.entry_type, EntryType::Directory);
+        assert!(etc.size.is_none());
+
+        let hosts = &snapshot.entries[2];
+        assert_eq!(hosts.entry_type, EntryType::File);
+        assert_eq!(hosts.mode.as_deref(), Some("644"));
+        assert_eq!(hosts.uid, Some(1000));
+        assert_eq!(hosts.gid, Some(100));
+        assert_eq!(hosts.size, Some(20));
+        assert_eq!(hosts.mtime.timestamp(), 1_700_000_000);
+
+        let expected = get_hasher(ChecksumAlgorithm::XXH3_64)
+            .unwrap()
+            .hash_reader(&mut &b"127.0.0.1 localhost\n"[..])
+            .unwrap();
+        assert_eq!(hosts.checksum.as_deref(), Some(expected.as_str()));
+
+        let link = &snapshot.entries[3];
+        assert_eq!(link.entry_type, EntryType::Symlink);
+        assert_eq!(link.target, Some(PathBuf::from("etc/hosts")));
+        assert!(link.checksum.is_none());
+    }
+
+    #[test]
+    fn test_from_tar_reader_hard_link_reuses_checksum() {
+        let archive = sample_archive();
+        let snapshot =
+            Snapshot::from_tar_reader(&archive[..], "-", ChecksumAlgorithm::Sha256).unwrap();
+
+        let hosts = &snapshot.entries[2];
+        let hard_link = &snapshot.entries[4];
+        assert_eq!(hard_link.entry_type, EntryType::File);
+        assert_eq!(hard_link.checksum, hosts.checksum);
+        assert_eq!(hard_link.size, hosts.size);
+        assert!(hard_link.target.is_none());
+    }
+
+    #[test]
+    fn test_from_tar_reader_rejects_garbage() {
+        let garbage = vec![0xffu8; 1024];
+        let result = Snapshot::from_tar_reader(&garbage[..], "-", ChecksumAlgorithm::XXH3_64);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_from_tar_reader_rejects_parent_dir_members() {
+        let mut builder = tar::Builder::new(Vec::new());
+        append_file(&mut builder, "./etc/hosts", 0o644, b"127.0.0.1 localhost\n");
+        let mut archive = builder.into_inner().unwrap();
+        // `tar::Builder` refuses `..` itself, so patch the header in place
+        let mut header = tar::Header::from_byte_slice(&archive[..512]).clone();
+        header.as_old_mut().name[..11].copy_from_slice(b"../etc/host");
+        header.set_cksum();
+        archive[..512].copy_from_slice(header.as_bytes());
+
+        let error =
+            Snapshot::from_tar_reader(&archive[..], "-", ChecksumAlgorithm::XXH3_64).unwrap_err();
+        match error {
+            ScanError::Io(err) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_normalize_member_path() {
+        let normalize = |path: &str| normalize_member_path(Path::new(path)).unwrap();
+        assert_eq!(normalize("./"), PathBuf::from(""));
+        assert_eq!(normalize("."), PathBuf::from(""));
+        assert_eq!(normalize("./a/b/"), PathBuf::from("a/b"));
+        assert_eq!(normalize("a/b"), PathBuf::from("a/b"));
+        for escaping in ["../a", "a/../../b", "./a/.."] {
+            assert!(normalize_member_path(Path::new(escaping)).is_err());
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Snapshots to test with, shared by the unit tests of every crate through
+//! the `test-util` feature. Tests adjust them with struct update syntax, e.g.
+//! `SnapshotEntry { size: Some(100), ..SnapshotEntry::test_file(path, hash) }`.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+
+use crate::migration::SNAPSHOT_VERSION;
+use crate::plumbah::{Meta, PlumbahObject, Status};
+use crate::snapshot::{ChecksumAlgorithm, EntryType, Snapshot, SnapshotEntry};
+
+/// Time recorded by test entries and snapshots, so that equal ones compare equal.
+fn test_time() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_700_000_000, 0).expect("a valid timestamp")
+}
+
+impl SnapshotEntry {
+    /// A 10-byte `644` file with the given checksum.
+    #[doc(hidden)]
+    pub fn test_file(path: &str, checksum: &str) -> SnapshotEntry {
+        SnapshotEntry {
+            path: PathBuf::from(path),
+            entry_type: EntryType::File,
+            size: Some(10),
+            mode: Some("644".to_string()),
+            uid: None,
+            gid: None,
+            mtime: test_time(),
+            checksum: Some(checksum.to_string()),
+            checksums: Default::default(),
+            content_type: None,
+            elf: None,
+            target: None,
+        }
+    }
+
+    /// A `755` directory.
+    #[doc(hidden)]
+    pub fn test_dir(path: &str) -> SnapshotEntry {
+        SnapshotEntry {
+            entry_type: EntryType::Directory,
+            size: None,
+            mode: Some("755".to_string()),
+            checksum: None,
+            ..SnapshotEntry::test_file(path, "")
+        }
+    }
+}
+
+impl Snapshot {
+    /// An `xxh3_64` snapshot of `entries`, rooted at `test`.
+    #[doc(hidden)]
+    pub fn test_of(entries: Vec<SnapshotEntry>) -> Snapshot {
+        Snapshot {
+            plumbah: PlumbahObject::new(
+                Status::Ok,
+                Meta::new(
+                    "galdi_snapshot",
+                    "0.0.0",
+                    true,
+                    false,
+                    true,
+                    false,
+                    0,
+                    test_time(),
+                ),
+            ),
+            version: SNAPSHOT_VERSION.to_string(),
+            root: PathBuf::from("test"),
+            checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
+            count: entries.len(),
+            entries,
+        }
+    }
+}
@@ -0,0 +1,498 @@
+//! Verification of a live tree against a stored snapshot.
+//!
+//! Like `sha256sum -c`, but for a whole tree and its metadata: the tree is
+//! re-scanned with the snapshot's own checksum algorithms and every entry is
+//! checked against what the snapshot recorded.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    time::Instant,
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AttributeChange, DiffOptions, Meta, PlumbahObject, Provenance, Status,
+    checksum::{ChecksumKey, DEFAULT_SAMPLE_LEN},
+    error::ScanError,
+    fs_scan::{ScanOptions, Scanner},
+    snapshot::{ChecksumAlgorithm, ContentMatch, Snapshot, SnapshotEntry, check_key_ids},
+};
+
+pub struct VerifyOptions {
+    /// Directory to verify; defaults to the snapshot's `root`.
+    pub root: Option<PathBuf>,
+    pub ignore_time: bool,
+    pub ignore_mode: bool,
+    pub threads: Option<usize>, // None = auto-detect
+    /// Key for snapshots with keyed checksums; must match `checksum_key_id`.
+    pub checksum_key: Option<ChecksumKey>,
+    /// Scan options; when `None`, those recorded in the snapshot's provenance
+    /// are used, so that the tree is walked the way the snapshot was taken.
+    pub follow_symlinks: Option<bool>,
+    pub max_depth: Option<usize>,
+    pub exclude_patterns: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyReport {
+    #[serde(rename = "$plumbah")]
+    pub plumbah: PlumbahObject,
+    /// The directory that was verified.
+    pub root: PathBuf,
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// True when every entry matched and nothing was missing, extra or unreadable.
+    pub passed: bool,
+    pub summary: VerifySummary,
+    /// Every entry that did not verify, sorted by path.
+    pub failures: Vec<VerifyFailure>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifySummary {
+    pub ok: usize,
+    pub missing: usize,
+    pub extra: usize,
+    pub modified: usize,
+    pub unreadable: usize,
+    /// How many of the ok entries were only compared on sampled checksums,
+    /// and so probably rather than certainly match.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub probably_ok: usize,
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyFailure {
+    pub path: PathBuf,
+    pub status: VerifyStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<AttributeChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<SnapshotEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<SnapshotEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyStatus {
+    /// In the snapshot, gone from the tree.
+    Missing,
+    /// In the tree, not in the snapshot.
+    Extra,
+    /// Present in both but different.
+    Modified,
+    /// Present but could not be read (e.g. permission denied).
+    Unreadable,
+}
+
+impl VerifyReport {
+    /// Exit code for the CLI: 0 when passed, 1 when anything is missing, extra
+    /// or modified, 2 when nothing differed but part of the tree could not be read.
+    pub fn exit_code(&self) -> i32 {
+        let summary = &self.summary;
+        if summary.missing + summary.extra + summary.modified > 0 {
+            1
+        } else if !self.passed {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+/// Re-scan the tree at the snapshot's root (or `options.root`) and check it
+/// against `snapshot`.
+///
+/// The live side is hashed with the snapshot's `checksum_algorithm` and
+/// `extra_checksums`, so checksums are always comparable. Fails only when the
+/// root itself cannot be scanned; problems with individual entries are
+/// reported in the `VerifyReport`.
+pub fn verify_snapshot(
+    snapshot: &Snapshot,
+    options: &VerifyOptions,
+) -> Result<VerifyReport, ScanError> {
+    let start = Instant::now();
+    let root = options
+        .root
+        .clone()
+        .unwrap_or_else(|| snapshot.root.clone());
+    if !root.is_dir() {
+        return Err(ScanError::PathNotFound(root));
+    }
+    let key_id = options.checksum_key.as_ref().map(ChecksumKey::id);
+    check_key_ids(snapshot.checksum_key_id.as_deref(), key_id.as_deref())?;
+    let recorded = snapshot
+        .plumbah
+        .meta
+        .as_ref()
+        .and_then(Provenance::of)
+        .and_then(|provenance| provenance.scan);
+
+    let scanner = Scanner::new(ScanOptions {
+        root: root.clone(),
+        checksum_algorithm: snapshot.checksum_algorithm,
+        extra_checksums: snapshot.extra_checksums.clone(),
+        checksum_key: options.checksum_key.clone(),
+        // Verification always reads the content rather than trusting a cache.
+        checksum_cache: false,
+        follow_symlinks: options
+            .follow_symlinks
+            .or(recorded.as_ref().map(|scan| scan.follow_symlinks))
+            .unwrap_or(false),
+        max_depth: options
+            .max_depth
+            .or(recorded.as_ref().and_then(|scan| scan.max_depth)),
+        exclude_patterns: options
+            .exclude_patterns
+            .clone()
+            .or(recorded.map(|scan| scan.exclude_patterns))
+            .unwrap_or_default(),
+        timeout_ms: None,
+        threads: options.threads,
+        // Snapshot paths use '/' whenever they were normalized; match them.
+        normalize_paths: snapshot
+            .entries
+            .iter()
+            .all(|entry| !entry.path.to_string_lossy().contains('\\')),
+        sample_len: snapshot.sample_len.unwrap_or(DEFAULT_SAMPLE_LEN),
+    });
+
+    let mut live = BTreeMap::new();
+    let mut unreadable = BTreeMap::new();
+    let mut errors = Vec::new();
+    for result in scanner.scan_iter() {
+        match result {
+            Ok(entry) => {
+                live.insert(entry.path.clone(), entry);
+            }
+            Err(err) => {
+                let error = err.to_plumbah_error();
+                if let Some(path) = &error.path {
+                    let relative = path.strip_prefix(&root).unwrap_or(path);
+                    unreadable.insert(relative.to_path_buf(), error.message.clone());
+                }
+                errors.push(error);
+            }
+        }
+    }
+
+    let expected: BTreeMap<_, _> = snapshot
+        .entries
+        .iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect();
+    let all_paths: BTreeSet<&PathBuf> = expected
+        .keys()
+        .chain(live.keys())
+        .chain(unreadable.keys())
+        .collect();
+
+    let diff_options = DiffOptions {
+        ignore_time: options.ignore_time,
+        ignore_mode: options.ignore_mode,
+        ..Default::default()
+    };
+    let mut summary = VerifySummary::default();
+    let mut failures = Vec::new();
+    for path in all_paths {
+        let failure = match (expected.get(path), live.get(path), unreadable.get(path)) {
+            (expected, _, Some(error)) => VerifyFailure {
+                path: path.clone(),
+                status: VerifyStatus::Unreadable,
+                changes: vec![],
+                expected: expected.map(|entry| (*entry).clone()),
+                actual: None,
+                error: Some(error.clone()),
+            },
+            (Some(expected), Some(actual), None) => {
+                let changes = expected.changes(actual, &diff_options);
+                if changes.is_empty() {
+                    summary.ok += 1;
+                    if expected.content_match(actual) == ContentMatch::ProbablySame {
+                        summary.probably_ok += 1;
+                    }
+                    continue;
+                }
+                VerifyFailure {
+                    path: path.clone(),
+                    status: VerifyStatus::Modified,
+                    changes,
+                    expected: Some((*expected).clone()),
+                    actual: Some(actual.clone()),
+                    error: None,
+                }
+            }
+            (Some(expected), None, None) => VerifyFailure {
+                path: path.clone(),
+                status: VerifyStatus::Missing,
+                changes: vec![],
+                expected: Some((*expected).clone()),
+                actual: None,
+                error: None,
+            },
+            (None, Some(actual), None) => VerifyFailure {
+                path: path.clone(),
+                status: VerifyStatus::Extra,
+                changes: vec![],
+                expected: None,
+                actual: Some(actual.clone()),
+                error: None,
+            },
+            (None, None, None) => unreachable!(),
+        };
+
+        match failure.status {
+            VerifyStatus::Missing => summary.missing += 1,
+            VerifyStatus::Extra => summary.extra += 1,
+            VerifyStatus::Modified => summary.modified += 1,
+            VerifyStatus::Unreadable => summary.unreadable += 1,
+        }
+        failures.push(failure);
+    }
+
+    let status = if errors.is_empty() {
+        Status::Ok
+    } else {
+        Status::Partial
+    };
+    // Errors without a path could hide anything; never report a pass with them.
+    let pathless_errors = errors.iter().any(|error| error.path.is_none());
+    let mut plumbah = PlumbahObject::new(
+        status,
+        Meta::new(
+            "galdi_verify",
+            env!("CARGO_PKG_VERSION"),
+            true,
+            false,
+            true,
+            false, // the tree can change between runs
+            start.elapsed().as_millis() as u64,
+            Utc::now(),
+        )
+        .with_default_profiles(),
+    );
+    if !errors.is_empty() {
+        plumbah = plumbah.with_errors(errors);
+    }
+
+    Ok(VerifyReport {
+        plumbah,
+        root,
+        checksum_algorithm: snapshot.checksum_algorithm,
+        passed: failures.is_empty() && !pathless_errors,
+        summary,
+        failures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+    use tempfile::TempDir;
+
+    fn scan(root: &Path, checksum_algorithm: ChecksumAlgorithm) -> Snapshot {
+        scan_keyed(root, checksum_algorithm, None)
+    }
+
+    fn scan_keyed(
+        root: &Path,
+        checksum_algorithm: ChecksumAlgorithm,
+        checksum_key: Option<ChecksumKey>,
+    ) -> Snapshot {
+        Scanner::new(ScanOptions {
+            root: root.to_path_buf(),
+            checksum_algorithm,
+            extra_checksums: vec![],
+            checksum_key,
+            checksum_cache: false,
+            follow_symlinks: false,
+            max_depth: None,
+            exclude_patterns: vec![],
+            timeout_ms: None,
+            threads: Some(1),
+            normalize_paths: true,
+            sample_len: DEFAULT_SAMPLE_LEN,
+        })
+        .scan()
+        .unwrap()
+    }
+
+    fn options() -> VerifyOptions {
+        VerifyOptions {
+            root: None,
+            ignore_time: true,
+            ignore_mode: false,
+            threads: Some(1),
+            checksum_key: None,
+            follow_symlinks: None,
+            max_depth: None,
+            exclude_patterns: None,
+        }
+    }
+
+    #[test]
+    fn test_verify_unchanged_tree_passes() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.txt"), b"alpha").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/b.txt"), b"beta").unwrap();
+
+        let snapshot = scan(dir.path(), ChecksumAlgorithm::Sha256);
+        let report = verify_snapshot(&snapshot, &options()).unwrap();
+
+        assert!(report.passed);
+        assert_eq!(report.exit_code(), 0);
+        assert_eq!(report.summary.ok, snapshot.count);
+        assert_eq!(report.checksum_algorithm, ChecksumAlgorithm::Sha256);
+    }
+
+    #[test]
+    fn test_verify_reports_missing_extra_and_modified() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("keep.txt"), b"keep").unwrap();
+        fs::write(dir.path().join("gone.txt"), b"gone").unwrap();
+        fs::write(dir.path().join("edit.txt"), b"before").unwrap();
+        let snapshot = scan(dir.path(), ChecksumAlgorithm::Blake3);
+
+        fs::remove_file(dir.path().join("gone.txt")).unwrap();
+        fs::write(dir.path().join("edit.txt"), b"after!").unwrap();
+        fs::write(dir.path().join("new.txt"), b"new").unwrap();
+
+        let report = verify_snapshot(&snapshot, &options()).unwrap();
+        assert!(!report.passed);
+        assert_eq!(report.exit_code(), 1);
+        assert_eq!(report.summary.missing, 1);
+        assert_eq!(report.summary.extra, 1);
+        assert_eq!(report.summary.modified, 1);
+
+        let statuses: Vec<_> = report
+            .failures
+            .iter()
+            .map(|failure| (failure.path.to_string_lossy().into_owned(), failure.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("edit.txt".to_string(), VerifyStatus::Modified),
+                ("gone.txt".to_string(), VerifyStatus::Missing),
+                ("new.txt".to_string(), VerifyStatus::Extra),
+            ]
+        );
+        assert!(matches!(
+            report.failures[0].changes[..],
+            [AttributeChange::Content]
+        ));
+    }
+
+    #[test]
+    fn test_verify_compares_like_diff() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.txt"), b"alpha").unwrap();
+
+        // Attributes that only diff used to compare are verified too
+        let mut snapshot = scan(dir.path(), ChecksumAlgorithm::Sha256);
+        let entry = snapshot
+            .entries
+            .iter_mut()
+            .find(|entry| entry.path == Path::new("a.txt"))
+            .unwrap();
+        entry.content_type = Some(crate::ContentType::Elf);
+        let report = verify_snapshot(&snapshot, &options()).unwrap();
+        assert!(!report.passed);
+        assert!(matches!(
+            report.failures[0].changes[..],
+            [AttributeChange::ContentType]
+        ));
+
+        // Matches on sampled checksums alone are only probable
+        let snapshot = scan(dir.path(), ChecksumAlgorithm::XXH3_64Sampled);
+        let report = verify_snapshot(&snapshot, &options()).unwrap();
+        assert!(report.passed);
+        assert_eq!(report.summary.probably_ok, 1);
+    }
+
+    #[test]
+    fn test_verify_follows_recorded_scan_options() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.txt"), b"alpha").unwrap();
+        fs::create_dir(dir.path().join("logs")).unwrap();
+        fs::write(dir.path().join("logs/app.log"), b"noise").unwrap();
+        let excluding_logs = || ScanOptions {
+            root: dir.path().to_path_buf(),
+            checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key: None,
+            checksum_cache: false,
+            follow_symlinks: false,
+            max_depth: None,
+            exclude_patterns: vec!["logs".to_string()],
+            timeout_ms: None,
+            threads: Some(1),
+            normalize_paths: true,
+            sample_len: DEFAULT_SAMPLE_LEN,
+        };
+        let mut snapshot = Scanner::new(excluding_logs()).scan().unwrap();
+
+        // Knowing nothing of the exclusion, the excluded files are extra
+        let report = verify_snapshot(&snapshot, &options()).unwrap();
+        assert_eq!(report.summary.extra, 2);
+
+        let explicit = VerifyOptions {
+            exclude_patterns: Some(vec!["logs".to_string()]),
+            ..options()
+        };
+        assert!(verify_snapshot(&snapshot, &explicit).unwrap().passed);
+
+        Provenance::capture()
+            .with_scan(&excluding_logs())
+            .record(snapshot.plumbah.meta.as_mut().unwrap());
+        assert!(verify_snapshot(&snapshot, &options()).unwrap().passed);
+    }
+
+    #[test]
+    fn test_verify_missing_root_is_error() {
+        let dir = TempDir::new().unwrap();
+        let snapshot = scan(dir.path(), ChecksumAlgorithm::XXH3_64);
+        let options = VerifyOptions {
+            root: Some(dir.path().join("nope")),
+            ..options()
+        };
+        let result = verify_snapshot(&snapshot, &options);
+        assert!(matches!(result, Err(ScanError::PathNotFound(_))));
+    }
+
+    #[test]
+    fn test_verify_keyed_snapshot_needs_its_key() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.txt"), b"alpha").unwrap();
+        let key = ChecksumKey::new("s3cret").unwrap();
+        let snapshot = scan_keyed(dir.path(), ChecksumAlgorithm::HmacSha256, Some(key.clone()));
+        assert_eq!(snapshot.checksum_key_id, Some(key.id()));
+
+        let keyed = VerifyOptions {
+            checksum_key: Some(key),
+            ..options()
+        };
+        assert!(verify_snapshot(&snapshot, &keyed).unwrap().passed);
+
+        let unkeyed = verify_snapshot(&snapshot, &options());
+        assert!(matches!(unkeyed, Err(ScanError::KeyMismatch(_))));
+
+        let wrong_key = VerifyOptions {
+            checksum_key: Some(ChecksumKey::new("guess").unwrap()),
+            ..options()
+        };
+        let result = verify_snapshot(&snapshot, &wrong_key);
+        assert!(matches!(result, Err(ScanError::KeyMismatch(_))));
+    }
+}
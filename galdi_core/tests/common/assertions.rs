@@ -98,11 +98,48 @@ pub fn assert_checksum_format(checksum: &str, algo: ChecksumAlgorithm) {
                 "Blake3 hex should be lowercase"
             );
         }
+        ChecksumAlgorithm::XXH128
+        | ChecksumAlgorithm::Sha512
+        | ChecksumAlgorithm::Sha1
+        | ChecksumAlgorithm::Md5
+        | ChecksumAlgorithm::Crc32c
+        | ChecksumAlgorithm::Blake3Keyed
+        | ChecksumAlgorithm::HmacSha256
+        | ChecksumAlgorithm::XXH3_64Sampled => {
+            let (prefix, hex_len) = match algo {
+                ChecksumAlgorithm::XXH128 => ("xxh128:", 32),
+                ChecksumAlgorithm::Sha512 => ("sha512:", 128),
+                ChecksumAlgorithm::Sha1 => ("sha1:", 40),
+                ChecksumAlgorithm::Md5 => ("md5:", 32),
+                ChecksumAlgorithm::Blake3Keyed => ("blake3_keyed:", 64),
+                ChecksumAlgorithm::HmacSha256 => ("hmac_sha256:", 64),
+                ChecksumAlgorithm::XXH3_64Sampled => ("xxh3_64s:", 16),
+                _ => ("crc32c:", 8),
+            };
+            let hex_part = checksum
+                .strip_prefix(prefix)
+                .unwrap_or_else(|| panic!("{:?} checksum should start with '{}'", algo, prefix));
+            assert_eq!(
+                hex_part.len(),
+                hex_len,
+                "{:?} hex part should be {} characters, got {}",
+                algo,
+                hex_len,
+                hex_part.len()
+            );
+            assert!(
+                hex_part
+                    .chars()
+                    .all(|c| c.is_ascii_hexdigit() && !c.is_uppercase()),
+                "{:?} hex part should contain only lowercase hex digits",
+                algo
+            );
+        }
     }
 }
 
 /// Assert that a Plumbah object has all required Level 2 fields
-pub fn assert_plumbah_compliant<T>(object: &PlumbahObject) {
+pub fn assert_plumbah_compliant(object: &PlumbahObject) {
     assert_eq!(object.version, "1.0", "Plumbah version should be 1.0");
 
     // Check that meta has all required semantic flags
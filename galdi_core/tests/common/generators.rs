@@ -17,12 +17,17 @@ pub fn arbitrary_entry_type() -> impl Strategy<Value = EntryType> {
     ]
 }
 
-/// Generate arbitrary ChecksumAlgorithm
+/// Generate arbitrary ChecksumAlgorithm (keyed algorithms need a key and are left out)
 pub fn arbitrary_checksum_algorithm() -> impl Strategy<Value = ChecksumAlgorithm> {
     prop_oneof![
         Just(ChecksumAlgorithm::XXH3_64),
         Just(ChecksumAlgorithm::Sha256),
         Just(ChecksumAlgorithm::Blake3),
+        Just(ChecksumAlgorithm::XXH128),
+        Just(ChecksumAlgorithm::Sha512),
+        Just(ChecksumAlgorithm::Sha1),
+        Just(ChecksumAlgorithm::Md5),
+        Just(ChecksumAlgorithm::Crc32c),
     ]
 }
 
@@ -38,6 +43,16 @@ fn format_checksum(algo: ChecksumAlgorithm, value: u64) -> String {
             // Generate a fake but valid-looking Blake3
             format!("blake3:{:064x}", value as u128 * 0xcafebabe)
         }
+        ChecksumAlgorithm::XXH128 => format!("xxh128:{:032x}", value as u128 * 0xfeedface),
+        ChecksumAlgorithm::Sha512 => format!("sha512:{:0128x}", value as u128 * 0xdeadbeef),
+        ChecksumAlgorithm::Sha1 => format!("sha1:{:040x}", value as u128 * 0xdeadbeef),
+        ChecksumAlgorithm::Md5 => format!("md5:{:032x}", value as u128 * 0xdeadbeef),
+        ChecksumAlgorithm::Crc32c => format!("crc32c:{:08x}", value as u32),
+        ChecksumAlgorithm::Blake3Keyed => {
+            format!("blake3_keyed:{:064x}", value as u128 * 0xcafebabe)
+        }
+        ChecksumAlgorithm::HmacSha256 => format!("hmac_sha256:{:064x}", value as u128 * 0xdeadbeef),
+        ChecksumAlgorithm::XXH3_64Sampled => format!("xxh3_64s:{:016x}", value),
     }
 }
 
@@ -73,8 +88,13 @@ pub fn arbitrary_entry() -> impl Strategy<Value = SnapshotEntry> {
                 entry_type,
                 size,
                 mode,
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum,
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target,
             }
         })
@@ -111,8 +131,13 @@ pub fn arbitrary_entry_with_algo(algo: ChecksumAlgorithm) -> impl Strategy<Value
                 entry_type,
                 size,
                 mode,
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum,
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target,
             }
         })
@@ -171,6 +196,9 @@ pub fn arbitrary_snapshot() -> impl Strategy<Value = Snapshot> {
                     version: "1.0".to_string(),
                     root: PathBuf::from(root.clone()),
                     checksum_algorithm: algo,
+                    extra_checksums: vec![],
+                    checksum_key_id: None,
+                    sample_len: None,
                     count: actual_count,
                     entries,
                 }
@@ -202,6 +230,9 @@ pub fn arbitrary_snapshot_with_count(count: usize) -> impl Strategy<Value = Snap
                     version: "1.0".to_string(),
                     root: PathBuf::from(root.clone()),
                     checksum_algorithm: algo,
+                    extra_checksums: vec![],
+                    checksum_key_id: None,
+                    sample_len: None,
                     count: entries.len(),
                     entries,
                 }
@@ -231,6 +262,9 @@ pub fn snapshot_with_root_and_count(root: String, count: usize) -> impl Strategy
                 version: "1.0".to_string(),
                 root: PathBuf::from(root_inner.clone()),
                 checksum_algorithm: algo,
+                extra_checksums: vec![],
+                checksum_key_id: None,
+                sample_len: None,
                 count: entries.len(),
                 entries,
             }
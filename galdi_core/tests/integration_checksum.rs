@@ -343,3 +343,55 @@ fn test_blake3_empty_file() {
         "blake3:af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
     );
 }
+
+#[test]
+fn test_scan_with_extra_checksums_single_read() {
+    use galdi_core::{DEFAULT_SAMPLE_LEN, ScanOptions, Scanner, get_hasher};
+
+    let temp_dir = create_test_dir();
+    let size = 100 * 1024; // Several read buffers
+    let file_path = create_file_with_size(temp_dir.path(), "data.bin", size);
+
+    let snapshot = Scanner::new(ScanOptions {
+        root: temp_dir.path().to_path_buf(),
+        checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+        extra_checksums: vec![
+            ChecksumAlgorithm::Sha256,
+            ChecksumAlgorithm::XXH3_64, // Primary, ignored
+            ChecksumAlgorithm::Md5,
+        ],
+        checksum_key: None,
+        checksum_cache: false,
+        follow_symlinks: false,
+        max_depth: None,
+        exclude_patterns: vec![],
+        timeout_ms: None,
+        threads: Some(1),
+        normalize_paths: false,
+        sample_len: DEFAULT_SAMPLE_LEN,
+    })
+    .scan()
+    .unwrap();
+
+    assert_eq!(
+        snapshot.extra_checksums,
+        vec![ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Md5]
+    );
+    let entry = snapshot
+        .entries
+        .iter()
+        .find(|e| e.path.ends_with("data.bin"))
+        .unwrap();
+    assert_eq!(
+        entry.checksum,
+        Some(XXH3_64Hasher.hash_file(&file_path).unwrap())
+    );
+    assert_eq!(entry.checksums.len(), 2);
+    for algorithm in [ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Md5] {
+        let expected = get_hasher(algorithm)
+            .unwrap()
+            .hash_file(&file_path)
+            .unwrap();
+        assert_eq!(entry.checksums[&algorithm], expected);
+    }
+}
@@ -30,7 +30,7 @@ proptest! {
             ChecksumAlgorithm::XXH3_64
         };
 
-        let hasher = get_hasher(algo);
+        let hasher = get_hasher(algo).unwrap();
         let checksum = hasher.hash_file(&file_path).expect("Hashing should succeed");
 
         if use_sha256 {
@@ -63,7 +63,7 @@ proptest! {
             ChecksumAlgorithm::XXH3_64
         };
 
-        let hasher = get_hasher(algo);
+        let hasher = get_hasher(algo).unwrap();
         let checksum = hasher.hash_file(&file_path).expect("Hashing should succeed");
 
         if use_sha256 {
@@ -97,7 +97,7 @@ proptest! {
             ChecksumAlgorithm::XXH3_64
         };
 
-        let hasher = get_hasher(algo);
+        let hasher = get_hasher(algo).unwrap();
         let checksum = hasher.hash_file(&file_path).expect("Hashing should succeed");
 
         let prefix_len = if use_sha256 { 7 } else { 8 };
@@ -123,7 +123,7 @@ proptest! {
         file.write_all(&content).unwrap();
         drop(file);
 
-        let hasher = get_hasher(ChecksumAlgorithm::Sha256);
+        let hasher = get_hasher(ChecksumAlgorithm::Sha256).unwrap();
         let checksum = hasher.hash_file(&file_path).expect("Hashing should succeed");
 
         let hex_part = &checksum[7..]; // Skip "sha256:" prefix
@@ -154,10 +154,10 @@ proptest! {
             ChecksumAlgorithm::XXH3_64
         };
 
-        let hasher1 = get_hasher(algo);
+        let hasher1 = get_hasher(algo).unwrap();
         let checksum1 = hasher1.hash_file(&file_path).expect("First hash should succeed");
 
-        let hasher2 = get_hasher(algo);
+        let hasher2 = get_hasher(algo).unwrap();
         let checksum2 = hasher2.hash_file(&file_path).expect("Second hash should succeed");
 
         prop_assert_eq!(checksum1, checksum2,
@@ -193,7 +193,7 @@ proptest! {
             ChecksumAlgorithm::XXH3_64
         };
 
-        let hasher = get_hasher(algo);
+        let hasher = get_hasher(algo).unwrap();
         let checksum1 = hasher.hash_file(&file1_path).expect("Hash 1 should succeed");
         let checksum2 = hasher.hash_file(&file2_path).expect("Hash 2 should succeed");
 
@@ -2,7 +2,7 @@
 mod common;
 
 use common::*;
-use galdi_core::{ChecksumAlgorithm, ScanOptions, Scanner};
+use galdi_core::{ChecksumAlgorithm, DEFAULT_SAMPLE_LEN, ScanOptions, Scanner};
 use proptest::prelude::*;
 
 proptest! {
@@ -23,12 +23,16 @@ proptest! {
         let scanner = Scanner::new(ScanOptions {
             root: temp_dir.path().to_path_buf(),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key: None,
+            checksum_cache: false,
             follow_symlinks: false,
             max_depth: None,
             exclude_patterns: vec![],
             timeout_ms: None,
             threads: Some(1), // Single-threaded for determinism
             normalize_paths: false,
+            sample_len: DEFAULT_SAMPLE_LEN,
         });
 
         let snapshot = scanner.scan().expect("Scan should succeed");
@@ -62,12 +66,16 @@ proptest! {
         let scanner = Scanner::new(ScanOptions {
             root: temp_dir.path().to_path_buf(),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key: None,
+            checksum_cache: false,
             follow_symlinks: false,
             max_depth: None,
             exclude_patterns: vec![],
             timeout_ms: None,
             threads: None,
             normalize_paths: false,
+            sample_len: DEFAULT_SAMPLE_LEN,
         });
 
         let snapshot = scanner.scan().expect("Scan should succeed");
@@ -95,12 +103,16 @@ proptest! {
         let scanner_single = Scanner::new(ScanOptions {
             root: temp_dir.path().to_path_buf(),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key: None,
+            checksum_cache: false,
             follow_symlinks: false,
             max_depth: None,
             exclude_patterns: vec![],
             timeout_ms: None,
             threads: Some(1),
             normalize_paths: false,
+            sample_len: DEFAULT_SAMPLE_LEN,
         });
 
         let snapshot_single = scanner_single.scan().expect("Single-threaded scan should succeed");
@@ -109,12 +121,16 @@ proptest! {
         let scanner_multi = Scanner::new(ScanOptions {
             root: temp_dir.path().to_path_buf(),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key: None,
+            checksum_cache: false,
             follow_symlinks: false,
             max_depth: None,
             exclude_patterns: vec![],
             timeout_ms: None,
             threads: Some(thread_count),
             normalize_paths: false,
+            sample_len: DEFAULT_SAMPLE_LEN,
         });
 
         let snapshot_multi = scanner_multi.scan().expect("Multi-threaded scan should succeed");
@@ -146,12 +162,16 @@ proptest! {
         let scanner = Scanner::new(ScanOptions {
             root: temp_dir.path().to_path_buf(),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key: None,
+            checksum_cache: false,
             follow_symlinks: false,
             max_depth: None,
             exclude_patterns: vec![],
             timeout_ms: None,
             threads: None,
             normalize_paths: false,
+            sample_len: DEFAULT_SAMPLE_LEN,
         });
 
         let snapshot = scanner.scan().expect("Scan should succeed");
@@ -184,12 +204,16 @@ proptest! {
         let scanner = Scanner::new(ScanOptions {
             root: temp_dir.path().to_path_buf(),
             checksum_algorithm: algo,
+            extra_checksums: vec![],
+            checksum_key: None,
+            checksum_cache: false,
             follow_symlinks: false,
             max_depth: None,
             exclude_patterns: vec![],
             timeout_ms: None,
             threads: None,
             normalize_paths: false,
+            sample_len: DEFAULT_SAMPLE_LEN,
         });
 
         let snapshot = scanner.scan().expect("Scan should succeed");
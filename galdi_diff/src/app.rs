@@ -8,8 +8,8 @@ use chrono::Utc;
 use galdi_core::*;
 use serde::Serialize;
 use std::fs::File;
-use std::io;
-use std::path::Path;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use crate::cli::ToolArgs;
@@ -60,10 +60,10 @@ pub fn run(args: ToolArgs) -> anyhow::Result<RunResult> {
             Meta::new(
                 "galdi_diff",
                 env!("CARGO_PKG_VERSION"),
-                true,  // idempotent
-                false, // mutates
-                true,  // safe
-                true,  // deterministic (same inputs → same outputs)
+                true,             // idempotent
+                args.xattr_cache, // mutates
+                true,             // safe
+                true,             // deterministic (same inputs → same outputs)
                 elapsed.as_millis() as u64,
                 Utc::now(),
             )
@@ -78,58 +78,109 @@ pub fn run(args: ToolArgs) -> anyhow::Result<RunResult> {
         });
     }
 
-    // Load source and target (either from filesystem or JSON)
-    let source_result = load_snapshot(&args.source, &args);
-    let target_result = load_snapshot(&args.target, &args);
+    // Compile --filter before loading anything, so that a typo fails fast
+    let filter = match args.filter.as_deref().map(JqFilter::new).transpose() {
+        Ok(filter) => filter,
+        Err(error) => {
+            let envelope = error_envelope(start, error.to_plumbah_error());
+            let output = emit(&envelope, &args)?;
+            return Ok(RunResult {
+                exit_code: 1,
+                output,
+            });
+        }
+    };
+
+    // Load source and target (either from filesystem or JSON). A stored
+    // snapshot is loaded first so the other side can be hashed to match it.
+    let (source_result, target_result) =
+        if !is_serialized(&args.source) && is_serialized(&args.target) {
+            let target = load_snapshot(&args.target, &args, None);
+            let source = load_snapshot(&args.source, &args, target.as_ref().ok());
+            (source, target)
+        } else {
+            let source = load_snapshot(&args.source, &args, None);
+            let target = load_snapshot(&args.target, &args, source.as_ref().ok());
+            (source, target)
+        };
+    // Line up subtrees of the two sides
+    let source_result = source_result.and_then(|s| extract(s, args.source_prefix.as_deref()));
+    let target_result = target_result.and_then(|s| extract(s, args.target_prefix.as_deref()));
 
     // Build envelope based on results
     let envelope = match (source_result, target_result) {
-        (Ok(source), Ok(target)) => {
-            // Perform diff
-            let engine = DiffEngine::new(DiffOptions {
-                ignore_time: args.ignore_time,
-                ignore_mode: args.ignore_mode,
-                structure_only: args.structure_only,
-            });
-            let diff_result = engine.diff(&source, &target);
-            Envelope::DiffResult(diff_result)
-        }
-        (Err(e), _) | (_, Err(e)) => {
+        (Ok(source), Ok(target)) => match source.check_comparable(&target) {
+            // Checksums keyed differently (or not at all) cannot be compared.
+            Err(error) if !args.structure_only => error_envelope(start, error.to_plumbah_error()),
+            _ => {
+                // Perform diff
+                // Manifests record no times, so there is nothing to compare them on.
+                let manifest = manifest_algorithm(&args.source).is_some()
+                    || manifest_algorithm(&args.target).is_some();
+                // Git records no file times either; its sides carry the commit time.
+                let git = [&args.source, &args.target]
+                    .iter()
+                    .any(|path| path.to_str().and_then(GitSource::parse).is_some());
+                let engine = DiffEngine::new(DiffOptions {
+                    ignore_time: args.ignore_time || manifest || git,
+                    ignore_mode: args.ignore_mode,
+                    structure_only: args.structure_only,
+                    manifest,
+                });
+                let mut diff_result = engine.diff(&source, &target);
+                // Scanned sides may have had their checksums cached in xattrs
+                if let Some(meta) = diff_result.plumbah.meta.as_mut() {
+                    meta.mutates = args.xattr_cache;
+                }
+                Envelope::DiffResult(diff_result)
+            }
+        },
+        (Err(e), _) | (_, Err(e)) => match e.downcast_ref::<ScanError>() {
+            // A stored snapshot whose signature does not check out, whose
+            // format version cannot be read, or without the requested subtree
+            Some(
+                error @ (ScanError::Signature(_)
+                | ScanError::Schema(_)
+                | ScanError::Operation(_)
+                | ScanError::Store(_)),
+            ) => error_envelope(start, error.to_plumbah_error()),
             // Error loading snapshots
-            let elapsed = start.elapsed();
-            Envelope::Error(
-                PlumbahObject::new(
-                    Status::Error,
-                    Meta::new(
-                        "galdi_diff",
-                        env!("CARGO_PKG_VERSION"),
-                        true,
-                        false,
-                        true,
-                        true,
-                        elapsed.as_millis() as u64,
-                        Utc::now(),
-                    )
-                    .with_default_profiles(),
-                )
-                .with_errors(vec![PlumbahError {
+            _ => error_envelope(
+                start,
+                PlumbahError {
                     code: "LOAD_ERROR".to_string(),
                     message: e.to_string(),
                     path: None,
                     recoverable: false,
                     context: None,
-                }]),
-            )
-        }
+                },
+            ),
+        },
     };
 
-    // Output to stdout
-    let output = if args.human {
-        format_human(&envelope)?
-    } else {
-        serde_json::to_string_pretty(&envelope)?
+    // Run --filter over a diff report; should the filter fail, its error is reported instead
+    let envelope = match (envelope, &filter) {
+        (Envelope::DiffResult(result), Some(filter)) => {
+            let exit_code = match result.plumbah.status {
+                Status::Ok => 0,
+                Status::Partial => 2,
+                Status::Error => 1,
+            };
+            match filter_report(&result, filter) {
+                Ok(output) => {
+                    return Ok(RunResult {
+                        exit_code,
+                        output: print_or_write(output, args.output.as_deref())?,
+                    });
+                }
+                Err(error) => error_envelope(start, error.to_plumbah_error()),
+            }
+        }
+        (envelope, _) => envelope,
     };
 
+    let output = emit(&envelope, &args)?;
+
     // Compute exit code from envelope
     let exit_code = match envelope {
         Envelope::DiffResult(ref result) => match result.plumbah.status {
@@ -147,6 +198,35 @@ pub fn run(args: ToolArgs) -> anyhow::Result<RunResult> {
     Ok(RunResult { exit_code, output })
 }
 
+/// The subtree of `snapshot` under `prefix`, if one is given.
+fn extract(snapshot: Snapshot, prefix: Option<&Path>) -> anyhow::Result<Snapshot> {
+    match prefix {
+        Some(prefix) => Ok(snapshot.subtree(prefix)?),
+        None => Ok(snapshot),
+    }
+}
+
+/// Wrap an error that prevented the diff in the output envelope.
+fn error_envelope(start: Instant, error: PlumbahError) -> Envelope {
+    Envelope::Error(
+        PlumbahObject::new(
+            Status::Error,
+            Meta::new(
+                "galdi_diff",
+                env!("CARGO_PKG_VERSION"),
+                true,
+                false,
+                true,
+                true,
+                start.elapsed().as_millis() as u64,
+                Utc::now(),
+            )
+            .with_default_profiles(),
+        )
+        .with_errors(vec![error]),
+    )
+}
+
 /// Formats the output for human consumption.
 ///
 /// Currently, this just returns the pretty-printed JSON implementation,
@@ -155,37 +235,196 @@ fn format_human(envelope: &Envelope) -> Result<String, serde_json::Error> {
     serde_json::to_string_pretty(&envelope)
 }
 
-/// Load a snapshot from a path (JSON file, stdin, or live filesystem).
+/// Render `envelope` for printing, or write it out directly and return
+/// nothing to print: to the output file when given, and to stdout in the
+/// binary and compressed formats.
+fn emit(envelope: &Envelope, args: &ToolArgs) -> anyhow::Result<String> {
+    if args.human
+        && args.output.is_none()
+        && args.format == Encoding::Json
+        && args.compress == Compression::None
+    {
+        return Ok(format_human(envelope)?);
+    }
+    Ok(render_or_write(
+        envelope,
+        args.output.as_deref(),
+        args.format,
+        args.compress,
+    )?)
+}
+
+/// The outputs of `filter` over the diff report, pretty-printed one after
+/// the other like `jq` prints them.
+fn filter_report(result: &DiffResult, filter: &JqFilter) -> Result<String, ScanError> {
+    let document =
+        serde_json::to_value(result).map_err(|err| ScanError::Filter(err.to_string()))?;
+    Ok(filter
+        .apply(document)?
+        .iter()
+        .map(|output| format!("{:#}", output))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Load a snapshot from a path (stored snapshot, stdin, or live filesystem).
 ///
 /// This function detects the input type based on the path:
-/// - "-" → Read JSON from stdin
-/// - "*.json" → Read JSON from file
+/// - "-" → Read a stored snapshot or JSONL stream from stdin
+/// - "@name" → Load a snapshot from the store, by tag, id prefix or as `@latest`
+/// - "*.tar" → Snapshot the archive members without extracting them
+/// - "SHA256SUMS", "*.sha256", "B3SUMS", ... → Read a checksum manifest, rooted
+///   at the directory that contains it
+/// - "git:<rev>[:<subdir>]" → Snapshot a commit or tree of the current repository
+/// - Files that start like a JSON, CBOR or MessagePack document, possibly
+///   zstd or gzip compressed (and any "*.json" or "*.jsonl") → Read a stored
+///   snapshot, or rebuild one from a JSONL stream
 /// - Otherwise → Scan live filesystem
 ///
-/// The `is_serialized` flag is set to indicate whether the snapshot
-/// came from a JSON file (vs. live scan), which affects diff behavior.
-fn load_snapshot(path: &Path, args: &ToolArgs) -> anyhow::Result<Snapshot> {
-    if path == Path::new("-") {
-        // Read from stdin
-        let stdin = io::stdin();
-        let snapshot: Snapshot = serde_json::from_reader(stdin)?;
-        Ok(snapshot)
-    } else if path.extension().and_then(|s| s.to_str()) == Some("json") {
-        // Load JSON snapshot
+/// Sides that are scanned use `--checksum` when given. Otherwise they follow
+/// `counterpart`, the already loaded other side, so that a live tree diffed
+/// against a SHA-256 snapshot is hashed with SHA-256 (and its extra checksums),
+/// and sampled with the sample length it recorded unless `--sample-len` is given.
+/// Keyed algorithms take their key from `--key-file` or `--key-env`.
+///
+/// With `--require-signature`, stored snapshots are only returned once their
+/// signature has been checked against the given public key. Stored snapshots
+/// of an older format version are upgraded to the current one.
+pub fn load_snapshot(
+    path: &Path,
+    args: &ToolArgs,
+    counterpart: Option<&Snapshot>,
+) -> anyhow::Result<Snapshot> {
+    let (checksum, extra_checksums) = match (args.checksum, counterpart) {
+        (Some(checksum), _) => (checksum, vec![]),
+        (None, Some(other)) => (other.checksum_algorithm, other.extra_checksums.clone()),
+        (None, None) => (ChecksumAlgorithm::XXH3_64, vec![]),
+    };
+    let sample_len = args
+        .sample_len
+        .or(counterpart.and_then(|other| other.sample_len))
+        .unwrap_or(DEFAULT_SAMPLE_LEN);
+
+    if let Some(reference) = path.to_str().filter(|_| SnapshotStore::is_reference(path)) {
+        // Reassemble a snapshot kept in the store
+        let store = SnapshotStore::locate(args.store.as_deref());
+        match &args.require_signature {
+            Some(key_file) => {
+                let key = VerifyingKey::from_file(key_file)?;
+                let document = store.load_document(reference)?;
+                verify_document(&document, None, &key)?;
+                Ok(upgraded_snapshot(document)?)
+            }
+            None => Ok(store.load(reference)?),
+        }
+    } else if let Some(source) = path.to_str().and_then(GitSource::parse) {
+        // Snapshot a git object straight from the object database
+        Ok(Snapshot::from_git(
+            &git_repo(path, args),
+            &source,
+            checksum,
+        )?)
+    } else if let Some(key_file) = &args.require_signature
+        && is_serialized(path)
+    {
+        // Only trust a stored snapshot once its signature checks out
+        if manifest_algorithm(path).is_some() {
+            anyhow::bail!("{}: checksum manifests cannot be signed", path.display());
+        }
+        let key = VerifyingKey::from_file(key_file)?;
+        let document: serde_json::Value = if path == Path::new("-") {
+            read_stored(io::stdin().lock())?
+        } else {
+            read_stored(File::open(path)?)?
+        };
+        verify_document_at(&document, path, &key)?;
+        Ok(upgraded_snapshot(document)?)
+    } else if path == Path::new("-") {
+        // Read from stdin, in whichever encoding it comes
+        Ok(upgraded_snapshot(read_stored(io::stdin().lock())?)?)
+    } else if is_stored_document(path) {
+        // Load a stored snapshot, in whichever encoding it was written
+        Ok(upgraded_snapshot(read_stored(File::open(path)?)?)?)
+    } else if path.extension().and_then(|s| s.to_str()) == Some("tar") {
+        // Snapshot a tar archive straight from its headers and member data
+        let file = File::open(path)?;
+        Ok(Snapshot::from_tar_reader(file, path, checksum)?)
+    } else if let Some(algorithm) = manifest_algorithm(path) {
+        // Read a sha256sum-style manifest; its paths are relative to its directory
         let file = File::open(path)?;
-        Ok(serde_json::from_reader(file)?)
+        let root = path.parent().unwrap_or(Path::new(""));
+        Ok(Snapshot::from_manifest(
+            BufReader::new(file),
+            root,
+            Some(algorithm),
+        )?)
     } else {
-        // Scan live filesystem
+        // Scan live filesystem, keyed like the counterpart if need be
+        let keyed = checksum.is_keyed() || extra_checksums.iter().any(|a| a.is_keyed());
+        let checksum_key = if keyed {
+            let key = ChecksumKey::load(args.key_file.as_deref(), args.key_env.as_deref())?;
+            Some(
+                key.ok_or_else(|| anyhow::anyhow!("keyed checksums need --key-file or --key-env"))?,
+            )
+        } else {
+            None
+        };
         let scanner = Scanner::new(ScanOptions {
             root: path.to_path_buf(),
-            checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            checksum_algorithm: checksum,
+            extra_checksums,
+            checksum_key,
+            checksum_cache: args.xattr_cache,
             follow_symlinks: args.follow_symlinks,
             max_depth: args.max_depth,
             exclude_patterns: args.exclude.clone(),
             timeout_ms: args.timeout_ms,
             threads: None, // Use default auto-detect
             normalize_paths: args.normalize_paths,
+            sample_len,
         });
         Ok(scanner.scan()?)
     }
 }
+
+/// The repository a `git:` side at `path` is read from: `--repo`, else the
+/// one holding the other side when that is a directory in one, else the
+/// current one.
+fn git_repo(path: &Path, args: &ToolArgs) -> PathBuf {
+    let other = if path == args.source {
+        &args.target
+    } else {
+        &args.source
+    };
+    args.repo
+        .clone()
+        .or_else(|| repository_of(other))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Whether `path` names a stored snapshot (document file, stdin or checksum
+/// manifest) rather than something that is scanned on the fly.
+fn is_serialized(path: &Path) -> bool {
+    path == Path::new("-")
+        || SnapshotStore::is_reference(path)
+        || is_stored_document(path)
+        || manifest_algorithm(path).is_some()
+}
+
+/// Whether `path` is a file holding a snapshot document, judged by its first
+/// bytes. "*.json" and "*.jsonl" files always count, so that a broken one is
+/// reported rather than scanned.
+fn is_stored_document(path: &Path) -> bool {
+    if matches!(
+        path.extension().and_then(|s| s.to_str()),
+        Some("json" | "jsonl")
+    ) {
+        return true;
+    }
+    if path.extension().and_then(|s| s.to_str()) == Some("tar") || !path.is_file() {
+        return false;
+    }
+    File::open(path)
+        .and_then(detect_format)
+        .is_ok_and(|format| format.is_some())
+}
@@ -13,7 +13,7 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use galdi_core::ChecksumAlgorithm;
+use galdi_core::{ChecksumAlgorithm, Compression, Encoding};
 
 #[derive(Parser, Debug)]
 #[command(name = "galdi_diff")]
@@ -28,10 +28,35 @@ pub struct ToolArgs {
 
     pub output: Option<PathBuf>,
 
-    /// Checksum algorithm to use for file content.
-    /// Defaults to `xxh3_64`. See `galdi_core::ChecksumAlgorithm`.
-    #[arg(long, default_value = "xxh3_64")]
-    pub checksum: ChecksumAlgorithm,
+    /// Checksum algorithm for sides that are scanned rather than loaded.
+    /// Defaults to the algorithm of the other side when that is a stored
+    /// snapshot, otherwise `xxh3_64`. See `galdi_core::ChecksumAlgorithm`.
+    #[arg(long)]
+    pub checksum: Option<ChecksumAlgorithm>,
+
+    /// Bytes per sample of sampled algorithms (`xxh3_64s`) for sides that are
+    /// scanned. Defaults to what the other side recorded when that is a stored
+    /// snapshot, otherwise 64 KiB.
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub sample_len: Option<u64>,
+
+    /// Read the key for keyed checksums (`blake3_keyed`, `hmac_sha256`) from this file.
+    #[arg(long, value_name = "PATH", conflicts_with = "key_env")]
+    pub key_file: Option<PathBuf>,
+
+    /// Read the key for keyed checksums from this environment variable.
+    #[arg(long, value_name = "VAR")]
+    pub key_env: Option<String>,
+
+    /// Reuse checksums cached in `user.galdi.<algorithm>` extended attributes
+    /// while size and mtime are unchanged, and cache fresh ones.
+    #[arg(long)]
+    pub xattr_cache: bool,
+
+    /// Only accept stored snapshots signed with the Ed25519 public key in this
+    /// file, embedded or as a detached `<snapshot>.sig`.
+    #[arg(long, value_name = "PUBLIC_KEY")]
+    pub require_signature: Option<PathBuf>,
 
     /// Follow symbolic links when scanning.
     #[arg(long)]
@@ -55,6 +80,19 @@ pub struct ToolArgs {
     #[arg(long)]
     pub human: bool,
 
+    /// Encode the report as `json`, `cbor` or `msgpack`.
+    #[arg(long, default_value = "json", conflicts_with = "human")]
+    pub format: Encoding,
+
+    /// Compress the report with `zstd` or `gzip`.
+    #[arg(long, default_value = "none", conflicts_with = "human")]
+    pub compress: Compression,
+
+    /// Run this jq expression over the report, in-process. Error documents
+    /// are never filtered.
+    #[arg(long, value_name = "EXPR", conflicts_with_all = ["human", "format", "compress"])]
+    pub filter: Option<String>,
+
     /// Ignore timestamp differences when comparing entries.
     #[arg(long)]
     pub ignore_time: bool,
@@ -67,6 +105,25 @@ pub struct ToolArgs {
     #[arg(long)]
     pub structure_only: bool,
 
+    /// Compare only this subtree of the source, re-rooted at it: e.g. `opt/app`
+    /// of a whole-host snapshot, to line up with a snapshot of `/opt/app`.
+    #[arg(long, value_name = "PATH")]
+    pub source_prefix: Option<PathBuf>,
+
+    /// Compare only this subtree of the target, re-rooted at it.
+    #[arg(long, value_name = "PATH")]
+    pub target_prefix: Option<PathBuf>,
+
+    /// Store that `@name` sides are loaded from; `$GALDI_STORE`, or else
+    /// `.galdi`, if omitted.
+    #[arg(long, value_name = "DIR")]
+    pub store: Option<PathBuf>,
+
+    /// Repository that `git:` sides are read from. Defaults to the one holding
+    /// the other side when that is a directory, otherwise the current one.
+    #[arg(long, value_name = "DIR")]
+    pub repo: Option<PathBuf>,
+
     /// Timeout in milliseconds for each snapshot operation.
     #[arg(long)]
     pub timeout_ms: Option<u64>,
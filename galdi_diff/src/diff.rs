@@ -1,69 +1,62 @@
-use std::{
-    collections::{BTreeSet, HashMap},
-    time::Instant,
-};
+use std::{cmp::Ordering, time::Instant};
 
+pub use galdi_core::DiffOptions;
 use galdi_core::{
-    AttributeChange, ChangeType, DiffResult, DiffSummary, Difference, PlumbahObject, Snapshot,
+    ChangeType, ContentMatch, DiffResult, DiffSummary, Difference, PlumbahObject, Snapshot,
     SnapshotEntry,
 };
 
 pub struct DiffEngine {
-    ignore_time: bool,
-    ignore_mode: bool,
-    structure_only: bool,
-}
-
-pub struct DiffOptions {
-    pub ignore_time: bool,
-    pub ignore_mode: bool,
-    pub structure_only: bool,
+    options: DiffOptions,
 }
 
 impl DiffEngine {
     pub fn new(options: DiffOptions) -> Self {
-        Self {
-            ignore_time: options.ignore_time,
-            ignore_mode: options.ignore_mode,
-            structure_only: options.structure_only,
-        }
+        Self { options }
     }
     pub fn diff(&self, source: &Snapshot, target: &Snapshot) -> DiffResult {
         let start = Instant::now();
         let mut differences = Vec::new();
         let mut summary = DiffSummary::default();
 
-        // Create lookup maps
-        let source_map: HashMap<_, _> = source
-            .entries
-            .iter()
-            .map(|e| (e.path.clone(), e.clone()))
-            .collect();
-        let target_map: HashMap<_, _> = target
-            .entries
-            .iter()
-            .map(|e| (e.path.clone(), e.clone()))
-            .collect();
-
-        // Find all unique paths
-        let all_paths: BTreeSet<_> = source_map.keys().chain(target_map.keys()).collect();
-
-        for path in all_paths {
-            match (source_map.get(path), target_map.get(path)) {
-                (Some(src), Some(tgt)) => {
+        // Walk both sides in path order, like a merge
+        let source_index = source.index();
+        let target_index = target.index();
+        let mut source_entries = source_index.iter().peekable();
+        let mut target_entries = target_index.iter().peekable();
+
+        loop {
+            let order = match (source_entries.peek(), target_entries.peek()) {
+                (Some(src), Some(tgt)) => src.path.cmp(&tgt.path),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            match order {
+                Ordering::Equal => {
                     // Entry exists in both
+                    let (src, tgt) = (
+                        source_entries.next().unwrap(),
+                        target_entries.next().unwrap(),
+                    );
                     if let Some(diff) = self.compare_entries(src, tgt) {
                         summary.modified += 1;
                         differences.push(diff);
                     } else {
                         summary.unchanged += 1;
+                        if !self.options.structure_only
+                            && src.content_match(tgt) == ContentMatch::ProbablySame
+                        {
+                            summary.probably_unchanged += 1;
+                        }
                     }
                 }
-                (Some(src), None) => {
+                Ordering::Less => {
                     // Removed
+                    let src = source_entries.next().unwrap();
                     summary.removed += 1;
                     differences.push(Difference {
-                        path: path.clone(),
+                        path: src.path.clone(),
                         change_type: ChangeType::Removed,
                         changes: vec![],
                         source: Some(src.clone()),
@@ -71,11 +64,12 @@ impl DiffEngine {
                         error: None,
                     });
                 }
-                (None, Some(tgt)) => {
+                Ordering::Greater => {
                     // Added
+                    let tgt = target_entries.next().unwrap();
                     summary.added += 1;
                     differences.push(Difference {
-                        path: path.clone(),
+                        path: tgt.path.clone(),
                         change_type: ChangeType::Added,
                         changes: vec![],
                         source: None,
@@ -83,7 +77,6 @@ impl DiffEngine {
                         error: None,
                     });
                 }
-                (None, None) => unreachable!(),
             }
         }
 
@@ -105,40 +98,14 @@ impl DiffEngine {
                 ),
             ),
             identical: differences.is_empty(),
+            probable: differences.is_empty() && summary.probably_unchanged > 0,
             summary,
             differences,
         }
     }
 
     fn compare_entries(&self, src: &SnapshotEntry, tgt: &SnapshotEntry) -> Option<Difference> {
-        let mut changes = Vec::new();
-
-        if src.entry_type != tgt.entry_type {
-            changes.push(AttributeChange::Type);
-        }
-
-        if !self.structure_only {
-            if src.checksum != tgt.checksum {
-                changes.push(AttributeChange::Content);
-            }
-
-            if !self.ignore_mode && src.mode != tgt.mode {
-                changes.push(AttributeChange::Mode);
-            }
-
-            if !self.ignore_time && src.mtime != tgt.mtime {
-                changes.push(AttributeChange::Mtime);
-            }
-
-            if src.size != tgt.size {
-                changes.push(AttributeChange::Size);
-            }
-
-            if src.target != tgt.target {
-                changes.push(AttributeChange::Target);
-            }
-        }
-
+        let changes = src.changes(tgt, &self.options);
         if changes.is_empty() {
             None
         } else {
@@ -153,3 +120,215 @@ impl DiffEngine {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galdi_core::{AttributeChange, ChecksumAlgorithm, ContentType, ElfInfo, EntryType};
+
+    fn engine() -> DiffEngine {
+        DiffEngine::new(DiffOptions {
+            ignore_time: false,
+            ignore_mode: false,
+            structure_only: false,
+            manifest: false,
+        })
+    }
+
+    #[test]
+    fn test_content_type_change_is_reported() {
+        let source = Snapshot::test_of(vec![SnapshotEntry {
+            content_type: Some(ContentType::Text),
+            ..SnapshotEntry::test_file("notes.txt", "xxh3_64:01")
+        }]);
+        let target = Snapshot::test_of(vec![SnapshotEntry {
+            content_type: Some(ContentType::Elf),
+            ..SnapshotEntry::test_file("notes.txt", "xxh3_64:02")
+        }]);
+
+        let result = engine().diff(&source, &target);
+        let changes = &result.differences[0].changes;
+        assert!(matches!(
+            changes[..],
+            [AttributeChange::Content, AttributeChange::ContentType]
+        ));
+    }
+
+    #[test]
+    fn test_directory_size_recorded_by_older_snapshots_is_ignored() {
+        let mut old = SnapshotEntry::test_file("etc", "xxh3_64:01");
+        old.entry_type = EntryType::Directory;
+        old.checksum = None;
+        old.size = Some(4096);
+        let mut new = old.clone();
+        new.size = None;
+
+        let result = engine().diff(&Snapshot::test_of(vec![old]), &Snapshot::test_of(vec![new]));
+        assert!(result.differences.is_empty());
+    }
+
+    #[test]
+    fn test_elf_build_id_and_library_changes_are_reported() {
+        let elf = ElfInfo {
+            build_id: Some("aa".to_string()),
+            arch: "x86_64".to_string(),
+            interpreter: Some("/lib64/ld-linux-x86-64.so.2".to_string()),
+            needed: vec!["libc.so.6".to_string()],
+            rpath: vec![],
+            runpath: vec![],
+            stripped: true,
+            pie: true,
+        };
+        let mut src = SnapshotEntry {
+            content_type: Some(ContentType::Elf),
+            ..SnapshotEntry::test_file("bin/app", "xxh3_64:01")
+        };
+        src.elf = Some(elf.clone());
+        let mut tgt = SnapshotEntry {
+            content_type: Some(ContentType::Elf),
+            ..SnapshotEntry::test_file("bin/app", "xxh3_64:02")
+        };
+        tgt.elf = Some(ElfInfo {
+            build_id: Some("bb".to_string()),
+            needed: vec!["libc.so.6".to_string(), "libevil.so".to_string()],
+            runpath: vec!["/tmp".to_string()],
+            ..elf
+        });
+
+        let result = engine().diff(&Snapshot::test_of(vec![src]), &Snapshot::test_of(vec![tgt]));
+        let changes = &result.differences[0].changes;
+        assert!(matches!(
+            changes[..],
+            [
+                AttributeChange::Content,
+                AttributeChange::BuildId,
+                AttributeChange::Libraries,
+                AttributeChange::Rpath
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_content_compared_on_common_algorithm() {
+        let src = SnapshotEntry::test_file("data.bin", "xxh3_64:01");
+        let mut tgt = SnapshotEntry::test_file("data.bin", "sha256:aa");
+        tgt.checksums
+            .insert(ChecksumAlgorithm::XXH3_64, "xxh3_64:01".to_string());
+        assert!(
+            engine()
+                .diff(&Snapshot::test_of(vec![src]), &Snapshot::test_of(vec![tgt]))
+                .identical
+        );
+
+        let mut src = SnapshotEntry::test_file("data.bin", "xxh3_64:01");
+        src.checksums
+            .insert(ChecksumAlgorithm::Sha256, "sha256:bb".to_string());
+        let mut tgt = SnapshotEntry::test_file("data.bin", "sha256:aa");
+        tgt.checksums
+            .insert(ChecksumAlgorithm::XXH3_64, "xxh3_64:01".to_string());
+        let result = engine().diff(&Snapshot::test_of(vec![src]), &Snapshot::test_of(vec![tgt]));
+        assert!(matches!(
+            result.differences[0].changes[..],
+            [AttributeChange::Content]
+        ));
+    }
+
+    #[test]
+    fn test_no_common_algorithm_is_a_content_change() {
+        let src = SnapshotEntry::test_file("data.bin", "xxh3_64:01");
+        let tgt = SnapshotEntry::test_file("data.bin", "sha256:01");
+        assert!(
+            !engine()
+                .diff(&Snapshot::test_of(vec![src]), &Snapshot::test_of(vec![tgt]))
+                .identical
+        );
+    }
+
+    #[test]
+    fn test_missing_content_type_is_not_a_change() {
+        let source = Snapshot::test_of(vec![SnapshotEntry::test_file("notes.txt", "xxh3_64:01")]);
+        let target = Snapshot::test_of(vec![SnapshotEntry {
+            content_type: Some(ContentType::Text),
+            ..SnapshotEntry::test_file("notes.txt", "xxh3_64:01")
+        }]);
+
+        assert!(engine().diff(&source, &target).identical);
+    }
+
+    #[test]
+    fn test_unrecorded_size_and_mode_are_not_changes_against_a_manifest() {
+        let manifest = DiffEngine::new(DiffOptions {
+            ignore_time: true,
+            ignore_mode: false,
+            structure_only: false,
+            manifest: true,
+        });
+        // Entries read from a checksum manifest carry neither size nor mode.
+        let mut manifest_entry = SnapshotEntry::test_file("notes.txt", "sha256:01");
+        manifest_entry.size = None;
+        manifest_entry.mode = None;
+        let source = Snapshot::test_of(vec![manifest_entry]);
+        let target = Snapshot::test_of(vec![SnapshotEntry::test_file("notes.txt", "sha256:01")]);
+        assert!(manifest.diff(&source, &target).identical);
+
+        let mut resized = SnapshotEntry::test_file("notes.txt", "sha256:01");
+        resized.size = Some(11);
+        let target = Snapshot::test_of(vec![resized]);
+        let source = Snapshot::test_of(vec![SnapshotEntry::test_file("notes.txt", "sha256:01")]);
+        assert!(!manifest.diff(&source, &target).identical);
+    }
+
+    #[test]
+    fn test_attribute_recorded_on_one_side_is_a_change() {
+        let mut unrecorded = SnapshotEntry::test_file("notes.txt", "sha256:01");
+        unrecorded.size = None;
+        unrecorded.mode = None;
+        let source = Snapshot::test_of(vec![unrecorded]);
+        let target = Snapshot::test_of(vec![SnapshotEntry::test_file("notes.txt", "sha256:01")]);
+        let result = engine().diff(&source, &target);
+        assert!(matches!(
+            result.differences[0].changes[..],
+            [AttributeChange::Mode, AttributeChange::Size]
+        ));
+    }
+
+    #[test]
+    fn test_sampled_match_is_only_probable() {
+        let source = Snapshot::test_of(vec![
+            SnapshotEntry::test_file("scratch/a.bin", "xxh3_64s:01"),
+            SnapshotEntry::test_file("scratch/b.bin", "xxh3_64s:02"),
+        ]);
+        let target = Snapshot::test_of(vec![
+            SnapshotEntry::test_file("scratch/a.bin", "xxh3_64s:01"),
+            SnapshotEntry::test_file("scratch/b.bin", "xxh3_64s:03"),
+        ]);
+        let result = engine().diff(&source, &target);
+        assert_eq!(result.summary.unchanged, 1);
+        assert_eq!(result.summary.probably_unchanged, 1);
+        assert!(!result.probable);
+        assert!(matches!(
+            result.differences[0].changes[..],
+            [AttributeChange::Content]
+        ));
+
+        // Without differences, the snapshots are only probably identical
+        let mut src = SnapshotEntry::test_file("scratch/a.bin", "xxh3_64s:01");
+        let result = engine().diff(
+            &Snapshot::test_of(vec![src.clone()]),
+            &Snapshot::test_of(vec![src.clone()]),
+        );
+        assert!(result.identical);
+        assert!(result.probable);
+
+        // A full checksum in common makes the match certain
+        src.checksums
+            .insert(ChecksumAlgorithm::Sha256, "sha256:aa".to_string());
+        let mut tgt = src.clone();
+        tgt.checksums
+            .insert(ChecksumAlgorithm::Sha256, "sha256:aa".to_string());
+        let result = engine().diff(&Snapshot::test_of(vec![src]), &Snapshot::test_of(vec![tgt]));
+        assert_eq!(result.summary.probably_unchanged, 0);
+        assert!(result.identical);
+        assert!(!result.probable);
+    }
+}
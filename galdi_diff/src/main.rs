@@ -22,6 +22,9 @@ use cli::ToolArgs;
 fn main() -> anyhow::Result<()> {
     let args = ToolArgs::parse();
     let result = app::run(args)?;
-    println!("{}", result.output);
+    // Output written to a file or streamed leaves nothing to print
+    if !result.output.is_empty() {
+        println!("{}", result.output);
+    }
     std::process::exit(result.exit_code);
 }
@@ -0,0 +1,147 @@
+// Integration tests for how galdi_diff loads its two sides
+
+use clap::Parser;
+use galdi_diff::app;
+use galdi_diff::cli::ToolArgs;
+
+/// First bytes of a PNG file; 0x89 also opens a MessagePack map.
+const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+
+#[test]
+fn test_binary_files_are_scanned_not_loaded() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.png");
+    let b = dir.path().join("b.png");
+    std::fs::write(&a, [PNG_HEADER, b"one"].concat()).unwrap();
+    std::fs::write(&b, [PNG_HEADER, b"two"].concat()).unwrap();
+
+    let args = ToolArgs::parse_from(["galdi_diff".as_ref(), a.as_os_str(), b.as_os_str()]);
+    let result = app::run(args).unwrap();
+
+    let report: serde_json::Value = serde_json::from_str(&result.output).unwrap();
+    assert_eq!(report["$plumbah"]["status"], "ok", "{}", result.output);
+    assert_eq!(report["identical"], false);
+    assert_eq!(report["summary"]["modified"], 1);
+}
+
+#[test]
+fn test_live_side_is_sampled_like_the_stored_snapshot() {
+    use galdi_core::{ChecksumAlgorithm, ScanOptions, Scanner};
+
+    let dir = tempfile::tempdir().unwrap();
+    let tree = dir.path().join("tree");
+    std::fs::create_dir(&tree).unwrap();
+    std::fs::write(tree.join("big.bin"), vec![7u8; 4096]).unwrap();
+    let snapshot = Scanner::new(ScanOptions {
+        root: tree.clone(),
+        checksum_algorithm: ChecksumAlgorithm::XXH3_64Sampled,
+        extra_checksums: vec![],
+        checksum_key: None,
+        checksum_cache: false,
+        follow_symlinks: false,
+        max_depth: None,
+        exclude_patterns: vec![],
+        timeout_ms: None,
+        threads: Some(1),
+        normalize_paths: false,
+        sample_len: 16,
+    })
+    .scan()
+    .unwrap();
+    assert_eq!(snapshot.sample_len, Some(16));
+    let stored = dir.path().join("tree.json");
+    std::fs::write(&stored, serde_json::to_string(&snapshot).unwrap()).unwrap();
+
+    let diff = |extra: &[&str]| {
+        let mut args = vec![
+            "galdi_diff",
+            stored.to_str().unwrap(),
+            tree.to_str().unwrap(),
+        ];
+        args.extend(extra);
+        let result = app::run(ToolArgs::parse_from(args)).unwrap();
+        serde_json::from_str::<serde_json::Value>(&result.output).unwrap()
+    };
+
+    let report = diff(&[]);
+    assert_eq!(report["identical"], true, "{}", report);
+    assert_eq!(report["probable"], true);
+
+    // Checksums sampled with another length cannot be compared
+    let report = diff(&["--sample-len", "32"]);
+    assert_eq!(report["errors"][0]["code"], "OPERATION_ERROR", "{}", report);
+}
+
+/// A repository in a temporary directory with `README` committed, which is
+/// kept with the directory.
+fn committed_repository() -> tempfile::TempDir {
+    use std::process::Command;
+
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args([
+                "-c",
+                "user.name=galdi",
+                "-c",
+                "user.email=galdi@example.com",
+            ])
+            .args(["-c", "commit.gpgsign=false"])
+            .args(args)
+            .status()
+            .expect("git should be installed");
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    std::fs::write(repo.join("README"), b"readme\n").unwrap();
+    git(&["init", "-q"]);
+    git(&["add", "README"]);
+    git(&["commit", "-q", "-m", "initial"]);
+    dir
+}
+
+#[test]
+fn test_git_side_is_read_from_the_other_sides_repository() {
+    let dir = committed_repository();
+    let repo = dir.path();
+
+    // The current directory is this crate, not the repository above
+    let args = ToolArgs::parse_from([
+        "galdi_diff".as_ref(),
+        "git:HEAD".as_ref(),
+        repo.as_os_str(),
+        "--ignore-time".as_ref(),
+    ]);
+    let report: serde_json::Value = serde_json::from_str(&app::run(args).unwrap().output).unwrap();
+    assert_eq!(report["$plumbah"]["status"], "ok", "{}", report);
+    assert_eq!(report["summary"]["modified"], 0, "{}", report);
+    assert_eq!(report["summary"]["unchanged"], 1, "{}", report);
+}
+
+#[test]
+fn test_git_side_is_compared_without_times() {
+    let dir = committed_repository();
+    let repo = dir.path();
+    // Checked out long before the commit time git records for every file
+    let readme = std::fs::File::options()
+        .write(true)
+        .open(repo.join("README"))
+        .unwrap();
+    readme
+        .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000))
+        .unwrap();
+
+    for (source, target) in [
+        ("git:HEAD".as_ref(), repo.as_os_str()),
+        (repo.as_os_str(), "git:HEAD".as_ref()),
+    ] {
+        let args = ToolArgs::parse_from(["galdi_diff".as_ref(), source, target]);
+        let report: serde_json::Value =
+            serde_json::from_str(&app::run(args).unwrap().output).unwrap();
+        assert_eq!(report["$plumbah"]["status"], "ok", "{}", report);
+        assert_eq!(report["summary"]["modified"], 0, "{}", report);
+        assert_eq!(report["summary"]["unchanged"], 1, "{}", report);
+    }
+}
@@ -51,8 +51,13 @@ fn arbitrary_snapshot() -> impl Strategy<Value = Snapshot> {
                         None
                     },
                     mode: Some("644".to_string()),
+                    uid: None,
+                    gid: None,
                     mtime: Utc::now(),
                     checksum,
+                    checksums: Default::default(),
+                    content_type: None,
+                    elf: None,
                     target,
                 });
             }
@@ -73,6 +78,9 @@ fn arbitrary_snapshot() -> impl Strategy<Value = Snapshot> {
                 version: "1.0".to_string(),
                 root: PathBuf::from(root),
                 checksum_algorithm: algo,
+                extra_checksums: vec![],
+                checksum_key_id: None,
+                sample_len: None,
                 count: entries.len(),
                 entries,
             }
@@ -93,6 +101,7 @@ proptest! {
             ignore_time: false,
             ignore_mode: false,
             structure_only: false,
+            manifest: false,
         });
 
         let diff_result = engine.diff(&snapshot, &snapshot);
@@ -120,6 +129,7 @@ proptest! {
             ignore_time: false,
             ignore_mode: false,
             structure_only: false,
+            manifest: false,
         });
 
         let diff_result = engine.diff(&snapshot, &snapshot);
@@ -136,6 +146,7 @@ proptest! {
             ignore_time: false,
             ignore_mode: false,
             structure_only: false,
+            manifest: false,
         });
 
         let diff_result = engine.diff(&snapshot, &snapshot);
@@ -157,6 +168,7 @@ proptest! {
             ignore_time: false,
             ignore_mode: false,
             structure_only: false,
+            manifest: false,
         });
 
         let diff_result = engine.diff(&snapshot, &snapshot);
@@ -187,8 +199,13 @@ proptest! {
                     entry_type: EntryType::File,
                     size: Some(i as u64 * 100),
                     mode: Some("644".to_string()),
+                    uid: None,
+                    gid: None,
                     mtime: Utc::now(),
                     checksum: Some(format!("xxh3_64:{:016x}", i as u64)),
+                    checksums: Default::default(),
+                    content_type: None,
+                    elf: None,
                     target: None,
                 });
             }
@@ -209,6 +226,9 @@ proptest! {
                 version: "1.0".to_string(),
                 root: PathBuf::from("test"),
                 checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+                extra_checksums: vec![],
+                checksum_key_id: None,
+                sample_len: None,
                 count: entries.len(),
                 entries,
             }
@@ -221,6 +241,7 @@ proptest! {
             ignore_time: false,
             ignore_mode: false,
             structure_only: false,
+            manifest: false,
         });
 
         let diff_ab = engine.diff(&snapshot_a, &snapshot_b);
@@ -254,6 +275,9 @@ proptest! {
             version: "1.0".to_string(),
             root: PathBuf::from("test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: 0,
             entries: vec![],
         };
@@ -266,8 +290,13 @@ proptest! {
                 entry_type: EntryType::File,
                 size: Some(100),
                 mode: Some("644".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: Some(format!("xxh3_64:{:016x}", i as u64)),
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             });
         }
@@ -278,6 +307,9 @@ proptest! {
             version: "1.0".to_string(),
             root: PathBuf::from("test"),
             checksum_algorithm: ChecksumAlgorithm::XXH3_64,
+            extra_checksums: vec![],
+            checksum_key_id: None,
+            sample_len: None,
             count: entries.len(),
             entries,
         };
@@ -286,6 +318,7 @@ proptest! {
             ignore_time: false,
             ignore_mode: false,
             structure_only: false,
+            manifest: false,
         });
 
         let diff_result = engine.diff(&empty_snapshot, &non_empty_snapshot);
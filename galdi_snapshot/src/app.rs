@@ -7,6 +7,9 @@
 use chrono::Utc;
 use galdi_core::*;
 use serde::Serialize;
+use std::fs::File;
+use std::io;
+use std::path::Path;
 use std::time::Instant;
 
 use crate::cli::ToolArgs;
@@ -30,9 +33,9 @@ pub fn run(args: ToolArgs) -> anyhow::Result<RunResult> {
             Meta::new(
                 "galdi_snapshot",
                 env!("CARGO_PKG_VERSION"),
-                true,  // idempotent
-                false, // mutates
-                true,  // safe
+                true,             // idempotent
+                args.xattr_cache, // mutates
+                true,             // safe
                 true, // deterministic (paths are required input, and output depends only on these paths, so output depends only on input, i.e. deterministic)
                 elapsed.as_millis() as u64,
                 Utc::now(),
@@ -48,31 +51,134 @@ pub fn run(args: ToolArgs) -> anyhow::Result<RunResult> {
         });
     }
 
+    // Compile --filter before anything else, so that a typo does not wait for a scan
+    let filter = match args.filter.as_deref().map(JqFilter::new).transpose() {
+        Ok(filter) => filter,
+        Err(e) => return render_json(Err(e), start, &args, None),
+    };
+
+    let git_source = args
+        .path
+        .as_deref()
+        .and_then(Path::to_str)
+        .and_then(GitSource::parse);
+    if !args.extra_checksum.is_empty() && (args.from_tar.is_some() || git_source.is_some()) {
+        anyhow::bail!("--extra-checksum is only supported when scanning a directory");
+    }
+
+    let checksum_key = ChecksumKey::load(args.key_file.as_deref(), args.key_env.as_deref())?;
+    let keyed = args.checksum.is_keyed() || args.extra_checksum.iter().any(|a| a.is_keyed());
+    match (keyed, &checksum_key) {
+        (true, None) => anyhow::bail!("keyed checksums need --key-file or --key-env"),
+        (false, Some(_)) => anyhow::bail!(
+            "--key-file and --key-env need a keyed checksum such as blake3_keyed or hmac_sha256"
+        ),
+        (true, Some(_)) if args.from_tar.is_some() || git_source.is_some() => {
+            anyhow::bail!("keyed checksums are only supported when scanning a directory")
+        }
+        _ => {}
+    }
+
+    if let Some(tar_path) = &args.from_tar {
+        return run_tar(tar_path, start, &args, filter);
+    }
+
+    let Some(root) = args.path.clone() else {
+        anyhow::bail!("a directory to snapshot or --from-tar is required");
+    };
+
+    if let Some(source) = git_source {
+        let repo = args.repo.as_deref().unwrap_or(Path::new("."));
+        let result = Snapshot::from_git(repo, &source, args.checksum)
+            .map(|snapshot| with_provenance(snapshot, provenance(&args, None)));
+        return match result {
+            Ok(snapshot) if args.jsonl => render_jsonl(snapshot, &args, filter),
+            result => render_json(result, start, &args, filter.as_ref()),
+        };
+    }
+
     // Build scanner - always uses parallel walking
     let scanner = Scanner::new(ScanOptions {
-        root: args.path.clone(),
+        root,
         checksum_algorithm: args.checksum,
+        extra_checksums: args.extra_checksum.clone(),
+        checksum_key,
+        checksum_cache: args.xattr_cache,
         follow_symlinks: args.follow_symlinks,
         max_depth: args.max_depth,
         exclude_patterns: args.exclude.clone(),
         timeout_ms: args.timeout_ms,
         threads: args.threads,
         normalize_paths: args.normalize_paths,
+        sample_len: args.sample_len,
     });
 
     if args.jsonl {
-        run_jsonl(scanner, start, &args)
+        run_jsonl(scanner, start, &args, filter)
     } else {
-        run_json(scanner, start, &args)
+        run_json(scanner, start, &args, filter)
     }
 }
 
 /// Run in JSON mode (original behavior)
-fn run_json(scanner: Scanner, start: Instant, args: &ToolArgs) -> anyhow::Result<RunResult> {
+fn run_json(
+    scanner: Scanner,
+    start: Instant,
+    args: &ToolArgs,
+    filter: Option<JqFilter>,
+) -> anyhow::Result<RunResult> {
     // Perform scan
-    let result = scanner.scan();
+    let result = scanner
+        .scan()
+        .map(|snapshot| with_provenance(snapshot, provenance(args, Some(&scanner.options))));
+    render_json(result, start, args, filter.as_ref())
+}
+
+/// Build a snapshot from a tar stream (`-` for stdin) instead of walking a directory.
+fn run_tar(
+    tar_path: &Path,
+    start: Instant,
+    args: &ToolArgs,
+    filter: Option<JqFilter>,
+) -> anyhow::Result<RunResult> {
+    let result = if tar_path == Path::new("-") {
+        Snapshot::from_tar_reader(io::stdin().lock(), tar_path, args.checksum)
+    } else {
+        File::open(tar_path)
+            .map_err(ScanError::from)
+            .and_then(|file| Snapshot::from_tar_reader(file, tar_path, args.checksum))
+    };
+    let result = result.map(|snapshot| with_provenance(snapshot, provenance(args, None)));
+
+    match result {
+        Ok(snapshot) if args.jsonl => render_jsonl(snapshot, args, filter),
+        result => render_json(result, start, args, filter.as_ref()),
+    }
+}
+
+/// Wrap a snapshot (or the error that prevented it) in the JSON envelope.
+fn render_json(
+    result: Result<Snapshot, ScanError>,
+    start: Instant,
+    args: &ToolArgs,
+    filter: Option<&JqFilter>,
+) -> anyhow::Result<RunResult> {
     let elapsed = start.elapsed();
 
+    // Run --filter over a snapshot; should the filter fail, its error is reported instead
+    let result = match (result, filter) {
+        (Ok(snapshot), Some(filter)) => match filter_snapshot(&snapshot, filter) {
+            Ok(output) => {
+                return Ok(RunResult {
+                    exit_code: 0,
+                    output: print_or_write(output, args.output.as_deref())?,
+                });
+            }
+            Err(e) => Err(e),
+        },
+        (result, _) => result,
+    };
+
     // Build output with Plumbah annotation
     let envelope = match result {
         Ok(snapshot) => Envelope::Snapshot(snapshot),
@@ -83,7 +189,7 @@ fn run_json(scanner: Scanner, start: Instant, args: &ToolArgs) -> anyhow::Result
                     "galdi_snapshot",
                     env!("CARGO_PKG_VERSION"),
                     true,
-                    false,
+                    args.xattr_cache,
                     true,
                     true,
                     elapsed.as_millis() as u64,
@@ -95,12 +201,7 @@ fn run_json(scanner: Scanner, start: Instant, args: &ToolArgs) -> anyhow::Result
         ),
     };
 
-    // Output to stdout
-    let output = if args.human {
-        format_human(&envelope)?
-    } else {
-        serde_json::to_string_pretty(&envelope)?
-    };
+    let output = emit(&envelope, args)?;
 
     // Compute exit code from envelope
     let code = match envelope {
@@ -124,12 +225,21 @@ fn run_json(scanner: Scanner, start: Instant, args: &ToolArgs) -> anyhow::Result
 }
 
 /// Run in JSONL streaming mode
-fn run_jsonl(scanner: Scanner, _start: Instant, _args: &ToolArgs) -> anyhow::Result<RunResult> {
+fn run_jsonl(
+    scanner: Scanner,
+    _start: Instant,
+    args: &ToolArgs,
+    filter: Option<JqFilter>,
+) -> anyhow::Result<RunResult> {
     use crate::output::StreamingOutput;
-    use std::io;
 
-    let stdout = io::stdout();
-    let mut streaming = StreamingOutput::new(stdout.lock());
+    let mut streaming = StreamingOutput::new(output_writer(args.output.as_deref())?)
+        .with_extra_checksums(scanner.options.extra_checksum_algorithms())
+        .with_checksum_key_id(scanner.options.checksum_key.as_ref().map(ChecksumKey::id))
+        .with_sample_len(scanner.options.recorded_sample_len())
+        .with_filter(filter)
+        .with_provenance(provenance(args, Some(&scanner.options)))
+        .with_mutates(scanner.options.caches_checksums());
 
     // Deterministic flag: false (filesystem can change between runs)
     let deterministic = false;
@@ -159,10 +269,103 @@ fn run_jsonl(scanner: Scanner, _start: Instant, _args: &ToolArgs) -> anyhow::Res
 
     Ok(RunResult {
         exit_code: streaming.exit_code(),
-        output: String::new(), // Output already written to stdout
+        output: String::new(), // Output already written
+    })
+}
+
+/// Write an already built snapshot in JSONL streaming format.
+fn render_jsonl(
+    snapshot: Snapshot,
+    args: &ToolArgs,
+    filter: Option<JqFilter>,
+) -> anyhow::Result<RunResult> {
+    use crate::output::StreamingOutput;
+
+    let mut streaming = StreamingOutput::new(output_writer(args.output.as_deref())?)
+        .with_extra_checksums(snapshot.extra_checksums.clone())
+        .with_checksum_key_id(snapshot.checksum_key_id.clone())
+        .with_sample_len(snapshot.sample_len)
+        .with_filter(filter);
+    if let Some(provenance) = snapshot.plumbah.meta.as_ref().and_then(Provenance::of) {
+        streaming = streaming.with_provenance(provenance);
+    }
+    if let Some(meta) = &snapshot.plumbah.meta {
+        streaming = streaming.with_mutates(meta.mutates);
+    }
+
+    // Carry over the determinism of whatever source produced the snapshot.
+    let deterministic = snapshot
+        .plumbah
+        .meta
+        .as_ref()
+        .is_some_and(|meta| meta.deterministic);
+    streaming.write_head(&snapshot.root, snapshot.checksum_algorithm, deterministic)?;
+    for entry in &snapshot.entries {
+        streaming.write_entry(entry)?;
+    }
+    streaming.write_tail()?;
+
+    Ok(RunResult {
+        exit_code: streaming.exit_code(),
+        output: String::new(),
     })
 }
 
+/// Where, by whom and how the snapshot is taken, with the scan `options` of
+/// a directory.
+fn provenance(args: &ToolArgs, options: Option<&ScanOptions>) -> Provenance {
+    let mut provenance = Provenance::capture()
+        .with_filter(args.filter.clone())
+        .with_labels(args.label.iter().cloned());
+    if let Some(options) = options {
+        provenance = provenance.with_scan(options);
+    }
+    if args.redact {
+        provenance = provenance.redacted();
+    }
+    provenance
+}
+
+/// Record `provenance` in the metadata of `snapshot`.
+fn with_provenance(mut snapshot: Snapshot, provenance: Provenance) -> Snapshot {
+    if let Some(meta) = snapshot.plumbah.meta.as_mut() {
+        provenance.record(meta);
+    }
+    snapshot
+}
+
+/// Render `envelope` for printing, or write it out directly and return
+/// nothing to print: to `--output` when given, and to stdout in the binary
+/// and compressed formats.
+fn emit(envelope: &Envelope, args: &ToolArgs) -> anyhow::Result<String> {
+    if args.human
+        && args.output.is_none()
+        && args.format == Encoding::Json
+        && args.compress == Compression::None
+    {
+        return Ok(format_human(envelope)?);
+    }
+    Ok(render_or_write(
+        envelope,
+        args.output.as_deref(),
+        args.format,
+        args.compress,
+    )?)
+}
+
+/// The outputs of `filter` over `snapshot`, pretty-printed one after the
+/// other like `jq` prints them.
+fn filter_snapshot(snapshot: &Snapshot, filter: &JqFilter) -> Result<String, ScanError> {
+    let document =
+        serde_json::to_value(snapshot).map_err(|err| ScanError::Filter(err.to_string()))?;
+    Ok(filter
+        .apply(document)?
+        .iter()
+        .map(|output| format!("{:#}", output))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
 pub struct RunResult {
     pub exit_code: i32,
     pub output: String,
@@ -13,7 +13,7 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use galdi_core::ChecksumAlgorithm;
+use galdi_core::{ChecksumAlgorithm, Compression, DEFAULT_SAMPLE_LEN, Encoding, parse_label};
 
 #[derive(Parser, Debug)]
 #[command(name = "galdi_snapshot")]
@@ -22,8 +22,19 @@ use galdi_core::ChecksumAlgorithm;
     long_about = None
 )]
 pub struct ToolArgs {
-    /// Directory to snapshot (required).
-    pub path: PathBuf,
+    /// Directory to snapshot, or a git object such as `git:HEAD` or `git:v1.2.0:subdir`
+    /// (required unless `--from-tar` is given).
+    #[arg(required_unless_present = "from_tar")]
+    pub path: Option<PathBuf>,
+
+    /// Build the snapshot from a tar stream instead of a directory (`-` for stdin).
+    /// Nothing is extracted to disk.
+    #[arg(long, value_name = "TAR", conflicts_with = "path")]
+    pub from_tar: Option<PathBuf>,
+
+    /// Repository that a `git:` path is read from; the current directory's if omitted.
+    #[arg(long, value_name = "DIR")]
+    pub repo: Option<PathBuf>,
 
     /// Write snapshot to this file; stdout if omitted.
     #[arg(short, long)]
@@ -34,6 +45,30 @@ pub struct ToolArgs {
     #[arg(long, default_value = "xxh3_64")]
     pub checksum: ChecksumAlgorithm,
 
+    /// Additional checksum algorithms computed in the same read, e.g.
+    /// `--extra-checksum sha256`. Stored per entry under `checksums`.
+    #[arg(long, value_name = "ALGORITHM", value_delimiter = ',')]
+    pub extra_checksum: Vec<ChecksumAlgorithm>,
+
+    /// Bytes hashed from each of the start, middle and end of a file by
+    /// sampled algorithms (`xxh3_64s`).
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_SAMPLE_LEN,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    pub sample_len: u64,
+
+    /// Read the key for keyed checksums (`blake3_keyed`, `hmac_sha256`) from this file.
+    #[arg(long, value_name = "PATH", conflicts_with = "key_env")]
+    pub key_file: Option<PathBuf>,
+
+    /// Read the key for keyed checksums from this environment variable.
+    #[arg(long, value_name = "VAR")]
+    pub key_env: Option<String>,
+
+    /// Reuse checksums cached in `user.galdi.<algorithm>` extended attributes
+    /// while size and mtime are unchanged, and cache fresh ones.
+    #[arg(long)]
+    pub xattr_cache: bool,
+
     /// Follow symbolic links when scanning.
     #[arg(long)]
     pub follow_symlinks: bool,
@@ -63,6 +98,21 @@ pub struct ToolArgs {
     #[arg(long)]
     pub jsonl: bool,
 
+    /// Encode the snapshot as `json`, `cbor` or `msgpack`. Readers recognize
+    /// the encoding by itself.
+    #[arg(long, default_value = "json", conflicts_with_all = ["jsonl", "human"])]
+    pub format: Encoding,
+
+    /// Compress the snapshot with `zstd` or `gzip`.
+    #[arg(long, default_value = "none", conflicts_with_all = ["jsonl", "human"])]
+    pub compress: Compression,
+
+    /// Run this jq expression over the output, in-process: over the whole
+    /// snapshot, or over each entry line with `--jsonl`. Error documents are
+    /// never filtered.
+    #[arg(long, value_name = "EXPR", conflicts_with_all = ["human", "format", "compress"])]
+    pub filter: Option<String>,
+
     /// Number of threads for parallel scanning (default: auto-detect).
     #[arg(long)]
     pub threads: Option<usize>,
@@ -71,6 +121,16 @@ pub struct ToolArgs {
     #[arg(long)]
     pub normalize_paths: bool,
 
+    /// Record a `key=value` label in `$plumbah.meta.scan_provenance`,
+    /// alongside the host, user, command line and scan options. May be repeated.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_label)]
+    pub label: Vec<(String, String)>,
+
+    /// Leave the hostname, username and command line out of the provenance,
+    /// for snapshots that are shared.
+    #[arg(long)]
+    pub redact: bool,
+
     /// Return only Plumbah metadata (dry-run for semantic introspection).
     #[arg(long)]
     pub plumbah_info: bool,
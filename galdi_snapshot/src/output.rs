@@ -9,16 +9,26 @@ use std::time::Instant;
 
 use chrono::Utc;
 use galdi_core::{
-    ChecksumAlgorithm, Meta, PlumbahError, PlumbahObject, ProfileMetadata, SnapshotEntry, Status,
-    StreamSummary,
+    ChecksumAlgorithm, JqFilter, Meta, PlumbahError, PlumbahObject, ProfileMetadata, Provenance,
+    SNAPSHOT_VERSION, SnapshotEntry, Status, StreamError, StreamErrors, StreamHead, StreamSummary,
+    StreamTail,
 };
 
 /// Handler for streaming JSONL output
 pub struct StreamingOutput<W: Write> {
     writer: W,
     start_time: Instant,
+    /// Middle lines written for entries.
     total_entries: usize,
+    /// Entries passed to `write_entry`, whether or not `filter` kept them.
+    processed: usize,
     error_count: usize,
+    extra_checksums: Vec<ChecksumAlgorithm>,
+    checksum_key_id: Option<String>,
+    sample_len: Option<u64>,
+    filter: Option<JqFilter>,
+    provenance: Option<Provenance>,
+    mutates: bool,
 }
 
 impl<W: Write> StreamingOutput<W> {
@@ -28,10 +38,55 @@ impl<W: Write> StreamingOutput<W> {
             writer,
             start_time: Instant::now(),
             total_entries: 0,
+            processed: 0,
             error_count: 0,
+            extra_checksums: Vec::new(),
+            checksum_key_id: None,
+            sample_len: None,
+            filter: None,
+            provenance: None,
+            mutates: false,
         }
     }
 
+    /// Record additional checksum algorithms in the head line.
+    pub fn with_extra_checksums(mut self, extra_checksums: Vec<ChecksumAlgorithm>) -> Self {
+        self.extra_checksums = extra_checksums;
+        self
+    }
+
+    /// Record the identity of the checksum key in the head line.
+    pub fn with_checksum_key_id(mut self, checksum_key_id: Option<String>) -> Self {
+        self.checksum_key_id = checksum_key_id;
+        self
+    }
+
+    /// Record the sample length of sampled checksums in the head line.
+    pub fn with_sample_len(mut self, sample_len: Option<u64>) -> Self {
+        self.sample_len = sample_len;
+        self
+    }
+
+    /// Run each entry through a jq filter, writing one line per output
+    /// instead of the entry. Head, error and tail lines are not filtered.
+    pub fn with_filter(mut self, filter: Option<JqFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Record where and how the snapshot was taken in the head line.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    /// Mark the head line as mutating, as when scanned files get their
+    /// checksums cached in extended attributes.
+    pub fn with_mutates(mut self, mutates: bool) -> Self {
+        self.mutates = mutates;
+        self
+    }
+
     /// Write head line with metadata
     pub fn write_head(
         &mut self,
@@ -39,9 +94,9 @@ impl<W: Write> StreamingOutput<W> {
         checksum: ChecksumAlgorithm,
         deterministic: bool,
     ) -> io::Result<()> {
-        let meta = Meta {
+        let mut meta = Meta {
             idempotent: true,
-            mutates: false,
+            mutates: self.mutates,
             safe: true,
             deterministic, // false if --no-sort, false otherwise (filesystem changes)
             plumbah_level: 2,
@@ -55,9 +110,12 @@ impl<W: Write> StreamingOutput<W> {
                 data: HashMap::new(),
             }]),
         };
+        if let Some(provenance) = &self.provenance {
+            provenance.record(&mut meta);
+        }
 
-        let head = serde_json::json!({
-            "$plumbah": PlumbahObject {
+        let head = StreamHead {
+            plumbah: PlumbahObject {
                 version: "1.0".to_string(),
                 stream: Some("head".to_string()),
                 status: Status::Ok,
@@ -66,10 +124,13 @@ impl<W: Write> StreamingOutput<W> {
                 summary: None,
                 execution_time_ms: None,
             },
-            "version": "1.0",
-            "root": root,
-            "checksum_algorithm": checksum,
-        });
+            version: SNAPSHOT_VERSION.to_string(),
+            root: root.to_path_buf(),
+            checksum_algorithm: checksum,
+            extra_checksums: self.extra_checksums.clone(),
+            checksum_key_id: self.checksum_key_id.clone(),
+            sample_len: self.sample_len,
+        };
 
         writeln!(self.writer, "{}", serde_json::to_string(&head)?)?;
         self.writer.flush()?;
@@ -77,20 +138,41 @@ impl<W: Write> StreamingOutput<W> {
     }
 
     /// Write data entry (middle line - no $plumbah)
+    ///
+    /// With a filter, its outputs are written instead, and an error raised by
+    /// the filter is written as an error line for the entry.
     pub fn write_entry(&mut self, entry: &SnapshotEntry) -> io::Result<()> {
-        writeln!(self.writer, "{}", serde_json::to_string(entry)?)?;
-        self.writer.flush()?;
-        self.total_entries += 1;
+        self.processed += 1;
+        let Some(filter) = &self.filter else {
+            writeln!(self.writer, "{}", serde_json::to_string(entry)?)?;
+            self.writer.flush()?;
+            self.total_entries += 1;
+            return Ok(());
+        };
+
+        match filter.apply(serde_json::to_value(entry)?) {
+            Ok(outputs) => {
+                for output in &outputs {
+                    writeln!(self.writer, "{}", output)?;
+                }
+                self.writer.flush()?;
+                self.total_entries += outputs.len();
+            }
+            Err(error) => self.write_error(&PlumbahError {
+                path: Some(entry.path.clone()),
+                ..error.to_plumbah_error()
+            })?,
+        }
         Ok(())
     }
 
     /// Write error line (middle line with $plumbah.errors)
     pub fn write_error(&mut self, error: &PlumbahError) -> io::Result<()> {
-        let error_line = serde_json::json!({
-            "$plumbah": {
-                "errors": vec![error],
-            }
-        });
+        let error_line = StreamError {
+            plumbah: StreamErrors {
+                errors: vec![error.clone()],
+            },
+        };
         writeln!(self.writer, "{}", serde_json::to_string(&error_line)?)?;
         self.writer.flush()?;
         self.error_count += 1;
@@ -105,8 +187,8 @@ impl<W: Write> StreamingOutput<W> {
             Status::Ok
         };
 
-        let tail = serde_json::json!({
-            "$plumbah": PlumbahObject {
+        let tail = StreamTail {
+            plumbah: PlumbahObject {
                 version: "1.0".to_string(),
                 stream: Some("tail".to_string()),
                 status,
@@ -114,12 +196,12 @@ impl<W: Write> StreamingOutput<W> {
                 errors: None,
                 summary: Some(StreamSummary {
                     total: self.total_entries,
-                    processed: self.total_entries,
+                    processed: self.processed,
                     errors: self.error_count,
                 }),
                 execution_time_ms: Some(self.start_time.elapsed().as_millis() as u64),
-            }
-        });
+            },
+        };
 
         writeln!(self.writer, "{}", serde_json::to_string(&tail)?)?;
         self.writer.flush()?;
@@ -7,7 +7,7 @@
 //! - Summary counts are accurate
 
 use chrono::Utc;
-use galdi_core::{ChecksumAlgorithm, EntryType, SnapshotEntry};
+use galdi_core::{ChecksumAlgorithm, EntryType, SNAPSHOT_VERSION, SnapshotEntry};
 use proptest::prelude::*;
 use std::path::PathBuf;
 
@@ -33,11 +33,16 @@ fn arbitrary_entry() -> impl Strategy<Value = SnapshotEntry> {
             entry_type,
             size: Some(size),
             mode,
+            uid: None,
+            gid: None,
             mtime: Utc::now(),
             checksum: match entry_type {
                 EntryType::File => Some(format!("xxh3_64:{:016x}", size % 0xFFFFFFFF)),
                 _ => None,
             },
+            checksums: Default::default(),
+            content_type: None,
+            elf: None,
             target: match entry_type {
                 EntryType::Symlink => Some(PathBuf::from("target.txt")),
                 _ => None,
@@ -80,8 +85,13 @@ proptest! {
                 entry_type: EntryType::File,
                 size: Some(100),
                 mode: Some("644".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: Some(format!("xxh3_64:{:016x}", i)),
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             })
             .collect();
@@ -129,8 +139,13 @@ proptest! {
                 entry_type: EntryType::File,
                 size: Some(100),
                 mode: Some("644".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: Some(format!("xxh3_64:{:016x}", i)),
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             })
             .collect();
@@ -171,8 +186,13 @@ proptest! {
                 entry_type: EntryType::File,
                 size: Some(100),
                 mode: Some("644".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: Some(format!("xxh3_64:{:016x}", i)),
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             })
             .collect();
@@ -197,7 +217,7 @@ proptest! {
         prop_assert_eq!(head["$plumbah"]["status"].as_str(), Some("ok"));
         prop_assert!(head["$plumbah"]["meta"].is_object());
         prop_assert_eq!(head["$plumbah"]["meta"]["profiles"][0]["name"].as_str(), Some("streaming"));
-        prop_assert_eq!(head["version"].as_str(), Some("1.0"));
+        prop_assert_eq!(head["version"].as_str(), Some(SNAPSHOT_VERSION));
         prop_assert_eq!(head["checksum_algorithm"].as_str(), Some("xxh3_64"));
     }
 
@@ -212,8 +232,13 @@ proptest! {
                 entry_type: EntryType::File,
                 size: Some(100),
                 mode: Some("644".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: Some(format!("xxh3_64:{:016x}", i)),
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             })
             .collect();
@@ -253,8 +278,13 @@ proptest! {
                 entry_type: EntryType::File,
                 size: Some(100),
                 mode: Some("644".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: Some(format!("xxh3_64:{:016x}", i)),
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             })
             .collect();
@@ -298,8 +328,13 @@ proptest! {
                 entry_type: EntryType::File,
                 size: Some(100),
                 mode: Some("644".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: Some(format!("xxh3_64:{:016x}", i)),
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             })
             .collect();
@@ -341,8 +376,13 @@ proptest! {
                 entry_type: EntryType::File,
                 size: Some(100),
                 mode: Some("644".to_string()),
+                uid: None,
+                gid: None,
                 mtime: Utc::now(),
                 checksum: Some(format!("xxh3_64:{:016x}", i)),
+                checksums: Default::default(),
+                content_type: None,
+                elf: None,
                 target: None,
             })
             .collect();
@@ -413,8 +453,13 @@ fn test_jsonl_single_entry() {
             entry_type: EntryType::File,
             size: Some(100),
             mode: Some("644".to_string()),
+            uid: None,
+            gid: None,
             mtime: Utc::now(),
             checksum: Some("xxh3_64:abc123".to_string()),
+            checksums: Default::default(),
+            content_type: None,
+            elf: None,
             target: None,
         };
         output.write_entry(&entry).unwrap();
@@ -476,3 +521,129 @@ fn test_jsonl_with_errors() {
     assert_eq!(tail["$plumbah"]["summary"]["errors"], 1);
     assert_eq!(tail["$plumbah"]["status"], "partial");
 }
+
+#[test]
+fn test_jsonl_reads_back_into_snapshot() {
+    use galdi_core::{PlumbahError, Snapshot};
+    use galdi_snapshot::output::StreamingOutput;
+
+    let mut buf = Vec::new();
+    let entry = SnapshotEntry {
+        path: PathBuf::from("kept.txt"),
+        entry_type: EntryType::File,
+        size: Some(4),
+        mode: Some("644".to_string()),
+        uid: None,
+        gid: None,
+        mtime: Utc::now(),
+        checksum: Some("xxh3_64:abc123".to_string()),
+        checksums: Default::default(),
+        content_type: None,
+        elf: None,
+        target: None,
+    };
+    {
+        let mut output = StreamingOutput::new(&mut buf);
+        output
+            .write_head(&PathBuf::from("/test"), ChecksumAlgorithm::XXH3_64, false)
+            .unwrap();
+        output.write_entry(&entry).unwrap();
+        output
+            .write_error(&PlumbahError {
+                code: "PERMISSION_DENIED".to_string(),
+                message: "Permission denied".to_string(),
+                path: Some(PathBuf::from("/test/secret")),
+                recoverable: true,
+                context: None,
+            })
+            .unwrap();
+        output.write_tail().unwrap();
+    }
+
+    let snapshot = Snapshot::from_jsonl(buf.as_slice()).unwrap();
+    assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+    assert_eq!(snapshot.root, PathBuf::from("/test"));
+    assert_eq!(snapshot.count, 1);
+    assert_eq!(snapshot.entries[0].path, entry.path);
+    assert_eq!(snapshot.entries[0].checksum, entry.checksum);
+    let errors = snapshot.plumbah.errors.unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code, "PERMISSION_DENIED");
+}
+
+#[test]
+fn test_jsonl_filtered_entries() {
+    use galdi_core::JqFilter;
+    use galdi_snapshot::output::StreamingOutput;
+
+    let entry = |path: &str, size: u64| SnapshotEntry {
+        path: PathBuf::from(path),
+        entry_type: EntryType::File,
+        size: Some(size),
+        mode: None,
+        uid: None,
+        gid: None,
+        mtime: Utc::now(),
+        checksum: None,
+        checksums: Default::default(),
+        content_type: None,
+        elf: None,
+        target: None,
+    };
+    let filter = JqFilter::new(
+        "select(.size > 10) | .path, if .size > 100 then error(\"too big\") else empty end",
+    )
+    .unwrap();
+
+    let mut buf = Vec::new();
+    {
+        let mut output = StreamingOutput::new(&mut buf).with_filter(Some(filter));
+        output
+            .write_head(&PathBuf::from("/test"), ChecksumAlgorithm::XXH3_64, false)
+            .unwrap();
+        output.write_entry(&entry("small.txt", 1)).unwrap();
+        output.write_entry(&entry("large.txt", 50)).unwrap();
+        output.write_entry(&entry("huge.txt", 500)).unwrap();
+        output.write_tail().unwrap();
+    }
+
+    let (head, middle, tail) = parse_jsonl(&String::from_utf8(buf).unwrap());
+    assert_eq!(head["$plumbah"]["stream"], "head");
+    // An entry the filter fails on gets an error line instead of any output
+    assert_eq!(middle.len(), 2);
+    assert_eq!(middle[0], "large.txt");
+    assert_eq!(middle[1]["$plumbah"]["errors"][0]["code"], "FILTER_ERROR");
+    assert_eq!(middle[1]["$plumbah"]["errors"][0]["path"], "huge.txt");
+    assert_eq!(tail["$plumbah"]["summary"]["total"], 1);
+    assert_eq!(tail["$plumbah"]["summary"]["processed"], 3);
+    assert_eq!(tail["$plumbah"]["summary"]["errors"], 1);
+}
+
+#[test]
+fn test_jsonl_head_records_provenance() {
+    use galdi_core::{Provenance, StreamHead};
+    use galdi_snapshot::output::StreamingOutput;
+
+    let provenance =
+        Provenance::capture().with_labels([("ticket".to_string(), "OPS-42".to_string())]);
+
+    let mut buf = Vec::new();
+    {
+        let mut output = StreamingOutput::new(&mut buf).with_provenance(provenance.clone());
+        output
+            .write_head(&PathBuf::from("/test"), ChecksumAlgorithm::XXH3_64, false)
+            .unwrap();
+        output.write_tail().unwrap();
+    }
+
+    let output = String::from_utf8(buf).unwrap();
+    let (head, _, _) = parse_jsonl(&output);
+    assert_eq!(
+        head["$plumbah"]["meta"]["scan_provenance"]["labels"]["ticket"],
+        "OPS-42"
+    );
+
+    let head: StreamHead = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+    let meta = head.plumbah.meta.unwrap();
+    assert_eq!(Provenance::of(&meta), Some(provenance));
+}